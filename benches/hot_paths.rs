@@ -0,0 +1,172 @@
+/*
+Benchmarks of the hot paths of the renderer:
+- Ray against bounding box
+- Ray against a single triangle
+- Ray against the Bvh of the bunny
+- Full render of a small scene
+
+Everything is built from fixed seeds, so that two runs measure the same work.
+Run with: cargo bench
+*/
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use raytracing2::hittable::*;
+use raytracing2::material::*;
+use raytracing2::utility::*;
+use raytracing2::bvh::*;
+use raytracing2::texture::*;
+use raytracing2::render::*;
+use raytracing2::randomness::*;
+use raytracing2::image::*;
+use raytracing2::mesh::*;
+use raytracing2::light::LightList;
+
+const NUM_RAYS: usize = 1024;
+const SEED: u64 = 42;
+
+/// Rays starting on a sphere around the target and aiming at random points close to it,
+/// so that a good part of them hit whatever stands there
+fn rays_toward(target: &Rvec3, distance: Real, spread: Real) -> Vec<Ray> {
+    let mut rng = Randomizer::seed_from_u64(SEED);
+    (0..NUM_RAYS).map(|_| {
+        let origin = target + distance * rng.sample(UnitSphere);
+        let aim = target + spread * rng.sample(UnitBall);
+        Ray {
+            origin,
+            direction: (aim - origin).normalize(),
+            t_min: RAY_EPSILON,
+            t_max: Real::INFINITY,
+            wavelength: None,
+            differentials: None,
+        }
+    }).collect()
+}
+
+fn aabb_collide(c: &mut Criterion) {
+    let aabb = AABB {min: vector![-1.0, -1.0, -1.0], max: vector![1.0, 1.0, 1.0]};
+    let rays: Vec<_> = rays_toward(&vector![0.0, 0.0, 0.0], 5.0, 2.0).into_iter().map(Ray::expand).collect();
+
+    let mut group = c.benchmark_group("aabb_collide");
+    group.throughput(Throughput::Elements(rays.len() as u64));
+    group.bench_function("unit_box", |b| b.iter(|| {
+        rays.iter().filter(|ray| black_box(&aabb).collide(ray)).count()
+    }));
+    group.finish();
+}
+
+fn triangle_hit(c: &mut Criterion) {
+    let normal = vector![1.0, 1.0, 1.0].normalize();
+    let mesh = Mesh::new(
+        vec![
+            Vertex {position: vector![1.0, 0.0, 0.0], normal, uv: vector![0.0, 0.0], color: WHITE},
+            Vertex {position: vector![0.0, 1.0, 0.0], normal, uv: vector![1.0, 0.0], color: WHITE},
+            Vertex {position: vector![0.0, 0.0, 1.0], normal, uv: vector![0.0, 1.0], color: WHITE},
+        ],
+        vec![0, 1, 2],
+        MaterialId(0)
+    );
+    let scene_data = SceneData {mesh_table: vec![mesh], ..SceneData::empty()};
+    let triangle = Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(0)};
+    let rays = rays_toward(&vector![1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0], 3.0, 1.0);
+
+    let mut group = c.benchmark_group("triangle_hit");
+    group.throughput(Throughput::Elements(rays.len() as u64));
+    group.bench_function("single", |b| b.iter(|| {
+        rays.iter().filter(|ray| black_box(&triangle).hit(ray, &scene_data).is_some()).count()
+    }));
+    group.finish();
+}
+
+fn bunny_bvh(c: &mut Criterion) {
+    let bunny = obj::load("assets/bunny.obj").unwrap();
+    let scene_data = SceneData {mesh_table: vec![bunny], ..SceneData::empty()};
+    let bvh = Hittable::TriangleBvh(TriangleBvh::from_mesh(MeshId(0), &scene_data));
+    let aabb = bvh.bounding_box(&scene_data);
+    let center = 0.5 * (aabb.min + aabb.max);
+    let size = (aabb.max - aabb.min).norm();
+    let rays = rays_toward(&center, 2.0 * size, 0.25 * size);
+
+    let mut group = c.benchmark_group("bunny_bvh");
+    group.throughput(Throughput::Elements(rays.len() as u64));
+    group.bench_function("traversal", |b| b.iter(|| {
+        rays.iter().filter(|ray| black_box(&bvh).hit(ray, &scene_data).is_some()).count()
+    }));
+    group.finish();
+}
+
+/// A few balls of each material under a glowing one, small enough to be rendered many times
+fn small_scene() -> (Hittable, SceneData, Emit, Camera) {
+    let texture_table = vec![
+        Texture::Checker {odd: TextureId(1), even: TextureId(2), space: TextureSpace::Position},
+        Texture::Solid(rgb(0.9, 0.9, 0.9)),
+        Texture::Solid(rgb(0.2, 0.3, 0.1)),
+    ];
+
+    let material_table = vec![
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::AlbedoMap(TextureId(0)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.1, 0.2, 0.5)), Emit::None),
+        Material::new(Scatter::Dielectric {refraction_index: 1.5, abbe_number: None}, Absorb::WhiteBody, Emit::None),
+        Material::new(Scatter::Metal {fuzziness: 0.1}, Absorb::Albedo(rgb(0.8, 0.6, 0.2)), Emit::None),
+        Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(4.0, 4.0, 4.0))),
+    ];
+
+    let mut root = Hittable::List(vec![
+        Hittable::Sphere {center: vector![0.0, -100.5, -1.0], radius: 100.0, material: MaterialId(0)}, // Ground
+        Hittable::Sphere {center: vector![0.0, 0.0, -1.0], radius: 0.5, material: MaterialId(1)},
+        Hittable::Sphere {center: vector![-1.0, 0.0, -1.0], radius: 0.5, material: MaterialId(2)},
+        Hittable::Sphere {center: vector![1.0, 0.0, -1.0], radius: 0.5, material: MaterialId(3)},
+        Hittable::Sphere {center: vector![0.0, 2.0, -1.0], radius: 0.5, material: MaterialId(4)}, // Light
+    ]);
+
+    let scene_data = SceneData {material_table, texture_table, ..SceneData::empty()};
+    root.build_acceleration(&scene_data);
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_2,
+        focus_distance: 3.46,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![-2.0, 2.0, 1.0],
+            &vector![0.0, 0.0, -1.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+    (root, scene_data, Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0)), camera)
+}
+
+fn small_scene_render(c: &mut Criterion) {
+    let (root, scene_data, background, camera) = small_scene();
+    let camera = camera.prepare();
+    let lights = LightList::new(&root, &scene_data);
+    let sampler = Multisampler {width: 32, height: 32, num_samples: 4, filter: Filter::Box};
+    let max_bounce = 8;
+
+    let mut group = c.benchmark_group("small_scene_render");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((sampler.width * sampler.height * sampler.num_samples) as u64));
+    group.bench_function("32x32_4spp", |b| b.iter(|| {
+        // Same seed at each iteration, so that every iteration traces the same paths
+        let mut rng = Randomizer::seed_from_u64(SEED);
+        let mut image = Array2d::<Color>::new(sampler.width, sampler.height);
+        for j in 0..sampler.height {
+            for i in 0..sampler.width {
+                let samples = sampler.make_uv_jitter(i, j, &mut rng);
+                let mut color = rgb(0.0, 0.0, 0.0);
+                for (uv, _, lens) in samples {
+                    let ray = camera.shoot(uv, lens, &mut rng);
+                    let trace_out = trace_path(
+                        &root, &ray, max_bounce, &scene_data, &mut rng, &background, &lights, None, 0.0
+                    );
+                    color += trace_out.final_color;
+                }
+                *image.get_mut(i, j) = color / sampler.num_samples as Real;
+            }
+        }
+        image
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, aabb_collide, triangle_hit, bunny_bvh, small_scene_render);
+criterion_main!(benches);
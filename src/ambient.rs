@@ -0,0 +1,84 @@
+/*
+In this file:
+- Ambient light = the irradiance of the background, projected on spherical harmonics
+*/
+
+use crate::utility::*;
+use crate::randomness::*;
+use crate::material::Emit;
+use crate::render::SceneData;
+
+// ------------------------------------------- Ambient light -------------------------------------------
+
+/// Number of directions used to project the background
+const NUM_PROJECTION_SAMPLES: usize = 16384;
+
+/// The light of the background seen by a diffuse surface, depending only on its normal.
+/// It ignores the occlusion by the scene and the light bounced by other objects, so it is only fit for previews.
+/// See Ramamoorthi and Hanrahan, An Efficient Representation for Irradiance Environment Maps.
+#[derive(Debug, Clone)]
+pub struct Ambient {
+    /// Coefficients of the first 3 bands of real spherical harmonics, ordered like in sh_basis
+    coefficients: [Color; 9],
+}
+
+/// The real spherical harmonics of bands 0, 1 and 2 for a unit direction
+fn sh_basis(d: &Rvec3) -> [Real; 9] {
+    [
+        0.282095,
+        0.488603 * d.y,
+        0.488603 * d.z,
+        0.488603 * d.x,
+        1.092548 * d.x * d.y,
+        1.092548 * d.y * d.z,
+        0.315392 * (3.0 * d.z * d.z - 1.0),
+        1.092548 * d.x * d.z,
+        0.546274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+/// The k-th of n points evenly spread on the unit sphere, which converges faster than random directions
+fn fibonacci_sphere(k: usize, n: usize) -> Rvec3 {
+    let golden_angle = PI * (3.0 - (5.0 as Real).sqrt());
+    let z = 1.0 - (2 * k + 1) as Real / n as Real;
+    let r = (1.0 - z * z).sqrt();
+    let (sin_phi, cos_phi) = (golden_angle * k as Real).sin_cos();
+    vector![r * cos_phi, r * sin_phi, z]
+}
+
+/// Convolution of each band with the clamped cosine, which turns radiance into irradiance
+const COSINE_BANDS: [Real; 9] = [
+    PI,
+    2.0 * FRAC_PI_3, 2.0 * FRAC_PI_3, 2.0 * FRAC_PI_3,
+    FRAC_PI_4, FRAC_PI_4, FRAC_PI_4, FRAC_PI_4, FRAC_PI_4,
+];
+
+impl Ambient {
+    /// Project the background on the spherical harmonics by sampling it in many directions.
+    /// The random numbers are only used by the background itself, like a noise texture.
+    pub fn from_background(background: &Emit, scene_data: &SceneData, rng: &mut Randomizer) -> Self {
+        let mut coefficients = [rgb(0.0, 0.0, 0.0); 9];
+        for k in 0..NUM_PROJECTION_SAMPLES {
+            let direction = fibonacci_sphere(k, NUM_PROJECTION_SAMPLES);
+            let ray = Ray {
+                origin: Rvec3::zeros(), direction, t_min: 0.0, t_max: Real::INFINITY,
+                wavelength: None, differentials: None,
+            };
+            let radiance = background.evaluate(&ray, &Hit::at_infinity(&direction), scene_data, rng);
+            for (coefficient, y) in coefficients.iter_mut().zip(sh_basis(&direction)) {
+                *coefficient += y * radiance;
+            }
+        }
+        // Each direction stands for an equal part of the 4 pi steradians of the sphere
+        let weight = 2.0 * TAU / NUM_PROJECTION_SAMPLES as Real;
+        Ambient {coefficients: coefficients.map(|c| weight * c)}
+    }
+
+    /// Light received by a surface with this unit normal, integrated over the hemisphere and weighted by the cosine
+    pub fn irradiance(&self, normal: &Rvec3) -> Color {
+        let irradiance = self.coefficients.iter().zip(COSINE_BANDS).zip(sh_basis(normal))
+            .fold(rgb(0.0, 0.0, 0.0), |sum, ((coefficient, band), y)| sum + band * y * coefficient);
+        // The truncated series can ring below zero in front of a very dark part of the background
+        irradiance.map(|x| x.max(0.0))
+    }
+}
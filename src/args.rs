@@ -0,0 +1,117 @@
+use raytracing2::utility::*;
+use std::error::Error;
+
+pub const USAGE: &str = "\
+Usage: raytracing2 [options]
+    --frames N          Number of frames to render, saved as output_0000.tga, output_0001.tga... (default 1)
+    --start-time T      Time of the first frame (default 0)
+    --end-time T        Time of the last frame (default 1)
+    --seed S            Seed of the random numbers, to render the same image twice (default random)
+    --quiet             Print nothing but the progress bar
+    --progress T        Layout of the progress bar, with the template keys of indicatif and {msg} for the
+                        throughput (default: bar, percentage, elapsed time, time left and throughput)
+    --verbose           Print the time taken by each stage
+    --open              Open the output in the default image viewer
+    --fast              Light the diffuse surfaces with an approximation of the background instead of following
+                        the light bounces: much faster, but without shadows from the sky nor indirect light
+    --min-roughness R   Make the metals and the GGX at least this rough after the first bounce: fewer fireflies
+                        from the caustics, but they and the reflections of reflections are blurred (default off)
+    --preview           Show the render in a window as the tiles complete, closing it stops the render and saves
+                        what is done (needs the preview feature, and a single pass)
+    --passes N          Render N passes one after the other, saving their sum in output.checkpoint after each of
+                        them, so that a long render can be resumed (default 1, without a checkpoint)
+    --resume            Start from the passes saved in the checkpoint of each frame, if any, and only render the
+                        missing ones: run the same command again with --resume after a crash. Give the same seed
+                        again to get the same image as if the render had never stopped.";
+
+/// How much is printed while rendering, from the least to the most
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Command line options
+#[derive(Debug, Clone)]
+pub struct Args {
+    pub frames: u32,
+    pub start_time: Real,
+    pub end_time: Real,
+    pub seed: Option<u64>,
+    pub verbosity: Verbosity,
+    pub open: bool,
+    pub preview: bool,
+    pub fast: bool,
+    pub min_roughness: Option<Real>,
+    pub progress_template: Option<String>,
+    pub passes: u32,
+    pub resume: bool,
+}
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, Box<dyn Error>> {
+        let mut parsed = Args {
+            frames: 1, start_time: 0.0, end_time: 1.0, seed: None, verbosity: Verbosity::Normal, open: false,
+            preview: false, fast: false, min_roughness: None, progress_template: None, passes: 1, resume: false,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("Missing value after {}", arg));
+            match arg.as_str() {
+                "--frames" => parsed.frames = value()?.parse()?,
+                "--start-time" => parsed.start_time = value()?.parse()?,
+                "--end-time" => parsed.end_time = value()?.parse()?,
+                "--seed" => parsed.seed = Some(value()?.parse()?),
+                "--quiet" => parsed.verbosity = Verbosity::Quiet,
+                "--verbose" => parsed.verbosity = Verbosity::Verbose,
+                "--open" => parsed.open = true,
+                "--fast" => parsed.fast = true,
+                "--min-roughness" => parsed.min_roughness = Some(value()?.parse()?),
+                "--progress" => parsed.progress_template = Some(value()?),
+                "--passes" => parsed.passes = value()?.parse()?,
+                "--resume" => parsed.resume = true,
+                "--preview" if cfg!(feature = "preview") => parsed.preview = true,
+                "--preview" => return Err("This build has no preview window, build it with --features preview".into()),
+                _ => return Err(format!("Unknown argument {}", arg).into()),
+            }
+        }
+        if parsed.frames == 0 {
+            return Err("There must be at least one frame".into())
+        }
+        if parsed.passes == 0 {
+            return Err("There must be at least one pass".into())
+        }
+        if parsed.preview && parsed.progressive() {
+            return Err("The preview cannot show a render in several passes".into())
+        }
+        Ok(parsed)
+    }
+
+    /// The frames are evenly spread from the start time to the end time, both included
+    pub fn frame_time(&self, frame: u32) -> Real {
+        if self.frames == 1 {
+            self.start_time
+        } else {
+            self.start_time + (self.end_time - self.start_time) * frame as Real / (self.frames - 1) as Real
+        }
+    }
+
+    /// Whether the frames are rendered in passes, with a checkpoint saved after each of them
+    pub fn progressive(&self) -> bool {
+        self.passes > 1 || self.resume
+    }
+
+    /// The checkpoint of a frame is saved next to it
+    pub fn checkpoint_name(&self, frame: u32) -> String {
+        self.frame_name(frame).replace(".tga", ".checkpoint")
+    }
+
+    /// A single frame keeps the historical name
+    pub fn frame_name(&self, frame: u32) -> String {
+        if self.frames == 1 {
+            "output.tga".to_string()
+        } else {
+            format!("output_{:04}.tga", frame)
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::utility::*;
-use crate::hittable::Hittable;
+use crate::hittable::{Hittable, hit_triangle, bounding_box_triangle};
 use crate::material::MaterialId;
+use crate::mesh::{MeshId, TriangleId};
 use crate::render::SceneData;
 
 // ------------------------------------------- Bounding volume hieracrchy -------------------------------------------
@@ -8,71 +9,125 @@ use crate::render::SceneData;
 type NodeId = u32;
 type LeafId = u32;
 
+/// The bounding boxes of both children are stored in their parent, so that they can be tested together
 #[derive(Debug, Clone)]
 enum BvhNode {
-    Branch {aabb: AABB, left: NodeId, right: NodeId},
-    Leaf {aabb: AABB, leaf: LeafId},
+    Branch {children: AabbPair, left: NodeId, right: NodeId},
+    Leaf {leaf: LeafId},
 }
 
-impl BvhNode {
-    fn bounding_box(&self) -> &AABB {
-        match self {
-            Self::Leaf {aabb, ..} => aabb,
-            Self::Branch {aabb, ..} => aabb,
-        }
+/// Anything that can be stored in the leaves of a Bvh
+pub trait BvhLeaf {
+    fn hit(&self, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)>;
+    /// Whether anything is hit between t_min and t_max, which may be answered without finding the closest hit
+    fn hit_any(&self, ray: &Ray, scene_data: &SceneData) -> bool {
+        self.hit(ray, scene_data).is_some()
+    }
+    fn bounding_box(&self, scene_data: &SceneData) -> AABB;
+    /// Number of spheres, capsules and triangles inside
+    fn count_primitives(&self) -> usize;
+}
+
+impl BvhLeaf for Hittable {
+    fn hit(&self, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
+        Hittable::hit(self, ray, scene_data)
+    }
+
+    fn hit_any(&self, ray: &Ray, scene_data: &SceneData) -> bool {
+        Hittable::hit_any(self, ray, scene_data)
+    }
+
+    fn bounding_box(&self, scene_data: &SceneData) -> AABB {
+        Hittable::bounding_box(self, scene_data)
+    }
+
+    fn count_primitives(&self) -> usize {
+        Hittable::count_primitives(self)
+    }
+}
+
+/// Triangles are intersected directly, without going through the Hittable dispatch
+impl BvhLeaf for (MeshId, TriangleId) {
+    fn hit(&self, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
+        hit_triangle(self.1, self.0, ray, scene_data)
+    }
+
+    fn bounding_box(&self, scene_data: &SceneData) -> AABB {
+        bounding_box_triangle(self.1, self.0, scene_data)
+    }
+
+    fn count_primitives(&self) -> usize {
+        1
     }
 }
 
 #[derive(Clone)]
-pub struct Bvh {
+pub struct Bvh<L = Hittable> {
     /// Content of the leaf nodes to be indexed by LeafId
-    leaves: Vec<Hittable>,
-    /// Tree structure to be index by NodeId
+    leaves: Vec<L>,
+    /// Tree structure to be index by NodeId, empty when there are no leaves
     nodes: Vec<BvhNode>,
-    /// Id of the root node
+    /// Id of the root node, unused when there are no nodes
     root: NodeId,
+    /// Bounding box of the root node
+    aabb: AABB,
 }
 
+/// Returns the id of the new node and its bounding box
 fn make_bvh(content: &mut [(LeafId, AABB)], sort_axis: usize, nodes: &mut Vec<BvhNode>) 
-    -> NodeId
+    -> (NodeId, AABB)
 {
     match content.len() {
         0 => unreachable!(),
         1 => {
             let (leaf, aabb) = content[0].clone();
-            nodes.push(BvhNode::Leaf {leaf, aabb});
-            (nodes.len() - 1) as NodeId
+            nodes.push(BvhNode::Leaf {leaf});
+            ((nodes.len() - 1) as NodeId, aabb)
         }
         _ => {
             let (left_content, right_content) = split(content, sort_axis);
-            let left = make_bvh(left_content, (sort_axis + 1) % 3, nodes);
-            let right = make_bvh(right_content, (sort_axis + 1) % 3, nodes);
-            let aabb = nodes[left as usize].bounding_box()
-                .union(nodes[right as usize].bounding_box());
-            nodes.push(BvhNode::Branch {left, right, aabb});
-            (nodes.len() - 1) as NodeId
+            let (left, left_aabb) = make_bvh(left_content, (sort_axis + 1) % 3, nodes);
+            let (right, right_aabb) = make_bvh(right_content, (sort_axis + 1) % 3, nodes);
+            let aabb = left_aabb.union(&right_aabb);
+            let children = AabbPair::new(&left_aabb, &right_aabb);
+            nodes.push(BvhNode::Branch {left, right, children});
+            ((nodes.len() - 1) as NodeId, aabb)
         }
     }
 }
 
 fn split(content: &mut [(LeafId, AABB)], sort_axis: usize) -> (&mut [(LeafId, AABB)], &mut [(LeafId, AABB)]) {
-    // Sort by bounding box centroid
+    // Sort by bounding box centroid, the empty boxes of empty lists (NaN) go last
     content.sort_unstable_by(|(_, x_bb), (_, y_bb)| {
-        let x_center = 0.5 * (x_bb.min[sort_axis] + x_bb.max[sort_axis]);
-        let y_center = 0.5 * (y_bb.min[sort_axis] + y_bb.max[sort_axis]);
-        x_center.partial_cmp(&y_center).unwrap()
+        x_bb.centroid()[sort_axis].total_cmp(&y_bb.centroid()[sort_axis])
     });
     // Split at the median without allocating a new vector
     content.split_at_mut(content.len() / 2)
 }
 
-impl Bvh {
-    pub fn new(hittables: Vec<Hittable>, scene_data: &SceneData) -> Self {
+/// A Bvh specialized for triangle soups, with a tight storage of the leaves
+pub type TriangleBvh = Bvh<(MeshId, TriangleId)>;
+
+impl TriangleBvh {
+    /// Build a Bvh over all the triangles of a mesh
+    pub fn from_mesh(mesh: MeshId, scene_data: &SceneData) -> Self {
+        let triangles = scene_data.mesh_table[mesh.to_index()].iter_triangles().map(|tid| (mesh, tid)).collect();
+        Bvh::new(triangles, scene_data)
+    }
+}
+
+impl<L: BvhLeaf> Bvh<L> {
+    pub fn new(hittables: Vec<L>, scene_data: &SceneData) -> Self {
         let mut content = hittables.iter().enumerate().map(|(id, x)| (id as LeafId, x.bounding_box(scene_data)))
             .collect::<Vec<_>>();
         
+        // Nothing to split, e.g. a mesh whose triangles were all degenerate: there is no tree and nothing is hit
+        if content.is_empty() {
+            return Bvh {leaves: hittables, nodes: Vec::new(), root: 0, aabb: AABB::empty()}
+        }
+
         let mut nodes = Vec::new();
-        let root = make_bvh(&mut content, 0, &mut nodes);
+        let (root, aabb) = make_bvh(&mut content, 0, &mut nodes);
 
         // nodes.iter().enumerate().for_each(|(id, n)| match n {
         //     BvhNode::Leaf {..} => println!("#{}: Leaf ({:?})", id, content[id].1),
@@ -86,40 +141,124 @@ impl Bvh {
         
         Bvh {
             leaves: hittables,
-            nodes, root
+            nodes, root, aabb
         }
     }
 
-    fn hit_node(&self, ray: &RayExpanded, node: NodeId, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
+    /// The ray is shared by the whole traversal, its t_max shrinks to the closest hit found so far
+    fn hit_node(&self, ray: &mut RayExpanded, node: NodeId, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
         match &self.nodes[node as usize] {
-            BvhNode::Leaf {aabb, leaf} => {
-                if aabb.collide(ray) {
-                    self.leaves[*leaf as usize].hit(&ray.inner, scene_data)
-                } else {
-                    None
-                }
-            },
-            BvhNode::Branch {aabb, left, right} => {
-                if aabb.collide(ray) {
-                    let mut hit = None;
-                    let mut ray = ray.clone();
-                    if let Some(new_hit) = self.hit_node(&ray, *left, scene_data) {
-                        ray.inner.t_max = new_hit.0.t;
-                        hit.replace(new_hit);
-                    }
-                    if let Some(new_hit) = self.hit_node(&ray, *right, scene_data) {
-                        hit.replace(new_hit);
+            BvhNode::Leaf {leaf} => self.leaves[*leaf as usize].hit(&ray.inner, scene_data),
+            BvhNode::Branch {children, left, right} => {
+                // Visit the children from near to far, so that the far one can be skipped if it is occluded
+                let [left_t, right_t] = children.collide(ray);
+                let ordered = match (left_t, right_t) {
+                    (Some(l), Some(r)) if r < l => [(*right, right_t), (*left, left_t)],
+                    _ => [(*left, left_t), (*right, right_t)],
+                };
+
+                let mut hit = None;
+                for (child, entry_t) in ordered {
+                    match entry_t {
+                        Some(t) if t <= ray.inner.t_max => {
+                            if let Some(new_hit) = self.hit_node(ray, child, scene_data) {
+                                ray.inner.t_max = new_hit.0.t;
+                                hit.replace(new_hit);
+                            }
+                        }
+                        _ => ()
                     }
-                    hit
-                } else {
-                    None
                 }
+                hit
             },
         }
     }
 
     pub fn hit(&self, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
+        let mut ray = ray.clone().expand();
+        if !self.nodes.is_empty() && self.aabb.collide(&ray) {
+            self.hit_node(&mut ray, self.root, scene_data)
+        } else {
+            None
+        }
+    }
+
+    /// Stops at the first leaf that is hit within the ray, in no particular order
+    fn hit_any_node(&self, ray: &RayExpanded, node: NodeId, scene_data: &SceneData) -> bool {
+        match &self.nodes[node as usize] {
+            BvhNode::Leaf {leaf} => self.leaves[*leaf as usize].hit_any(&ray.inner, scene_data),
+            BvhNode::Branch {children, left, right} => {
+                let [left_t, right_t] = children.collide(ray);
+                [(*left, left_t), (*right, right_t)].iter().any(|&(child, entry_t)| match entry_t {
+                    Some(t) if t <= ray.inner.t_max => self.hit_any_node(ray, child, scene_data),
+                    _ => false,
+                })
+            }
+        }
+    }
+
+    /// Whether anything is hit, for the shadow rays, without looking for the closest hit
+    pub fn hit_any(&self, ray: &Ray, scene_data: &SceneData) -> bool {
         let ray = ray.clone().expand();
-        self.hit_node(&ray, self.root, scene_data)
+        !self.nodes.is_empty() && self.aabb.collide(&ray) && self.hit_any_node(&ray, self.root, scene_data)
+    }
+
+    pub fn bounding_box(&self) -> AABB {
+        self.aabb.clone()
+    }
+
+    pub fn leaves(&self) -> &[L] {
+        &self.leaves
+    }
+
+    /// The leaves can be moved in place, as long as refit is called before the next ray
+    pub fn leaves_mut(&mut self) -> &mut [L] {
+        &mut self.leaves
+    }
+
+    /// Recompute the bounding boxes after the leaves were moved, or the vertices of their meshes were moved with
+    /// Mesh::update_vertices, without splitting again.
+    /// Much cheaper than a new Bvh for the animations, but the tree is only as good as the positions it was built for.
+    /// The leaves must still be in the same order.
+    pub fn refit(&mut self, scene_data: &SceneData) {
+        if !self.nodes.is_empty() {
+            self.aabb = self.refit_node(self.root, scene_data);
+        }
+    }
+
+    /// Returns the bounding box of the node
+    fn refit_node(&mut self, node: NodeId, scene_data: &SceneData) -> AABB {
+        match self.nodes[node as usize] {
+            BvhNode::Leaf {leaf} => self.leaves[leaf as usize].bounding_box(scene_data),
+            BvhNode::Branch {left, right, ..} => {
+                let left_aabb = self.refit_node(left, scene_data);
+                let right_aabb = self.refit_node(right, scene_data);
+                let children = AabbPair::new(&left_aabb, &right_aabb);
+                self.nodes[node as usize] = BvhNode::Branch {children, left, right};
+                left_aabb.union(&right_aabb)
+            }
+        }
+    }
+
+    /// Number of spheres, capsules and triangles in all the leaves
+    pub fn count_primitives(&self) -> usize {
+        self.leaves.iter().map(BvhLeaf::count_primitives).sum()
+    }
+
+    /// Number of branches and leaves of the tree
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of nodes on the longest path from the root to a leaf, both included
+    pub fn max_depth(&self) -> usize {
+        if self.nodes.is_empty() {0} else {self.node_depth(self.root)}
+    }
+
+    fn node_depth(&self, node: NodeId) -> usize {
+        match &self.nodes[node as usize] {
+            BvhNode::Leaf {..} => 1,
+            BvhNode::Branch {left, right, ..} => 1 + self.node_depth(*left).max(self.node_depth(*right)),
+        }
     }
 }
\ No newline at end of file
@@ -1,12 +1,12 @@
-use raytracing2::hittable::*;
-use raytracing2::material::*;
-use raytracing2::utility::*;
-use raytracing2::bvh::*;
-use raytracing2::texture::*;
-use raytracing2::render::*;
-use raytracing2::randomness::*;
-use raytracing2::image::*;
-use raytracing2::mesh::*;
+use crate::hittable::*;
+use crate::material::*;
+use crate::utility::*;
+use crate::bvh::*;
+use crate::texture::*;
+use crate::render::*;
+use crate::randomness::*;
+use crate::image::*;
+use crate::mesh::*;
 
 // TODO: Have a scene verifier that detects missing texture/material and circular references?
 // It would use string ids instead of integers for ease of use and to allow the merging or multiple scenes
@@ -16,6 +16,17 @@ pub struct ExampleScene {
     pub scene_data: SceneData,
     pub root: Hittable,
     pub background: Emit,
+    /// Put the scene in its state at a given time. It runs before the lists are turned into Bvhs, so it should
+    /// rebuild the objects that it moves rather than look for them in the root.
+    pub animation: Option<fn(&mut ExampleScene, Real)>,
+}
+
+impl ExampleScene {
+    pub fn at_time(&mut self, time: Real) {
+        if let Some(animation) = self.animation {
+            animation(self, time);
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -23,8 +34,9 @@ pub fn three_balls() -> ExampleScene {
     let camera = Camera {
         aspect_ratio: 1.0,
         fov: FRAC_PI_2,
-        focal_dist: 3.46,
+        focus_distance: 3.46,
         lens_radius: 0.1,
+        chromatic_aberration: 0.0,
         transformation: Transformation::lookat(
             &vector![-2.0, 2.0, 1.0],
             &vector![0.0, 0.0, -1.0],
@@ -40,9 +52,9 @@ pub fn three_balls() -> ExampleScene {
 
     // Table of materials
     let material_table = vec![
-        Material::new(Scatter::Lambert, Absorb::AlbedoMap(TextureId(0)), Emit::None),
-        Material::new(Scatter::Lambert, Absorb::AlbedoMap(TextureId(1)), Emit::None),
-        Material::new(Scatter::Dielectric {refraction_index: 1.5}, Absorb::WhiteBody, Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::AlbedoMap(TextureId(0)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::AlbedoMap(TextureId(1)), Emit::None),
+        Material::new(Scatter::Dielectric {refraction_index: 1.5, abbe_number: None}, Absorb::WhiteBody, Emit::None),
         Material::new(Scatter::Metal {fuzziness: 0.0}, Absorb::Albedo(rgb(0.8, 0.6, 0.2)), Emit::None),
     ];
 
@@ -54,9 +66,9 @@ pub fn three_balls() -> ExampleScene {
         Hittable::Sphere {center: vector![1.0, 0.0, -1.0], radius: 0.5, material: MaterialId(3)}, // Glass sphere
     ]);
 
-    let scene_data = SceneData {material_table, texture_table, mesh_table: Vec::new()};
-    let background = Emit::SkyGradient;
-    ExampleScene {camera, scene_data, root, background}
+    let scene_data = SceneData {material_table, texture_table, ..SceneData::empty()};
+    let background = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
+    ExampleScene {camera, scene_data, root, background, animation: None}
 }
 
 #[allow(dead_code)]
@@ -64,8 +76,9 @@ pub fn more_balls() -> ExampleScene {
     let camera = Camera {
         aspect_ratio: 1.0,
         fov: FRAC_PI_2,
-        focal_dist: 7.5,
+        focus_distance: 7.5,
         lens_radius: 0.02,
+        chromatic_aberration: 0.0,
         transformation: Transformation::lookat(
             &vector![6.0, 2.0, 4.0],
             &vector![0.0, 0.0, 0.0],
@@ -75,17 +88,17 @@ pub fn more_balls() -> ExampleScene {
 
     // Table of textures
     let texture_table = vec![
-        Texture::Checker {odd: TextureId(1), even: TextureId(2)},
+        Texture::Checker {odd: TextureId(1), even: TextureId(2), space: TextureSpace::Position},
         Texture::Solid(rgb(0.2, 0.3, 0.1)),
         Texture::Solid(rgb(0.9, 0.9, 0.9))
     ];
 
     // Table of materials
     let mut material_table = vec![
-        Material::new(Scatter::Lambert, Absorb::AlbedoMap(TextureId(0)), Emit::None),
-        Material::new(Scatter::Lambert, Absorb::Albedo(rgb(0.1, 0.2, 0.5)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::AlbedoMap(TextureId(0)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.1, 0.2, 0.5)), Emit::None),
         Material::new(Scatter::Metal {fuzziness: 0.0}, Absorb::Albedo(rgb(0.8, 0.6, 0.2)), Emit::None),
-        Material::new(Scatter::Dielectric {refraction_index: 1.5}, Absorb::WhiteBody, Emit::None),
+        Material::new(Scatter::Dielectric {refraction_index: 1.5, abbe_number: None}, Absorb::WhiteBody, Emit::None),
     ];
 
     // List of objects of the scene
@@ -116,7 +129,7 @@ pub fn more_balls() -> ExampleScene {
             if rng.sample(Bernoulli(0.7)) {
                 // Random lambert material
                 material_table.push(Material::new(
-                    Scatter::Lambert, Absorb::Albedo(albedo), Emit::None
+                    Scatter::Lambert {two_sided: false}, Absorb::Albedo(albedo), Emit::None
                 ));
             } else if rng.sample(Bernoulli(0.7)) {
                 // Random metal
@@ -126,26 +139,21 @@ pub fn more_balls() -> ExampleScene {
             } else {
                 // Glass
                 material_table.push(Material::new(
-                    Scatter::Dielectric {refraction_index: 1.5}, Absorb::WhiteBody, Emit::None
+                    Scatter::Dielectric {refraction_index: 1.5, abbe_number: None}, Absorb::WhiteBody, Emit::None
                 ));
             }
         }
     }
 
-    let scene_data = SceneData {material_table, texture_table, mesh_table: Vec::new()};
-    let background = Emit::SkyGradient;
-    ExampleScene {camera, scene_data, root: Hittable::List(root), background}
+    let scene_data = SceneData {material_table, texture_table, ..SceneData::empty()};
+    let background = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
+    ExampleScene {camera, scene_data, root: Hittable::List(root), background, animation: None}
 }
 
 #[allow(dead_code)]
 pub fn more_balls_optimized() -> ExampleScene {
     let mut example_scene = more_balls();
-    let list = if let Hittable::List(list) = example_scene.root {
-        list
-    } else {
-        unreachable!()
-    };
-    example_scene.root = Hittable::Bvh(Bvh::new(list, &example_scene.scene_data));
+    example_scene.root = example_scene.root.into_bvh(&example_scene.scene_data);
     example_scene
 }
 
@@ -154,8 +162,9 @@ pub fn two_balls() -> ExampleScene {
     let camera = Camera {
         aspect_ratio: 1.0,
         fov: FRAC_PI_2,
-        focal_dist: 7.5,
+        focus_distance: 7.5,
         lens_radius: 0.0,
+        chromatic_aberration: 0.0,
         transformation: Transformation::lookat(
             &vector![6.0, 0.0, 4.0],
             &vector![0.0, 0.0, 0.0],
@@ -166,24 +175,24 @@ pub fn two_balls() -> ExampleScene {
     let texture_table = vec![
         Texture::Solid(rgb(0.2, 0.2, 0.2)),
         Texture::Solid(rgb(0.9, 0.0, 0.5)),
-        Texture::Checker {odd: TextureId(0), even: TextureId(1)},
-        Texture::Perlin {seed: 0},
+        Texture::Checker {odd: TextureId(0), even: TextureId(1), space: TextureSpace::Position},
+        Texture::Perlin {seed: 0, space: TextureSpace::Position},
     ];
 
     let material_table = vec![
-        Material::new(Scatter::Lambert, Absorb::AlbedoMap(TextureId(2)), Emit::None),
-        Material::new(Scatter::Lambert, Absorb::AlbedoMap(TextureId(3)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::AlbedoMap(TextureId(2)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::AlbedoMap(TextureId(3)), Emit::None),
     ];
 
-    let scene_data = SceneData {material_table, texture_table, mesh_table: Vec::new()};
+    let scene_data = SceneData {material_table, texture_table, ..SceneData::empty()};
 
-    let root = Hittable::Bvh(Bvh::new(vec![
+    let root = Hittable::List(vec![
         Hittable::Sphere {center: vector![0.0, -10.0, 0.0], radius: 10.0, material: MaterialId(0)},
         Hittable::Sphere {center: vector![0.0, 10.0, 0.0], radius: 10.0, material: MaterialId(1)},
-    ], &scene_data));
+    ]).into_bvh(&scene_data);
 
-    let background = Emit::SkyGradient;
-    ExampleScene {camera, scene_data, root, background}
+    let background = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
+    ExampleScene {camera, scene_data, root, background, animation: None}
 }
 
 #[allow(dead_code)]
@@ -191,8 +200,9 @@ pub fn earth() -> ExampleScene {
     let camera = Camera {
         aspect_ratio: 1.0,
         fov: PI / 9.0,
-        focal_dist: 1.0,
+        focus_distance: 1.0,
         lens_radius: 0.0,
+        chromatic_aberration: 0.0,
         transformation: Transformation::lookat(
             &vector![13.0, 7.0, 3.0],
             &vector![0.0, 0.0, 0.0],
@@ -201,21 +211,59 @@ pub fn earth() -> ExampleScene {
     };
 
     let texture_table = vec![
-        Texture::Image(tga::load("assets/earthmap.tga").unwrap())
+        Texture::Image {
+            image: tga::load("assets/earthmap.tga").unwrap(),
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::Clamp,
+            mips: None,
+        }
     ];
 
     let material_table = vec![
-        Material::new(Scatter::Lambert, Absorb::AlbedoMap(TextureId(0)), Emit::None)
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::AlbedoMap(TextureId(0)), Emit::None)
     ];
 
-    let scene_data = SceneData {material_table, texture_table, mesh_table: Vec::new()};
+    let scene_data = SceneData {material_table, texture_table, ..SceneData::empty()};
     
-    let root = Hittable::Bvh(Bvh::new(vec![
+    let root = Hittable::List(vec![
         Hittable::Sphere {center: vector![0.0, 0.0, 0.0], radius: 2.0, material: MaterialId(0)}
-    ], &scene_data));
+    ]).into_bvh(&scene_data);
+
+    let background = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
+    ExampleScene {camera, root, scene_data, background, animation: None}
+}
 
-    let background = Emit::SkyGradient;
-    ExampleScene {camera, root, scene_data, background}
+#[allow(dead_code)]
+pub fn earth_uv_grid() -> ExampleScene {
+    let mut example_scene = earth();
+    // Replace the map by a reference grid to check the texture coordinates
+    example_scene.scene_data.texture_table[0] =
+        Texture::UvGrid {cells: 12, line_color: rgb(0.05, 0.05, 0.05), bg_color: rgb(0.9, 0.9, 0.9)};
+    example_scene
+}
+
+#[allow(dead_code)]
+pub fn earth_uvs() -> ExampleScene {
+    let mut example_scene = earth();
+    // Show the texture coordinates that the map is sampled with. The bunny would not do, its obj has no texcoords.
+    example_scene.scene_data.material_table[0] = Material::new(Scatter::None, Absorb::BlackBody, Emit::DebugUVs);
+    example_scene
+}
+
+#[allow(dead_code)]
+pub fn displaced_earth() -> ExampleScene {
+    let mut example_scene = earth();
+    // Raise the land above the seas, using the brightness of the map as the height
+    example_scene.root = Hittable::List(vec![
+        Hittable::DisplacedSphere {
+            center: vector![0.0, 0.0, 0.0],
+            radius: 2.0,
+            material: MaterialId(0),
+            height: TextureId(0),
+            amplitude: 0.1,
+        }
+    ]).into_bvh(&example_scene.scene_data);
+    example_scene
 }
 
 #[allow(dead_code)]
@@ -225,32 +273,33 @@ pub fn one_triangle() -> ExampleScene {
 
     let material_table = vec![
         Material::new(Scatter::None, Absorb::BlackBody, Emit::DebugNormals),
-        Material::new(Scatter::Lambert, Absorb::Albedo(rgb(0.1, 0.2, 0.5)), Emit::None)
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.1, 0.2, 0.5)), Emit::None)
     ];
 
     let mesh_table = vec![
-        Mesh {
-            vertices: vec![
-                Vertex {position: vector![1.0, 0.0, 0.0], normal, uv},
-                Vertex {position: vector![0.0, 1.0, 0.0], normal, uv},
-                Vertex {position: vector![0.0, 0.0, 1.0], normal, uv},
+        Mesh::new(
+            vec![
+                Vertex {position: vector![1.0, 0.0, 0.0], normal, uv, color: WHITE},
+                Vertex {position: vector![0.0, 1.0, 0.0], normal, uv, color: WHITE},
+                Vertex {position: vector![0.0, 0.0, 1.0], normal, uv, color: WHITE},
             ],
-            indices: vec![0, 1, 2],
-            material: MaterialId(0)
-        }
+            vec![0, 1, 2],
+            MaterialId(0)
+        )
     ];
 
-    let scene_data = SceneData {material_table, mesh_table, texture_table: Vec::new()};
-    let root = Hittable::Bvh(Bvh::new(vec![
+    let scene_data = SceneData {material_table, mesh_table, ..SceneData::empty()};
+    let root = Hittable::List(vec![
         Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(0)}, // One lone triangle
         Hittable::Sphere {center: vector![0.0, -1000.0, -1.0], radius: 1000.0, material: MaterialId(1)}, // Ground
-    ], &scene_data));
-    let background = Emit::SkyGradient;
+    ]).into_bvh(&scene_data);
+    let background = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
     let camera = Camera {
         aspect_ratio: 1.0,
         fov: FRAC_PI_2,
-        focal_dist: 1.0,
+        focus_distance: 1.0,
         lens_radius: 0.0,
+        chromatic_aberration: 0.0,
         transformation: Transformation::lookat(
             &vector![2.0, 0.5, 1.0],
             &vector![0.0, 0.0, 0.0],
@@ -258,43 +307,46 @@ pub fn one_triangle() -> ExampleScene {
         ),
     };
 
-    ExampleScene {root, camera, scene_data, background}
+    ExampleScene {root, camera, scene_data, background, animation: None}
 }
 
 #[allow(dead_code)]
 pub fn glass_bunny() -> ExampleScene {
     let bunny = obj::load("assets/bunny_flat.obj").unwrap();
-    let mut hittable_list = Vec::new();
 
     let material_table = vec![
-        Material::new(Scatter::Dielectric {refraction_index: 1.5}, Absorb::Albedo(rgb(0.7, 0.8, 0.7)), Emit::None),
+        Material::new(
+            Scatter::Dielectric {refraction_index: 1.5, abbe_number: None}, Absorb::Albedo(rgb(0.7, 0.8, 0.7)),
+            Emit::None,
+        ),
         Material::new(Scatter::Metal {fuzziness: 0.05}, Absorb::Albedo(rgb(0.8, 0.8, 0.8)), Emit::None)
     ];
 
     let texture_table = vec![
-        Texture::Image(tga::load("assets/sky_panorama.tga").unwrap())
+        Texture::Image {
+            image: tga::load("assets/sky_panorama.tga").unwrap(),
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::Clamp,
+            mips: None,
+        }
     ];
 
-    hittable_list.extend(
-        bunny.iter_triangles().map(|tid| Hittable::Triangle {triangle: tid, mesh: MeshId(0)})
-    );
-    hittable_list.push(
-        Hittable::Sphere {center: vector![0.0, -1000.0, -1.0], radius: 1000.0, material: MaterialId(1)}
-    );
-
     let mesh_table = vec![
         bunny
     ];
 
-    let scene_data = SceneData {material_table, mesh_table, texture_table};
-    let root = Hittable::Bvh(Bvh::new(hittable_list, &scene_data));
-    // let root = Hittable::List(hittable_list); // OOH THAT'S SLOW
-    let background = Emit::SkySphere(TextureId(0));
+    let scene_data = SceneData {material_table, mesh_table, texture_table, ..SceneData::empty()};
+    let root = Hittable::List(vec![
+        Hittable::TriangleBvh(TriangleBvh::from_mesh(MeshId(0), &scene_data)),
+        Hittable::Sphere {center: vector![0.0, -1000.0, -1.0], radius: 1000.0, material: MaterialId(1)}, // Ground
+    ]);
+    let background = Emit::SkySphere {texture: TextureId(0), yaw: 0.0, pitch: 0.0};
     let camera = Camera {
         aspect_ratio: 1.0,
         fov: FRAC_PI_4,
-        focal_dist: 1.0,
+        focus_distance: 1.0,
         lens_radius: 0.0,
+        chromatic_aberration: 0.0,
         transformation: Transformation::lookat(
             &vector![-1.5, 1.5, 2.5],
             &vector![0.0, 0.5, 0.0],
@@ -302,13 +354,12 @@ pub fn glass_bunny() -> ExampleScene {
         ),
     };
 
-    ExampleScene {root, camera, scene_data, background}
+    ExampleScene {root, camera, scene_data, background, animation: None}
 }
 
 #[allow(dead_code)]
 pub fn bunny() -> ExampleScene {
     let bunny = obj::load("assets/bunny.obj").unwrap();
-    let mut hittable_list = Vec::new();
 
     let material_table = vec![
         Material::new(Scatter::None, Absorb::BlackBody, Emit::DebugNormals),
@@ -316,29 +367,30 @@ pub fn bunny() -> ExampleScene {
     ];
 
     let texture_table = vec![
-        Texture::Image(tga::load("assets/sky_panorama.tga").unwrap())
+        Texture::Image {
+            image: tga::load("assets/sky_panorama.tga").unwrap(),
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::Clamp,
+            mips: None,
+        }
     ];
 
-    hittable_list.extend(
-        bunny.iter_triangles().map(|tid| Hittable::Triangle {triangle: tid, mesh: MeshId(0)})
-    );
-    hittable_list.push(
-        Hittable::Sphere {center: vector![0.0, -1000.0, -1.0], radius: 1000.0, material: MaterialId(1)}
-    );
-
     let mesh_table = vec![
         bunny
     ];
 
-    let scene_data = SceneData {material_table, mesh_table, texture_table};
-    let root = Hittable::Bvh(Bvh::new(hittable_list, &scene_data));
-    // let root = Hittable::List(hittable_list); // OOH THAT'S SLOW
-    let background = Emit::SkySphere(TextureId(0));
+    let scene_data = SceneData {material_table, mesh_table, texture_table, ..SceneData::empty()};
+    let root = Hittable::List(vec![
+        Hittable::TriangleBvh(TriangleBvh::from_mesh(MeshId(0), &scene_data)),
+        Hittable::Sphere {center: vector![0.0, -1000.0, -1.0], radius: 1000.0, material: MaterialId(1)}, // Ground
+    ]);
+    let background = Emit::SkySphere {texture: TextureId(0), yaw: 0.0, pitch: 0.0};
     let camera = Camera {
         aspect_ratio: 1.0,
         fov: FRAC_PI_4,
-        focal_dist: 1.0,
+        focus_distance: 1.0,
         lens_radius: 0.0,
+        chromatic_aberration: 0.0,
         transformation: Transformation::lookat(
             &vector![-1.5, 1.5, 2.5],
             &vector![0.0, 0.5, 0.0],
@@ -346,5 +398,363 @@ pub fn bunny() -> ExampleScene {
         ),
     };
 
-    ExampleScene {root, camera, scene_data, background}
-}
\ No newline at end of file
+    ExampleScene {root, camera, scene_data, background, animation: None}
+}
+
+#[allow(dead_code)]
+pub fn bunny_orbit() -> ExampleScene {
+    let mut example_scene = bunny();
+    // Turn around the bunny once between the times 0 and 1
+    example_scene.animation = Some(|example_scene, time| {
+        let angle = TAU * time;
+        let position = vector![-1.5 * angle.cos() + 2.5 * angle.sin(), 1.5, 2.5 * angle.cos() + 1.5 * angle.sin()];
+        example_scene.camera.transformation = Transformation::lookat(
+            &position,
+            &vector![0.0, 0.5, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        );
+    });
+    example_scene
+}
+
+/// A triangular prism along the X axis with flat faces, the apex pointing down
+fn prism_mesh(length: Real, side: Real, center: &Rvec3, material: MaterialId) -> Mesh {
+    let half_length = 0.5 * length;
+    let height = side * (0.75 as Real).sqrt();
+    let section = [
+        vector![0.0, -2.0 / 3.0 * height, 0.0],
+        vector![0.0, height / 3.0, 0.5 * side],
+        vector![0.0, height / 3.0, -0.5 * side],
+    ];
+    let left = section.map(|p| center + p - vector![half_length, 0.0, 0.0]);
+    let right = section.map(|p| center + p + vector![half_length, 0.0, 0.0]);
+    let faces = vec![
+        vec![left[0], left[2], left[1]],
+        vec![right[0], right[1], right[2]],
+        vec![left[0], left[1], right[1], right[0]],
+        vec![left[1], left[2], right[2], right[1]],
+        vec![left[2], left[0], right[0], right[2]],
+    ];
+
+    // Each face has its own vertices so that the normals are not smoothed
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for face in faces {
+        // The dielectric needs the normals to point outward
+        let normal = (face[1] - face[0]).cross(&(face[2] - face[0])).normalize();
+        let normal = if normal.dot(&(face[0] - center)) < 0.0 { -normal } else { normal };
+        let first = vertices.len() as u32;
+        vertices.extend(face.iter().map(|&position| Vertex {position, normal, uv: Rvec2::zeros(), color: WHITE}));
+        for k in 1..face.len() as u32 - 1 {
+            indices.extend([first, first + k, first + k + 1]);
+        }
+    }
+    Mesh::new(vertices, indices, material)
+}
+
+#[allow(dead_code)]
+pub fn prism() -> ExampleScene {
+    let material_table = vec![
+        // A flint glass with an exaggerated dispersion
+        Material::new(
+            Scatter::Dielectric {refraction_index: 1.5, abbe_number: Some(15.0)}, Absorb::WhiteBody, Emit::None
+        ),
+        Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(4.0, 4.0, 4.0))),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.5, 0.5, 0.5)), Emit::None),
+    ];
+
+    let mesh_table = vec![
+        prism_mesh(2.0, 0.8, &vector![0.0, 0.8, 0.0], MaterialId(0))
+    ];
+
+    let scene_data = SceneData {material_table, mesh_table, ..SceneData::empty()};
+    let root = Hittable::List(vec![
+        Hittable::TriangleBvh(TriangleBvh::from_mesh(MeshId(0), &scene_data)),
+        // Light bar, only visible through the prism
+        Hittable::Capsule {
+            a: vector![-3.0, 2.4, -2.0], b: vector![3.0, 2.4, -2.0], radius: 0.05, material: MaterialId(1)
+        },
+        Hittable::Sphere {center: vector![0.0, -1000.0, 0.0], radius: 1000.0, material: MaterialId(2)}, // Ground
+    ]);
+    let background = Emit::Color(rgb(0.02, 0.02, 0.03));
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_6,
+        focus_distance: 3.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 0.8, 3.0],
+            &vector![0.0, 0.8, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+
+    ExampleScene {root, camera, scene_data, background, animation: None}
+}
+
+#[allow(dead_code)]
+pub fn bitten_ball() -> ExampleScene {
+    let material_table = vec![
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.7, 0.1, 0.1)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.9, 0.8, 0.5)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.5, 0.5, 0.5)), Emit::None),
+    ];
+
+    let scene_data = SceneData::new(material_table);
+    let root = Hittable::List(vec![
+        // An apple with a bite taken out of it, the inside of the bite has the material of the removed sphere
+        Hittable::Difference {
+            a: Box::new(Hittable::Sphere {center: vector![0.0, 1.0, 0.0], radius: 1.0, material: MaterialId(0)}),
+            b: Box::new(Hittable::Sphere {center: vector![0.6, 1.5, 0.6], radius: 0.7, material: MaterialId(1)}),
+        },
+        Hittable::Sphere {center: vector![0.0, -1000.0, 0.0], radius: 1000.0, material: MaterialId(2)}, // Ground
+    ]);
+    let background = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_4,
+        focus_distance: 1.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![3.5, 2.0, -1.5],
+            &vector![0.0, 0.8, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+
+    ExampleScene {root, camera, scene_data, background, animation: None}
+}
+
+#[allow(dead_code)]
+pub fn clearcoat_balls() -> ExampleScene {
+    let red = Absorb::Albedo(rgb(0.7, 0.1, 0.1));
+    let material_table = vec![
+        Material::new(Scatter::Lambert {two_sided: false}, red.clone(), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, red, Emit::None)
+            .with_clearcoat(Clearcoat {refraction_index: 1.5, roughness: 0.1}),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.5, 0.5, 0.5)), Emit::None),
+    ];
+
+    let texture_table = vec![
+        Texture::Image {
+            image: tga::load("assets/sky_panorama.tga").unwrap(),
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::Clamp,
+            mips: None,
+        }
+    ];
+
+    let scene_data = SceneData {material_table, texture_table, ..SceneData::empty()};
+    let root = Hittable::List(vec![
+        Hittable::Sphere {center: vector![-1.1, 1.0, 0.0], radius: 1.0, material: MaterialId(0)}, // Matte
+        Hittable::Sphere {center: vector![1.1, 1.0, 0.0], radius: 1.0, material: MaterialId(1)}, // Coated
+        Hittable::Sphere {center: vector![0.0, -1000.0, 0.0], radius: 1000.0, material: MaterialId(2)}, // Ground
+    ]);
+    let background = Emit::SkySphere {texture: TextureId(0), yaw: 0.0, pitch: 0.0};
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_4,
+        focus_distance: 1.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 2.0, 6.0],
+            &vector![0.0, 1.0, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+
+    ExampleScene {root, camera, scene_data, background, animation: None}
+}
+
+#[allow(dead_code)]
+pub fn bumpy_ball() -> ExampleScene {
+    let texture_table = vec![
+        Texture::Perlin {seed: 1, space: TextureSpace::Position},
+    ];
+
+    let material_table = vec![
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.8, 0.8, 0.8)), Emit::None)
+            .with_bump(Bump {texture: TextureId(0), strength: 1.0}),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.5, 0.5, 0.5)), Emit::None),
+        Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(4.0, 4.0, 4.0))),
+    ];
+
+    let scene_data = SceneData {material_table, texture_table, ..SceneData::empty()};
+    let root = Hittable::List(vec![
+        Hittable::Sphere {center: vector![0.0, 3.0, 0.0], radius: 3.0, material: MaterialId(0)}, // Bumpy ball
+        Hittable::Sphere {center: vector![0.0, -1000.0, 0.0], radius: 1000.0, material: MaterialId(1)}, // Ground
+        Hittable::Sphere {center: vector![-12.0, 15.0, 12.0], radius: 6.0, material: MaterialId(2)}, // Light
+    ]);
+    let background = Emit::Color(rgb(0.02, 0.02, 0.02));
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_4,
+        focus_distance: 1.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 6.0, 13.5],
+            &vector![0.0, 3.0, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+
+    ExampleScene {root, camera, scene_data, background, animation: None}
+}
+
+#[allow(dead_code)]
+pub fn veined_ball() -> ExampleScene {
+    let mut example_scene = bumpy_ball();
+    // Paint the edges of Worley cells instead of the bumps
+    example_scene.scene_data.texture_table[0] =
+        Texture::Worley {seed: 1, scale: 1.5, feature: WorleyFeature::Edge, space: TextureSpace::Position};
+    example_scene.scene_data.material_table[0] =
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::AlbedoMap(TextureId(0)), Emit::None);
+    example_scene
+}
+
+#[allow(dead_code)]
+pub fn glowing_panel() -> ExampleScene {
+    let normal = vector![0.0, 0.0, 1.0];
+    let uv = vector![0.0, 0.0];
+
+    let material_table = vec![
+        Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(20.0, 16.0, 10.0))),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.3, 0.3, 0.3)), Emit::None),
+        Material::new(Scatter::Ggx {roughness: 0.2}, Absorb::Albedo(rgb(0.9, 0.9, 0.9)), Emit::None),
+    ];
+
+    let mesh_table = vec![
+        Mesh::new(
+            vec![
+                Vertex {position: vector![-1.5, 2.0, -1.0], normal, uv, color: WHITE},
+                Vertex {position: vector![1.5, 2.0, -1.0], normal, uv, color: WHITE},
+                Vertex {position: vector![1.5, 2.6, -1.0], normal, uv, color: WHITE},
+                Vertex {position: vector![-1.5, 2.6, -1.0], normal, uv, color: WHITE},
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+            MaterialId(0)
+        )
+    ];
+
+    let scene_data = SceneData {material_table, mesh_table, ..SceneData::empty()};
+    let root = Hittable::List(vec![
+        Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(0)}, // Panel
+        Hittable::Triangle {triangle: TriangleId(3), mesh: MeshId(0)},
+        Hittable::Sphere {center: vector![0.0, 0.7, 0.0], radius: 0.7, material: MaterialId(2)}, // Shiny ball
+        Hittable::Sphere {center: vector![0.0, -1000.0, 0.0], radius: 1000.0, material: MaterialId(1)}, // Ground
+    ]);
+    let background = Emit::Color(rgb(0.01, 0.01, 0.015));
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_3,
+        focus_distance: 1.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 1.5, 5.0],
+            &vector![0.0, 1.2, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+
+    ExampleScene {root, camera, scene_data, background, animation: None}
+}
+
+#[allow(dead_code)]
+pub fn two_lights() -> ExampleScene {
+    let material_table = vec![
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.5, 0.5, 0.5)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.8, 0.3, 0.2)), Emit::None),
+        Material::new(Scatter::Ggx {roughness: 0.3}, Absorb::Albedo(rgb(0.9, 0.9, 0.9)), Emit::None),
+        Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(400.0, 380.0, 340.0))),
+        Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(0.4, 0.5, 0.7))),
+    ];
+
+    let scene_data = SceneData::new(material_table);
+    let root = Hittable::List(vec![
+        Hittable::Sphere {center: vector![0.0, -1000.0, 0.0], radius: 1000.0, material: MaterialId(0)}, // Ground
+        Hittable::Sphere {center: vector![-1.2, 1.0, 0.0], radius: 1.0, material: MaterialId(1)}, // Matte ball
+        Hittable::Sphere {center: vector![1.2, 1.0, 0.0], radius: 1.0, material: MaterialId(2)}, // Glossy ball
+        Hittable::Sphere {center: vector![2.0, 4.0, 2.0], radius: 0.1, material: MaterialId(3)}, // Small bright light
+        Hittable::Sphere {center: vector![-6.0, 6.0, -8.0], radius: 4.0, material: MaterialId(4)}, // Large dim light
+    ]);
+    let background = Emit::Color(rgb(0.0, 0.0, 0.0));
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_4,
+        focus_distance: 1.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 2.5, 8.0],
+            &vector![0.0, 1.0, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+
+    ExampleScene {root, camera, scene_data, background, animation: None}
+}
+
+#[allow(dead_code)]
+pub fn textured_floor() -> ExampleScene {
+    // A fine checker with 4 texels per square, repeated every 2 units on a floor that goes to the horizon
+    let size = 256;
+    let mut checker = Array2d::new(size, size);
+    for j in 0..size {
+        for i in 0..size {
+            let value = if (i / 4 + j / 4) % 2 == 0 {0xe0} else {0x20};
+            *checker.get_mut(i, j) = [value, value, value, 0xff];
+        }
+    }
+    let repeat_size = 2.0;
+
+    let texture_table = vec![
+        Texture::Image {
+            mips: Some(MipChain::new(&checker, repeat_size / size as Real)),
+            image: checker,
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::Repeat,
+        }
+    ];
+
+    let material_table = vec![
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::AlbedoMap(TextureId(0)), Emit::None),
+    ];
+
+    let half_size = 200.0;
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    let vertices = corners.iter().map(|&(x, z)| Vertex {
+        position: vector![x * half_size, 0.0, z * half_size],
+        normal: vector![0.0, 1.0, 0.0],
+        uv: vector![x, -z] * half_size / repeat_size,
+        color: WHITE,
+    }).collect();
+    let mesh_table = vec![
+        Mesh::new(vertices, vec![0, 2, 1, 0, 3, 2], MaterialId(0))
+    ];
+
+    let scene_data = SceneData {material_table, mesh_table, texture_table, ..SceneData::empty()};
+    let root = Hittable::List(vec![
+        Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(0)},
+        Hittable::Triangle {triangle: TriangleId(3), mesh: MeshId(0)},
+    ]);
+    let background = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_3,
+        focus_distance: 1.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 1.0, 0.0],
+            &vector![0.0, 0.6, -10.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+
+    ExampleScene {root, camera, scene_data, background, animation: None}
+}
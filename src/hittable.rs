@@ -3,40 +3,228 @@ use crate::utility::*;
 use crate::bvh::*;
 use crate::mesh::*;
 use crate::material::MaterialId;
+use crate::texture::TextureId;
+use crate::randomness::*;
 
 // ------------------------------------------- Hittable -------------------------------------------
 
+/// Lists with more elements than this are turned into a Bvh by build_acceleration
+pub const BVH_THRESHOLD: usize = 8;
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Hittable {
     Sphere {center: Rvec3, radius: Real, material: MaterialId},
+    /// A sphere whose radius is pushed out by amplitude times the height texture, sampled at the texture coordinates
+    /// of the plain sphere. The height is the average of the channels, from 0 to 1, so the surface stays between
+    /// radius and radius + amplitude, a negative amplitude digging into the sphere. The ray marches through the
+    /// shell, so the silhouette shows the relief too, but it is much slower than a plain sphere.
+    DisplacedSphere {center: Rvec3, radius: Real, material: MaterialId, height: TextureId, amplitude: Real},
+    /// The part of a sphere inside an angular window, for domes and lenses. phi goes around the Y axis like the u
+    /// texture coordinate, from 0 to 2π, and theta down from the +Y pole, from 0 to π: the lower half is theta from
+    /// π/2 to π. The section is open, so it cannot be used in a Difference.
+    SphereSection {
+        center: Rvec3,
+        radius: Real,
+        phi_range: (Real, Real),
+        theta_range: (Real, Real),
+        material: MaterialId,
+    },
+    /// A cylinder between a and b capped by two half-spheres
+    Capsule {a: Rvec3, b: Rvec3, radius: Real, material: MaterialId},
     Triangle {triangle: TriangleId, mesh: MeshId},
     List(Vec<Hittable>),
+    /// The acceleration structures are not serialized, build_acceleration makes them again after loading
+    #[cfg_attr(feature = "serde", serde(skip))]
     Bvh(Bvh),
+    #[cfg_attr(feature = "serde", serde(skip))]
+    TriangleBvh(TriangleBvh),
+    /// The part of a that is not inside b. Both must be closed convex objects, like spheres and capsules, because
+    /// only the first entry and exit points of the ray are tracked: the ray must cross each of them at most once.
+    Difference {a: Box<Hittable>, b: Box<Hittable>},
+    /// The same object with its normals pointing the other way, like the inside walls of a room
+    FlipNormals(Box<Hittable>),
+    /// The child moved by an offset, cheaper than a full transformation
+    Translate {offset: Rvec3, child: Box<Hittable>},
+    /// The child turned by angle radians around the Y axis, counterclockwise seen from above
+    RotateY {angle: Real, child: Box<Hittable>},
 }
 
 impl Hittable {
     pub fn hit(&self, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
         match self {
-            Self::Sphere {center, radius, material} => hit_sphere(center, *radius, *material, ray),
+            Self::Sphere {center, radius, material} => hit_sphere(center, *radius, *material, ray, |_| true),
+            Self::SphereSection {center, radius, phi_range, theta_range, material} => {
+                let (phi_range, theta_range) = (*phi_range, *theta_range);
+                hit_sphere(center, *radius, *material, ray, |normal| {
+                    let phi = sphere_uv(normal).x * TAU;
+                    let theta = normal.y.clamp(-1.0, 1.0).acos();
+                    (phi_range.0..=phi_range.1).contains(&phi) && (theta_range.0..=theta_range.1).contains(&theta)
+                })
+            }
+            Self::DisplacedSphere {center, radius, material, height, amplitude}
+                => hit_displaced_sphere(center, *radius, *material, *height, *amplitude, ray, scene_data),
+            Self::Capsule {a, b, radius, material} => hit_capsule(a, b, *radius, *material, ray),
             Self::Triangle {triangle, mesh} => hit_triangle(*triangle, *mesh, ray, scene_data),
             Self::List(list) => hit_list(list, ray, scene_data),
             Self::Bvh(bvh) => bvh.hit(ray, scene_data),
+            Self::TriangleBvh(bvh) => bvh.hit(ray, scene_data),
+            Self::Difference {a, b} => hit_difference(a, b, ray, scene_data),
+            Self::FlipNormals(inner) => hit_flip_normals(inner, ray, scene_data),
+            Self::Translate {offset, child} => hit_translate(offset, child, ray, scene_data),
+            Self::RotateY {angle, child} => hit_rotate_y(*angle, child, ray, scene_data),
+        }
+    }
+
+    /// Whether anything is hit between t_min and t_max, like a shadow ray asks. Cheaper than hit, because the Bvhs
+    /// and the lists stop at the first hit instead of looking for the closest one.
+    pub fn hit_any(&self, ray: &Ray, scene_data: &SceneData) -> bool {
+        match self {
+            Self::List(list) => list.iter().any(|x| x.hit_any(ray, scene_data)),
+            Self::Bvh(bvh) => bvh.hit_any(ray, scene_data),
+            Self::TriangleBvh(bvh) => bvh.hit_any(ray, scene_data),
+            Self::FlipNormals(inner) => inner.hit_any(ray, scene_data),
+            Self::Translate {offset, child} => child.hit_any(&translate_to_local(offset, ray), scene_data),
+            Self::RotateY {angle, child} => child.hit_any(&rotate_y_to_local(*angle, ray), scene_data),
+            Self::Sphere {..} | Self::SphereSection {..} | Self::DisplacedSphere {..} | Self::Capsule {..}
+                | Self::Triangle {..} | Self::Difference {..} => self.hit(ray, scene_data).is_some(),
         }
     }
 
     pub fn bounding_box(&self, scene_data: &SceneData) -> AABB {
         match self {
-            Self::Sphere {center, radius, ..} => bounding_box_sphere(center, *radius),
+            Self::Sphere {center, radius, ..} | Self::SphereSection {center, radius, ..}
+                => bounding_box_sphere(center, *radius),
+            Self::DisplacedSphere {center, radius, amplitude, ..}
+                => bounding_box_sphere(center, radius + amplitude.max(0.0)),
+            Self::Capsule {a, b, radius, ..} => bounding_box_sphere(a, *radius).union(&bounding_box_sphere(b, *radius)),
             Self::Triangle {triangle, mesh} => bounding_box_triangle(*triangle, *mesh, scene_data),
             Self::List(list) => bounding_box_list(list, scene_data),
-            Self::Bvh(_) => panic!("Do not take the bounding box of a Bvh. What are you trying to do?")
+            Self::Bvh(bvh) => bvh.bounding_box(),
+            Self::TriangleBvh(bvh) => bvh.bounding_box(),
+            Self::Difference {a, ..} => a.bounding_box(scene_data),
+            Self::FlipNormals(inner) => inner.bounding_box(scene_data),
+            Self::Translate {offset, child} => {
+                let aabb = child.bounding_box(scene_data);
+                AABB {min: aabb.min + offset, max: aabb.max + offset}
+            }
+            Self::RotateY {angle, child} => child.bounding_box(scene_data).transform(&rotation_y(*angle)),
+        }
+    }
+
+    /// Number of spheres, capsules and triangles, looking into the lists, the Bvhs and the wrappers
+    pub fn count_primitives(&self) -> usize {
+        let mut count = 0;
+        self.visit(&mut |x| count += match x {
+            Self::Sphere {..} | Self::SphereSection {..} | Self::DisplacedSphere {..} | Self::Capsule {..}
+                | Self::Triangle {..} => 1,
+            Self::TriangleBvh(bvh) => bvh.count_primitives(),
+            _ => 0,
+        });
+        count
+    }
+}
+
+impl Hittable {
+    /// Turn a non-empty List into a Bvh, anything else is returned unchanged
+    pub fn into_bvh(self, scene_data: &SceneData) -> Hittable {
+        match self {
+            Self::List(list) if !list.is_empty() => Self::Bvh(Bvh::new(list, scene_data)),
+            other => other,
+        }
+    }
+
+    /// Replace the lists longer than BVH_THRESHOLD by Bvhs, starting from the innermost ones
+    pub fn build_acceleration(&mut self, scene_data: &SceneData) {
+        self.visit_mut(&mut |x| match x {
+            Self::List(list) if list.len() > BVH_THRESHOLD => {
+                let list = std::mem::take(list);
+                *x = Self::Bvh(Bvh::new(list, scene_data));
+            }
+            _ => (),
+        });
+    }
+
+    /// Call f on this node, then on all the nodes below it: the elements of the lists, the leaves of the Bvhs, both
+    /// sides of the differences and the children of the wrappers. The triangles of a TriangleBvh are not Hittables,
+    /// f only sees the TriangleBvh.
+    pub fn visit(&self, f: &mut impl FnMut(&Hittable)) {
+        f(self);
+        match self {
+            Self::List(list) => list.iter().for_each(|x| x.visit(f)),
+            Self::Bvh(bvh) => bvh.leaves().iter().for_each(|x| x.visit(f)),
+            Self::Difference {a, b} => {
+                a.visit(f);
+                b.visit(f);
+            }
+            Self::FlipNormals(child) | Self::Translate {child, ..} | Self::RotateY {child, ..} => child.visit(f),
+            Self::Sphere {..} | Self::SphereSection {..} | Self::DisplacedSphere {..} | Self::Capsule {..}
+                | Self::Triangle {..} | Self::TriangleBvh(_) => (),
+        }
+    }
+
+    /// Like visit, but f also gets where the node sits in the scene: the Translate and RotateY above it, and whether
+    /// it is inside a Difference
+    pub fn visit_with_context(&self, f: &mut impl FnMut(&Hittable, &VisitContext)) {
+        self.visit_context_from(&VisitContext {to_world: Transformation::identity(), in_difference: false}, f);
+    }
+
+    fn visit_context_from(&self, context: &VisitContext, f: &mut impl FnMut(&Hittable, &VisitContext)) {
+        f(self, context);
+        match self {
+            Self::List(list) => list.iter().for_each(|x| x.visit_context_from(context, f)),
+            Self::Bvh(bvh) => bvh.leaves().iter().for_each(|x| x.visit_context_from(context, f)),
+            Self::Difference {a, b} => {
+                let inner = VisitContext {in_difference: true, ..context.clone()};
+                a.visit_context_from(&inner, f);
+                b.visit_context_from(&inner, f);
+            }
+            Self::FlipNormals(child) => child.visit_context_from(context, f),
+            Self::Translate {offset, child} => {
+                let to_world = context.to_world.compose(&Transformation::new(Rmat3::identity(), *offset));
+                child.visit_context_from(&VisitContext {to_world, ..context.clone()}, f);
+            }
+            Self::RotateY {angle, child} => {
+                let to_world = context.to_world.compose(&Transformation::new(rotation_y(*angle), Rvec3::zeros()));
+                child.visit_context_from(&VisitContext {to_world, ..context.clone()}, f);
+            }
+            Self::Sphere {..} | Self::SphereSection {..} | Self::DisplacedSphere {..} | Self::Capsule {..}
+                | Self::Triangle {..} | Self::TriangleBvh(_) => (),
+        }
+    }
+
+    /// Call f on all the nodes below this one, then on this one, so that f may replace a node once its children are
+    /// done. The leaves of the Bvhs are left alone, changing them would invalidate the bounding boxes of the tree.
+    pub fn visit_mut(&mut self, f: &mut impl FnMut(&mut Hittable)) {
+        match self {
+            Self::List(list) => list.iter_mut().for_each(|x| x.visit_mut(f)),
+            Self::Difference {a, b} => {
+                a.visit_mut(f);
+                b.visit_mut(f);
+            }
+            Self::FlipNormals(child) | Self::Translate {child, ..} | Self::RotateY {child, ..} => child.visit_mut(f),
+            Self::Sphere {..} | Self::SphereSection {..} | Self::DisplacedSphere {..} | Self::Capsule {..}
+                | Self::Triangle {..} | Self::Bvh(_) | Self::TriangleBvh(_) => (),
         }
+        f(self);
     }
 }
 
+/// Where a node met by Hittable::visit_with_context sits in the scene
+#[derive(Debug, Clone)]
+pub struct VisitContext {
+    /// From the frame of the node to the world
+    pub to_world: Transformation,
+    /// Only part of the surface of a node inside a Difference shows
+    pub in_difference: bool,
+}
+
 // ------------------------------------------- Hit implementations -------------------------------------------
 
-fn hit_sphere(center: &Rvec3, radius: Real, material: MaterialId, ray: &Ray) -> Option<(Hit, MaterialId)> {
+/// Only the hits where the outward unit normal passes the keep test count
+fn hit_sphere(center: &Rvec3, radius: Real, material: MaterialId, ray: &Ray, keep: impl Fn(&Rvec3) -> bool)
+    -> Option<(Hit, MaterialId)>
+{
     let to_center = ray.origin - center;
     let a = ray.direction.norm_squared();
     let half_b = ray.direction.dot(&to_center);
@@ -46,65 +234,251 @@ fn hit_sphere(center: &Rvec3, radius: Real, material: MaterialId, ray: &Ray) ->
         return None
     }
     
-    // Compute the intersection parameter t
+    // Compute the intersection parameter t, trying the closer hit first
     let sqrt_delta = delta.sqrt();
-    let mut t = (-half_b - sqrt_delta) / a; // Try the closer hit
-    if t < ray.t_min || t > ray.t_max {
-        t = (-half_b + sqrt_delta) / a; // Try the further hit
-        if t < ray.t_min || t > ray.t_max {
+    let (t, position, normal) = [(-half_b - sqrt_delta) / a, (-half_b + sqrt_delta) / a].iter()
+        .filter(|&&t| t >= ray.t_min && t <= ray.t_max)
+        .map(|&t| {
+            let position = ray.at(t);
+            (t, position, (position - center).normalize())
+        })
+        .find(|(_, _, normal)| keep(normal))?;
+    let uv = sphere_uv(&normal);
+    let tangent = tangent_along(&normal, &vector![normal.z, 0.0, -normal.x]);
+    let differentials = ray.surface_differentials(t, &normal);
+    Some((Hit {t, position, normal, geometric_normal: normal, uv, tangent, color: WHITE, differentials}, material))
+}
+
+/// Texture coordinates of a point of the unit sphere: u goes around the Y axis, v from the bottom to the top
+fn sphere_uv(direction: &Rvec3) -> Rvec2 {
+    vector![0.5 - direction.z.atan2(direction.x) / TAU, direction.y.asin() / PI + 0.5]
+}
+
+/// The march stops after this many steps, at grazing angles where it crawls along the surface
+const DISPLACED_MAX_STEPS: usize = 1000;
+/// Fraction of the distance to the surface taken at each step, below 1 because the distance is only estimated along
+/// the radius, and the surface may be closer sideways where the relief is steep
+const DISPLACED_STEP_FACTOR: Real = 0.5;
+/// Smallest step and precision of the hit, relative to the radius
+const DISPLACED_PRECISION: Real = 1e-6;
+
+fn hit_displaced_sphere(center: &Rvec3, radius: Real, material: MaterialId, height: TextureId, amplitude: Real,
+    ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)>
+{
+    // The textures do not draw random numbers, but sampling them asks for a generator
+    let mut rng = Randomizer::seed_from_u64(0);
+    let texture = scene_data.texture(height);
+    // Radial distance to the surface, positive outside
+    let mut field = |p: &Rvec3| {
+        let from_center = p - center;
+        let distance = from_center.norm();
+        let direction = from_center / distance;
+        let hit = Hit {
+            t: 0.0,
+            position: *p,
+            normal: direction,
+            geometric_normal: direction,
+            uv: sphere_uv(&direction),
+            tangent: tangent_along(&direction, &vector![direction.z, 0.0, -direction.x]),
+            color: WHITE,
+            differentials: None,
+        };
+        distance - radius - amplitude * texture.sample(ray, &hit, scene_data, &mut rng).sum() / 3.0
+    };
+
+    // March through the part of the ray inside the shell that holds the whole relief
+    let (t_start, t_end) = {
+        let outer = radius + amplitude.max(0.0);
+        let to_center = ray.origin - center;
+        let a = ray.direction.norm_squared();
+        let half_b = ray.direction.dot(&to_center);
+        let delta = half_b * half_b - a * (to_center.norm_squared() - outer * outer);
+        if delta <= 0.0 {
+            return None
+        }
+        let sqrt_delta = delta.sqrt();
+        (((-half_b - sqrt_delta) / a).max(ray.t_min), ((-half_b + sqrt_delta) / a).min(ray.t_max))
+    };
+    let speed = ray.direction.norm();
+    let precision = DISPLACED_PRECISION * radius;
+    let mut t = t_start;
+    let mut value = field(&ray.at(t));
+    // Rays that start below the surface look for the way out
+    let side = value.signum();
+    let mut steps = 0;
+    let (mut before, mut after) = loop {
+        if t > t_end || steps == DISPLACED_MAX_STEPS {
             return None
         }
+        let step = (DISPLACED_STEP_FACTOR * value.abs()).max(precision) / speed;
+        let next_value = field(&ray.at(t + step));
+        if next_value * side <= 0.0 {
+            break (t, t + step)
+        }
+        t += step;
+        value = next_value;
+        steps += 1;
+    };
+
+    // Narrow down the crossing
+    while (after - before) * speed > precision {
+        let middle = 0.5 * (before + after);
+        if field(&ray.at(middle)) * side > 0.0 {
+            before = middle;
+        } else {
+            after = middle;
+        }
+    }
+    // Keep the side the ray comes from, so that the bounces do not start behind the surface
+    let t = before;
+    if t < ray.t_min || t > ray.t_max {
+        return None
     }
 
+    // The normal is the gradient of the field
     let position = ray.at(t);
-    let normal = (position - center).normalize();
-    let uv = vector![0.5 - normal.z.atan2(normal.x) / TAU, normal.y.asin() / PI + 0.5];
-    Some((Hit {t, position, normal, uv}, material))
+    let e = 1e-4 * radius;
+    let gradient = vector![
+        field(&(position + vector![e, 0.0, 0.0])) - field(&(position - vector![e, 0.0, 0.0])),
+        field(&(position + vector![0.0, e, 0.0])) - field(&(position - vector![0.0, e, 0.0])),
+        field(&(position + vector![0.0, 0.0, e])) - field(&(position - vector![0.0, 0.0, e]))
+    ];
+    let direction = (position - center).normalize();
+    let normal = if gradient.norm_squared() > 0.0 { gradient.normalize() } else { direction };
+    let tangent = tangent_along(&normal, &vector![direction.z, 0.0, -direction.x]);
+    let differentials = ray.surface_differentials(t, &normal);
+    let uv = sphere_uv(&direction);
+    let hit = Hit {t, position, normal, geometric_normal: normal, uv, tangent, color: WHITE, differentials};
+    Some((hit, material))
 }
 
-fn hit_triangle(triangle: TriangleId, mesh: MeshId, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
-    // https://facultyweb.cs.wwu.edu/~wehrwes/courses/csci480_20w/lectures/L10/L10.pdf
-    let triangle = scene_data.mesh_table[mesh.to_index()].get_triangle(triangle);
-    let a = triangle.0.position;
-    let b = triangle.1.position;
-    let c = triangle.2.position;
-    let ba = a - b;
-    let ca = a - c;
-    let pa = a - ray.origin;
-    let d = ray.direction;
+fn hit_capsule(a: &Rvec3, b: &Rvec3, radius: Real, material: MaterialId, ray: &Ray) -> Option<(Hit, MaterialId)> {
+    // The surface is made of three pieces: the side of the cylinder where 0 < y < |ab|², the half-sphere around a
+    // where y <= 0 and the half-sphere around b where y >= |ab|², with y the projection on the axis times |ab|.
+    // Every root of every piece is a candidate, so that the ray can also hit the capsule from the inside.
+    let ab = b - a;
+    let ab2 = ab.norm_squared();
+    let axial = |p: &Rvec3| (p - a).dot(&ab);
+    let in_range = |t: Real| t >= ray.t_min && t <= ray.t_max;
+    let mut best: Option<(Real, Rvec3)> = None; // (t, center of the normal)
+    let mut consider = |t: Real, center: Rvec3| {
+        if in_range(t) && !matches!(best, Some((best_t, _)) if best_t <= t) {
+            best = Some((t, center));
+        }
+    };
+
+    // Cylinder side
+    let ao = ray.origin - a;
+    let d_ab = ray.direction.dot(&ab);
+    let ao_ab = ao.dot(&ab);
+    let qa = ab2 - d_ab * d_ab;
+    let qb = ab2 * ray.direction.dot(&ao) - ao_ab * d_ab;
+    let qc = ab2 * ao.norm_squared() - ao_ab * ao_ab - radius * radius * ab2;
+    let delta = qb * qb - qa * qc;
+    if qa > SMOL && delta > 0.0 {
+        let sqrt_delta = delta.sqrt();
+        for t in [(-qb - sqrt_delta) / qa, (-qb + sqrt_delta) / qa] {
+            let y = axial(&ray.at(t));
+            if y > 0.0 && y < ab2 {
+                consider(t, a + ab * (y / ab2));
+            }
+        }
+    }
+
+    // Half-spheres
+    for (center, is_end) in [(a, false), (b, true)] {
+        let to_center = ray.origin - center;
+        let half_b = ray.direction.dot(&to_center);
+        let delta = half_b * half_b - (to_center.norm_squared() - radius * radius);
+        if delta > 0.0 {
+            let sqrt_delta = delta.sqrt();
+            for t in [-half_b - sqrt_delta, -half_b + sqrt_delta] {
+                let y = axial(&ray.at(t));
+                if (is_end && y >= ab2) || (!is_end && y <= 0.0) {
+                    consider(t, *center);
+                }
+            }
+        }
+    }
+
+    let (t, normal_center) = best?;
+    let position = ray.at(t);
+    let normal = (position - normal_center) / radius;
+
+    // Texture coordinates: u follows the axis, v goes around it
+    let (axis, u) = if ab2 > 0.0 {
+        (ab / ab2.sqrt(), (axial(&position) / ab2).clamp(0.0, 1.0))
+    } else {
+        (vector![0.0, 1.0, 0.0], 0.5)
+    };
+    let (x, y) = orthonormal_basis(&axis);
+    let v = 0.5 + normal.dot(&y).atan2(normal.dot(&x)) / TAU;
+    let tangent = tangent_along(&normal, &axis);
+    let differentials = ray.surface_differentials(t, &normal);
+    let uv = vector![u, v];
+    Some((Hit {t, position, normal, geometric_normal: normal, uv, tangent, color: WHITE, differentials}, material))
+}
 
-    // Solve this system of equations: [ a-b  a-c  d ] * [ u  v  t ]^T = a-p
-    let det = ba.x * ca.y * d.z + ba.y * ca.z * d.x + ba.z * ca.x * d.y
-            - ba.x * ca.z * d.y - ba.y * ca.x * d.z - ba.z * ca.y * d.x;
+pub(crate) fn hit_triangle(triangle: TriangleId, mesh: MeshId, ray: &Ray, scene_data: &SceneData)
+    -> Option<(Hit, MaterialId)>
+{
+    // Möller–Trumbore, using the edges precomputed by the mesh
+    // https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+    let mesh = &scene_data.mesh_table[mesh.to_index()];
+    let edges = mesh.get_edges(triangle);
+    let p = ray.direction.cross(&edges.edge2);
+    let det = edges.edge1.dot(&p);
 
     if det.abs() < SMOL {
         return None
     }
     let inv_det = 1.0 / det;
 
-    let t = (pa.x * (ba.y * ca.z - ba.z * ca.y)
-           + pa.y * (ba.z * ca.x - ba.x * ca.z)
-           + pa.z * (ba.x * ca.y - ba.y * ca.x)) * inv_det;
-    
-    let u = (pa.x * (ca.y * d.z - ca.z * d.y)
-           + pa.y * (ca.z * d.x - ca.x * d.z)
-           + pa.z * (ca.x * d.y - ca.y * d.x)) * inv_det;
+    let to_origin = ray.origin - edges.origin;
+    let u = to_origin.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None
+    }
 
-    let v = (pa.x * (ba.z * d.y - ba.y * d.z)
-           + pa.y * (ba.x * d.z - ba.z * d.x)
-           + pa.z * (ba.y * d.x - ba.x * d.y)) * inv_det;
-    
-    let w = 1.0 - u - v;
-        
-    if t < ray.t_min || t > ray.t_max || u < 0.0 || v < 0.0 || w < 0.0 {
+    let q = to_origin.cross(&edges.edge1);
+    let v = ray.direction.dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
         return None
     }
 
-    // Interpolate the normals and texture coordinates
+    let t = edges.edge2.dot(&q) * inv_det;
+    if t < ray.t_min || t > ray.t_max {
+        return None
+    }
+
+    // Interpolate the normals, texture coordinates and colors
+    let w = 1.0 - u - v;
+    let triangle = mesh.get_triangle(triangle);
     let position = ray.at(t);
     let normal = w * triangle.0.normal + u * triangle.1.normal + v * triangle.2.normal;
     let uv = w * triangle.0.uv + u * triangle.1.uv + v * triangle.2.uv;
-    Some((Hit {t, position, normal, uv}, scene_data.mesh_table[mesh.to_index()].material))
+    let color = w * triangle.0.color + u * triangle.1.color + v * triangle.2.color;
+
+    // The tangent is dP/du, found by inverting the texture coordinates deltas along the edges
+    let duv1 = triangle.1.uv - triangle.0.uv;
+    let duv2 = triangle.2.uv - triangle.0.uv;
+    let uv_det = duv1.x * duv2.y - duv2.x * duv1.y;
+    let dp_du = if uv_det.abs() > SMOL {
+        (edges.edge1 * duv2.y - edges.edge2 * duv1.y) / uv_det
+    } else {
+        // No usable texture coordinates, any tangent will do
+        Rvec3::zeros()
+    };
+    let tangent = tangent_along(&normal, &dp_du);
+    let face_normal = edges.edge1.cross(&edges.edge2).normalize();
+    let geometric_normal = if face_normal.dot(&normal) < 0.0 {-face_normal} else {face_normal};
+    // The footprint lies on the flat triangle, not on the smooth surface of the interpolated normals
+    let differentials = if ray.differentials.is_some() {
+        ray.surface_differentials(t, &face_normal)
+    } else {
+        None
+    };
+    Some((Hit {t, position, normal, geometric_normal, uv, tangent, color, differentials}, mesh.material))
 }
 
 fn hit_list(list: &[Hittable], ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
@@ -119,6 +493,95 @@ fn hit_list(list: &[Hittable], ray: &Ray, scene_data: &SceneData) -> Option<(Hit
     hit
 }
 
+/// The part of a ray that is inside a convex object
+struct Span {
+    /// None if the ray starts inside
+    entry: Option<(Hit, MaterialId)>,
+    /// None if the ray never leaves
+    exit: Option<(Hit, MaterialId)>,
+}
+
+impl Span {
+    fn contains(&self, t: Real) -> bool {
+        let t_entry = self.entry.as_ref().map_or(-Real::INFINITY, |(hit, _)| hit.t);
+        let t_exit = self.exit.as_ref().map_or(Real::INFINITY, |(hit, _)| hit.t);
+        t > t_entry && t < t_exit
+    }
+}
+
+/// Find where the ray enters and leaves a convex object, beyond the end of the ray if needed
+fn convex_span(hittable: &Hittable, ray: &Ray, scene_data: &SceneData) -> Option<Span> {
+    let ray = Ray {t_max: Real::INFINITY, ..ray.clone()};
+    let first = hittable.hit(&ray, scene_data)?;
+    if first.0.normal.dot(&ray.direction) > 0.0 {
+        return Some(Span {entry: None, exit: Some(first)})
+    }
+    let exit = hittable.hit(&Ray {t_min: first.0.t + SMOL, ..ray}, scene_data);
+    Some(Span {entry: Some(first), exit})
+}
+
+fn hit_difference(a: &Hittable, b: &Hittable, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
+    let span_a = convex_span(a, ray, scene_data)?;
+    let span_b = match convex_span(b, ray, scene_data) {
+        Some(span_b) => span_b,
+        None => return a.hit(ray, scene_data),
+    };
+
+    // The surface of a is kept outside of b, and the surface of b is kept inside of a, turned inside out
+    let surface_a = span_a.entry.iter().chain(&span_a.exit)
+        .filter(|(hit, _)| !span_b.contains(hit.t))
+        .cloned();
+    let surface_b = span_b.entry.iter().chain(&span_b.exit)
+        .filter(|(hit, _)| span_a.contains(hit.t))
+        .map(|(hit, material)| {
+            (Hit {normal: -hit.normal, geometric_normal: -hit.geometric_normal, ..hit.clone()}, *material)
+        });
+    surface_a.chain(surface_b)
+        .filter(|(hit, _)| hit.t >= ray.t_min && hit.t <= ray.t_max)
+        .min_by(|(x, _), (y, _)| x.t.partial_cmp(&y.t).unwrap())
+}
+
+fn hit_flip_normals(inner: &Hittable, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
+    inner.hit(ray, scene_data).map(|(hit, material)| {
+        (Hit {normal: -hit.normal, geometric_normal: -hit.geometric_normal, ..hit}, material)
+    })
+}
+
+/// The ray in the frame of the child of Hittable::Translate, where it starts at origin - offset
+fn translate_to_local(offset: &Rvec3, ray: &Ray) -> Ray {
+    ray.transform(&Transformation::new(Rmat3::identity(), -offset))
+}
+
+/// Hit the child in its own frame
+fn hit_translate(offset: &Rvec3, child: &Hittable, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
+    let local_ray = translate_to_local(offset, ray);
+    child.hit(&local_ray, scene_data).map(|(hit, material)| (Hit {position: hit.position + offset, ..hit}, material))
+}
+
+/// The rotation of Hittable::RotateY, from the frame of the child to the world
+pub(crate) fn rotation_y(angle: Real) -> Rmat3 {
+    Rotation::AxisAngle {axis: vector![0.0, 1.0, 0.0], angle}.matrix()
+}
+
+/// The ray in the frame of the child of Hittable::RotateY, where it is turned by -angle
+fn rotate_y_to_local(angle: Real, ray: &Ray) -> Ray {
+    ray.transform(&Transformation::new(rotation_y(angle).transpose(), Rvec3::zeros()))
+}
+
+/// Hit the child in its own frame
+fn hit_rotate_y(angle: Real, child: &Hittable, ray: &Ray, scene_data: &SceneData) -> Option<(Hit, MaterialId)> {
+    let to_world = rotation_y(angle);
+    let local_ray = rotate_y_to_local(angle, ray);
+    child.hit(&local_ray, scene_data).map(|(hit, material)| (Hit {
+        position: to_world * hit.position,
+        normal: to_world * hit.normal,
+        geometric_normal: to_world * hit.geometric_normal,
+        tangent: to_world * hit.tangent,
+        differentials: hit.differentials.as_ref().map(|d| d.rotate(&to_world)),
+        ..hit
+    }, material))
+}
+
 // ------------------------------------------- Bounding box implementation -------------------------------------------
 
 fn bounding_box_sphere(center: &Rvec3, radius: Real) -> AABB {
@@ -128,7 +591,7 @@ fn bounding_box_sphere(center: &Rvec3, radius: Real) -> AABB {
     }
 }
 
-fn bounding_box_triangle(triangle: TriangleId, mesh: MeshId, scene_data: &SceneData) -> AABB {
+pub(crate) fn bounding_box_triangle(triangle: TriangleId, mesh: MeshId, scene_data: &SceneData) -> AABB {
     let triangle = scene_data.mesh_table[mesh.to_index()].get_triangle(triangle);
     let a = triangle.0.position;
     let b = triangle.1.position;
@@ -140,8 +603,5 @@ fn bounding_box_triangle(triangle: TriangleId, mesh: MeshId, scene_data: &SceneD
 }
 
 fn bounding_box_list(list: &[Hittable], scene_data: &SceneData) -> AABB {
-    if list.is_empty() {
-        return AABB::default();
-    }
-    list.iter().skip(1).fold(list[0].bounding_box(scene_data), |aabb, x| aabb.union(&x.bounding_box(scene_data)))
+    list.iter().fold(AABB::empty(), |aabb, x| aabb.union(&x.bounding_box(scene_data)))
 }
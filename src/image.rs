@@ -8,6 +8,7 @@ In this file:
 // ------------------------------------------- Image storage -------------------------------------------
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Array2d<T> {
     width: u32,
     height: u32,
@@ -20,6 +21,28 @@ impl<'a, T: Clone + Default + 'a> Array2d<T> {
         Array2d {width, height, storage}
     }
 
+    /// Wrap an existing buffer of width x height elements, stored row by row, without copying it
+    pub fn from_raw(width: u32, height: u32, storage: Vec<T>) -> Result<Self, Box<dyn std::error::Error>> {
+        let len = width as usize * height as usize;
+        if storage.len() != len {
+            return Err(format!("A {}x{} array needs {} elements, not {}", width, height, len, storage.len()).into())
+        }
+        Ok(Array2d {width, height, storage})
+    }
+
+    /// The buffer with the elements stored row by row
+    pub fn into_raw(self) -> Vec<T> {
+        self.storage
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.storage
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.storage
+    }
+
     pub fn width(&self) -> u32 {
         self.width
     }
@@ -35,6 +58,40 @@ impl<'a, T: Clone + Default + 'a> Array2d<T> {
     pub fn get_mut(&mut self, i: u32, j: u32) -> &mut T {
         &mut self.storage[(i + j * self.width) as usize]
     }
+
+    /// The elements of the row j, which are contiguous since the storage is row by row
+    pub fn row(&self, j: u32) -> &[T] {
+        let start = (j * self.width) as usize;
+        &self.storage[start..start + self.width as usize]
+    }
+
+    pub fn row_mut(&mut self, j: u32) -> &mut [T] {
+        let start = (j * self.width) as usize;
+        &mut self.storage[start..start + self.width as usize]
+    }
+
+    /// A new array where the element (i, j) is the element (j, i) of this one, so that the columns become rows
+    pub fn transpose(&self) -> Array2d<T> {
+        let storage = (0..self.width)
+            .flat_map(|i| (0..self.height).map(move |j| self.get(i, j).clone()))
+            .collect();
+        Array2d {width: self.height, height: self.width, storage}
+    }
+
+    /// A new array with f applied to each element
+    pub fn map<U>(&self, f: impl Fn(&T) -> U) -> Array2d<U> {
+        Array2d {width: self.width, height: self.height, storage: self.storage.iter().map(f).collect()}
+    }
+
+    /// A new array with f applied to each pair of elements at the same position. Both arrays must have the same size.
+    pub fn zip_map<U, V>(&self, other: &Array2d<U>, f: impl Fn(&T, &U) -> V) -> Array2d<V> {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "Cannot zip a {}x{} array with a {}x{} array", self.width, self.height, other.width, other.height
+        );
+        let storage = self.storage.iter().zip(&other.storage).map(|(a, b)| f(a, b)).collect();
+        Array2d {width: self.width, height: self.height, storage}
+    }
 }
 
 // ------------------------------------------- Image loading and saving -------------------------------------------
@@ -46,7 +103,6 @@ pub mod tga {
     use std::io::{Read, Write, BufReader, BufWriter};
     use std::error::Error;
 
-    #[repr(C)]
     #[derive(Default, Debug)]
     // See http://paulbourke.net/dataformats/tga/
     struct TgaHeader {
@@ -62,57 +118,105 @@ pub mod tga {
         image_desc:     u8,
     }
 
+    /// Size of the header in the file, where the fields are packed and the 16 bits ones are little-endian
+    const HEADER_SIZE: usize = 18;
+
     impl TgaHeader {
-        fn buffer(&mut self) -> &mut [u8] {
-            unsafe {
-                std::slice::from_raw_parts_mut(self as *mut _ as *mut u8, std::mem::size_of::<Self>())
+        fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Self {
+            let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+            TgaHeader {
+                id_length: bytes[0],
+                colormap_type: bytes[1],
+                datatype_code: bytes[2],
+                colormap_spec: [bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]],
+                x_origin: u16_at(8),
+                y_origin: u16_at(10),
+                width: u16_at(12),
+                height: u16_at(14),
+                bits_per_pixel: bytes[16],
+                image_desc: bytes[17],
             }
         }
+
+        fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+            let mut bytes = [0; HEADER_SIZE];
+            bytes[0] = self.id_length;
+            bytes[1] = self.colormap_type;
+            bytes[2] = self.datatype_code;
+            bytes[3..8].copy_from_slice(&self.colormap_spec);
+            bytes[8..10].copy_from_slice(&self.x_origin.to_le_bytes());
+            bytes[10..12].copy_from_slice(&self.y_origin.to_le_bytes());
+            bytes[12..14].copy_from_slice(&self.width.to_le_bytes());
+            bytes[14..16].copy_from_slice(&self.height.to_le_bytes());
+            bytes[16] = self.bits_per_pixel;
+            bytes[17] = self.image_desc;
+            bytes
+        }
     }
 
+    /// Uncompressed color images of 15, 16, 24 or 32 bits per pixel, and grayscale images of 8 bits, or 16 with alpha.
+    /// They are all expanded to RGBA, the grayscale ones with the same value in the 3 channels.
     pub fn load(path: &str) -> Result<Array2d<[u8; 4]>, Box<dyn Error>> {
         let mut file = BufReader::new(File::open(path)?);
         
         // Read header
-        let mut header = TgaHeader::default();
-        file.read_exact(header.buffer())?;
+        let mut bytes = [0; HEADER_SIZE];
+        file.read_exact(&mut bytes)?;
+        let header = TgaHeader::from_bytes(&bytes);
 
         // Check header
         let mut header_ok = true;
         header_ok &= header.id_length == 0;
         header_ok &= header.colormap_type == 0;
-        header_ok &= header.datatype_code == 2; // 2 = uncompressed color data
-        header_ok &= header.bits_per_pixel == 24 || header.bits_per_pixel == 32; // BGR or BGRA
+        header_ok &= match header.datatype_code {
+            2 => matches!(header.bits_per_pixel, 15 | 16 | 24 | 32), // 2 = uncompressed color data
+            3 => matches!(header.bits_per_pixel, 8 | 16), // 3 = uncompressed grayscale
+            _ => false,
+        };
         if !header_ok {
             return Err(format!("This tga header is not supported: {:?}", header).into())
         }
+        // The low bits of the descriptor count the alpha bits, a 16 bits pixel has either 1 or none
+        let alpha_bits = header.image_desc & 0x0f;
 
-        // Read data
+        // Read all the pixels at once, then swizzle them in memory
         let mut image = Array2d::new(header.width as u32, header.height as u32);
-        for y in 0..image.height {
-            for x in 0..image.width {
-                // To flip vertically or not
-                let y = if (header.image_desc & 1 << 5) != 0 {
-                    image.height - 1 - y
-                } else {
-                    y
+        let bytes_per_pixel = (header.bits_per_pixel as usize).div_ceil(8);
+        let mut data = vec![0; image.width as usize * image.height as usize * bytes_per_pixel];
+        file.read_exact(&mut data)?;
+
+        let row_size = image.width as usize * bytes_per_pixel;
+        for (y, row) in data.chunks_exact(row_size.max(1)).enumerate() {
+            // To flip vertically or not
+            let y = if (header.image_desc & 1 << 5) != 0 {
+                image.height - 1 - y as u32
+            } else {
+                y as u32
+            };
+            for (x, pixel) in row.chunks_exact(bytes_per_pixel).enumerate() {
+                *image.get_mut(x as u32, y) = match (header.datatype_code, pixel) {
+                    (2, [b, g, r, a]) => [*r, *g, *b, *a], // BGRA
+                    (2, [b, g, r]) => [*r, *g, *b, 0xff], // BGR
+                    (2, [low, high]) => from_argb1555(u16::from_le_bytes([*low, *high]), alpha_bits == 1),
+                    (3, [l]) => [*l, *l, *l, 0xff],
+                    (3, [l, a]) => [*l, *l, *l, *a],
+                    _ => unreachable!(),
                 };
-                if header.bits_per_pixel == 32 {
-                    // BGRA
-                    let mut bgra = [0; 4];
-                    file.read_exact(&mut bgra)?;
-                    *image.get_mut(x, y) = [bgra[2], bgra[1], bgra[0], bgra[3]];
-                } else if header.bits_per_pixel == 24 { 
-                    // BGR
-                    let mut bgr = [0; 3];
-                    file.read_exact(&mut bgr)?;
-                    *image.get_mut(x, y) = [bgr[2], bgr[1], bgr[0], 0xff];
-                }
             }
         }
         Ok(image)
     }
 
+    /// A 16 bits pixel, ARRRRRGG GGGBBBBB, with 5 bits per channel stretched to 8 bits
+    fn from_argb1555(pixel: u16, has_alpha: bool) -> [u8; 4] {
+        let channel = |shift: u16| {
+            let x = (pixel >> shift) & 0x1f;
+            ((x << 3) | (x >> 2)) as u8
+        };
+        let alpha = if !has_alpha || pixel & 0x8000 != 0 {0xff} else {0};
+        [channel(10), channel(5), channel(0), alpha]
+    }
+
     pub fn save(image: &Array2d<[u8; 4]>, path: &str) -> Result<(), Box<dyn Error>> {
         let mut file = BufWriter::new(File::create(path)?);
         let mut header = TgaHeader::default();
@@ -124,7 +228,7 @@ pub mod tga {
         header.height = image.height().try_into()?;
 
         // Write header
-        file.write(header.buffer())?;
+        file.write_all(&header.to_bytes())?;
 
         // Write data
         for y in 0..image.height {
@@ -137,6 +241,118 @@ pub mod tga {
     }
 }
 
+/// Radiance RGBE images (.hdr), which keep the colors brighter than white, like the sun in an environment map
+pub mod hdr {
+    use super::*;
+    use crate::utility::*;
+    use std::fs::File;
+    use std::io::{BufRead, Read, BufReader};
+    use std::error::Error;
+
+    // See https://paulbourke.net/dataformats/pic/ and Graphics Gems II, Real Pixels
+    pub fn load(path: &str) -> Result<Array2d<Color>, Box<dyn Error>> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        // Read the header, made of text lines up to an empty one
+        let mut line = String::new();
+        file.read_line(&mut line)?;
+        if !line.starts_with("#?") {
+            return Err(format!("{} is not a Radiance hdr file", path).into())
+        }
+        let mut exposure = 1.0;
+        loop {
+            line.clear();
+            if file.read_line(&mut line)? == 0 {
+                return Err("The hdr header does not end".into())
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break
+            } else if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(format!("This hdr format is not supported: {}", format).into())
+                }
+            } else if let Some(value) = line.strip_prefix("EXPOSURE=") {
+                // The pixels were multiplied by the exposure, which may be given several times
+                exposure *= value.trim().parse::<Real>()?;
+            }
+        }
+
+        // Then the resolution, with the rows from top to bottom (-Y) or from bottom to top (+Y)
+        line.clear();
+        file.read_line(&mut line)?;
+        let (top_down, height, width) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (true, height.parse::<u32>()?, width.parse::<u32>()?),
+            ["+Y", height, "+X", width] => (false, height.parse::<u32>()?, width.parse::<u32>()?),
+            _ => return Err(format!("This hdr resolution is not supported: {}", line.trim_end()).into()),
+        };
+
+        let mut image = Array2d::new(width, height);
+        let mut scanline = vec![[0u8; 4]; width as usize];
+        for y in 0..height {
+            read_scanline(&mut file, &mut scanline)?;
+            let y = if top_down {y} else {height - 1 - y};
+            for (x, rgbe) in scanline.iter().enumerate() {
+                *image.get_mut(x as u32, y) = rgbe_to_color(rgbe) / exposure;
+            }
+        }
+        Ok(image)
+    }
+
+    /// A shared exponent for the 3 channels, biased by 128, and 8 bits of mantissa each
+    fn rgbe_to_color(rgbe: &[u8; 4]) -> Color {
+        if rgbe[3] == 0 {
+            return rgb(0.0, 0.0, 0.0)
+        }
+        let scale = (2.0 as Real).powi(rgbe[3] as i32 - (128 + 8));
+        rgb(rgbe[0] as Real, rgbe[1] as Real, rgbe[2] as Real) * scale
+    }
+
+    fn read_scanline(file: &mut impl Read, scanline: &mut [[u8; 4]]) -> Result<(), Box<dyn Error>> {
+        let width = scanline.len();
+        let mut start = [0u8; 4];
+        file.read_exact(&mut start)?;
+
+        // The run length encoding starts with 2, 2 and the width, it is only used for widths from 8 to 32767
+        let encoded = (8..0x8000).contains(&width) && start[0] == 2 && start[1] == 2 && start[2] & 0x80 == 0;
+        if !encoded {
+            scanline[0] = start;
+            for pixel in scanline[1..].iter_mut() {
+                file.read_exact(pixel)?;
+            }
+            return Ok(())
+        }
+        if ((start[2] as usize) << 8 | start[3] as usize) != width {
+            return Err("The length of an hdr scanline does not match the width".into())
+        }
+
+        // Each channel is stored on its own, as runs of a repeated byte or as literal bytes
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let mut byte = [0u8; 1];
+                file.read_exact(&mut byte)?;
+                let run = byte[0] > 128;
+                let count = if run {byte[0] as usize - 128} else {byte[0] as usize};
+                if count == 0 || x + count > width {
+                    return Err("An hdr scanline is corrupted".into())
+                }
+                if run {
+                    let mut value = [0u8; 1];
+                    file.read_exact(&mut value)?;
+                    scanline[x..x + count].iter_mut().for_each(|pixel| pixel[channel] = value[0]);
+                } else {
+                    let mut values = vec![0u8; count];
+                    file.read_exact(&mut values)?;
+                    scanline[x..x + count].iter_mut().zip(values).for_each(|(pixel, value)| pixel[channel] = value);
+                }
+                x += count;
+            }
+        }
+        Ok(())
+    }
+}
+
 // ------------------------------------------- Image tiling -------------------------------------------
 
 #[derive(Debug, Clone)]
@@ -165,4 +381,49 @@ impl Tile {
         }
         tile_descriptions
     }
+
+    /// Same tiles, ordered by distance to the image center and spiraling outward.
+    /// The first tile contains the center pixel, so that the subject shows up before the corners.
+    pub fn split_spiral(full_width: u32, full_height: u32, tile_width: u32, tile_height: u32) -> Vec<Tile> {
+        let (center_i, center_j) = (full_width / 2, full_height / 2);
+        let mut tiles = Tile::split_in_tiles(full_width, full_height, tile_width, tile_height);
+        tiles.sort_by(|a, b| {
+            a.spiral_key(center_i, center_j).partial_cmp(&b.spiral_key(center_i, center_j)).unwrap()
+        });
+        tiles
+    }
+
+    /// Tiles are assembled row by row, then from left to right within a row
+    pub fn assembly_key(&self) -> (u32, u32) {
+        (self.offset_j, self.offset_i)
+    }
+
+    /// Distance from the tile to a pixel (zero if it contains it), then distance from the tile center,
+    /// then angle around the pixel
+    fn spiral_key(&self, i: u32, j: u32) -> (u32, f64, f64) {
+        let gap = |p: u32, offset: u32, size: u32| (offset.saturating_sub(p)).max(p.saturating_sub(offset + size - 1));
+        let (gap_i, gap_j) = (gap(i, self.offset_i, self.width), gap(j, self.offset_j, self.height));
+        let di = (self.offset_i as f64 + 0.5 * self.width as f64) - (i as f64 + 0.5);
+        let dj = (self.offset_j as f64 + 0.5 * self.height as f64) - (j as f64 + 0.5);
+        (gap_i * gap_i + gap_j * gap_j, di.hypot(dj), dj.atan2(di))
+    }
+}
+
+/// Paste the buffers of the tiles into one image. They are sorted first, so that the result does not depend on the
+/// order in which the tiles were completed, even where they overlap.
+pub fn assemble_tiles<'a, T: Clone + Default + 'a>(width: u32, height: u32,
+    tiles: impl IntoIterator<Item = (&'a Tile, &'a Array2d<T>)>) -> Array2d<T>
+{
+    let mut tiles: Vec<_> = tiles.into_iter().collect();
+    tiles.sort_by_key(|(tile, _)| tile.assembly_key());
+
+    let mut image = Array2d::new(width, height);
+    for (tile, buffer) in tiles {
+        for tj in 0..tile.height {
+            for ti in 0..tile.width {
+                *image.get_mut(ti + tile.offset_i, tj + tile.offset_j) = buffer.get(ti, tj).clone();
+            }
+        }
+    }
+    image
 }
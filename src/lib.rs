@@ -7,4 +7,7 @@ pub mod bvh;
 pub mod texture;
 pub mod render;
 pub mod randomness;
-pub mod mesh;
\ No newline at end of file
+pub mod mesh;
+pub mod light;
+pub mod ambient;
+pub mod example_scenes;
\ No newline at end of file
@@ -0,0 +1,220 @@
+/*
+In this file:
+- Light list = the emissive objects of the scene that can be sampled directly
+- Light sampling
+- Point and spot lights = lights without area, that can only be sampled
+*/
+
+use crate::utility::*;
+use crate::randomness::*;
+use crate::hittable::Hittable;
+use crate::material::MaterialId;
+use crate::mesh::{MeshId, TriangleId};
+use crate::render::SceneData;
+
+// ------------------------------------------- Light list -------------------------------------------
+
+#[derive(Debug, Clone)]
+enum LightShape {
+    Sphere {center: Rvec3, radius: Real},
+    Triangle {a: Rvec3, b: Rvec3, c: Rvec3},
+}
+
+impl LightShape {
+    fn area(&self) -> Real {
+        match self {
+            Self::Sphere {radius, ..} => 2.0 * TAU * radius * radius,
+            Self::Triangle {a, b, c} => 0.5 * (b - a).cross(&(c - a)).norm(),
+        }
+    }
+
+    /// Move a shape by a rigid transformation
+    fn transform(&mut self, transformation: &Transformation) {
+        match self {
+            Self::Sphere {center, ..} => *center = transformation.transform_point(center),
+            Self::Triangle {a, b, c} => {
+                *a = transformation.transform_point(a);
+                *b = transformation.transform_point(b);
+                *c = transformation.transform_point(c);
+            }
+        }
+    }
+
+    /// A uniformly distributed point on the surface, and the normal there
+    fn sample(&self, rng: &mut Randomizer) -> (Rvec3, Rvec3) {
+        match self {
+            Self::Sphere {center, radius} => {
+                let normal: Rvec3 = rng.sample(UnitSphere);
+                (center + *radius * normal, normal)
+            }
+            Self::Triangle {a, b, c} => {
+                let (r1, r2) = (rng.gen::<Real>().sqrt(), rng.gen::<Real>());
+                let position = (1.0 - r1) * a + r1 * (1.0 - r2) * b + r1 * r2 * c;
+                (position, (b - a).cross(&(c - a)).normalize())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AreaLight {
+    shape: LightShape,
+    material: MaterialId,
+}
+
+/// A point picked on one of the lights
+#[derive(Debug, Clone)]
+pub struct LightSample {
+    pub position: Rvec3,
+    pub normal: Rvec3,
+    pub emission: Color,
+    /// Probability density over the area of the lights
+    pub pdf_area: Real,
+}
+
+/// The lights are the spheres and triangles whose material has a constant emission, found in the scene without having
+/// to be registered. They are picked proportionally to their power, so that the density of a point only depends on the
+/// luminance of its material.
+/// A material that is also used by an object that cannot be sampled (a capsule, a difference) is not a light at all,
+/// so that what the scattered rays find on it is never weighted as if the lights could have found it.
+#[derive(Debug, Clone, Default)]
+pub struct LightList {
+    lights: Vec<AreaLight>,
+    /// Cumulated power of the lights, to pick them by binary search
+    cumulated_power: Vec<Real>,
+    /// For each material, the density over the area of a point on it, zero if it is not a light
+    material_pdf: Vec<Real>,
+}
+
+impl LightList {
+    pub fn new(root: &Hittable, scene_data: &SceneData) -> Self {
+        let mut candidates = Vec::new();
+        let mut excluded = vec![false; scene_data.material_table.len()];
+        collect_lights(root, scene_data, &mut candidates, &mut excluded);
+
+        let luminances: Vec<Real> = scene_data.material_table.iter().zip(&excluded).map(|(material, &excluded)| {
+            match material.constant_emission() {
+                Some(emission) if !excluded => luminance(&emission).max(0.0),
+                _ => 0.0,
+            }
+        }).collect();
+
+        let lights: Vec<AreaLight> = candidates.into_iter()
+            .filter(|light| luminances[light.material.to_index()] > 0.0 && light.shape.area() > 0.0)
+            .collect();
+        let mut total_power = 0.0;
+        let cumulated_power = lights.iter().map(|light| {
+            total_power += light.shape.area() * luminances[light.material.to_index()];
+            total_power
+        }).collect();
+        let material_pdf = luminances.iter()
+            .map(|luminance| if total_power > 0.0 { luminance / total_power } else { 0.0 })
+            .collect();
+
+        LightList {lights, cumulated_power, material_pdf}
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// The number of spheres and triangles that can be sampled
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    /// Pick a point on the lights, proportionally to the power they emit
+    pub fn sample(&self, scene_data: &SceneData, rng: &mut Randomizer) -> Option<LightSample> {
+        let total_power = *self.cumulated_power.last()?;
+        let x = rng.gen::<Real>() * total_power;
+        let index = self.cumulated_power.partition_point(|&power| power <= x).min(self.lights.len() - 1);
+        let light = &self.lights[index];
+        let (position, normal) = light.shape.sample(rng);
+        let emission = scene_data.material(light.material).constant_emission()?;
+        Some(LightSample {position, normal, emission, pdf_area: self.pdf_area(light.material)})
+    }
+
+    /// Probability density over the area that sample picks a point with this material
+    pub fn pdf_area(&self, material: MaterialId) -> Real {
+        self.material_pdf.get(material.to_index()).copied().unwrap_or(0.0)
+    }
+}
+
+/// Only the primitives with an emissive material are kept, the rest of the scene does not make candidates
+fn collect_lights(root: &Hittable, scene_data: &SceneData, lights: &mut Vec<AreaLight>, excluded: &mut [bool]) {
+    let emissive = |material: MaterialId| scene_data.material(material).is_emissive();
+    root.visit_with_context(&mut |hittable, context| {
+        let first = lights.len();
+        match hittable {
+            Hittable::Sphere {center, radius, material} if emissive(*material) => {
+                let shape = LightShape::Sphere {center: *center, radius: *radius};
+                lights.push(AreaLight {shape, material: *material})
+            }
+            Hittable::Triangle {triangle, mesh} if emissive(scene_data.mesh_table[mesh.to_index()].material) => {
+                lights.push(triangle_light(*mesh, *triangle, scene_data))
+            }
+            Hittable::TriangleBvh(bvh) => lights.extend(bvh.leaves().iter()
+                .filter(|(mesh, _)| emissive(scene_data.mesh_table[mesh.to_index()].material))
+                .map(|(mesh, triangle)| triangle_light(*mesh, *triangle, scene_data))),
+            Hittable::Capsule {material, ..} | Hittable::SphereSection {material, ..}
+                | Hittable::DisplacedSphere {material, ..} => excluded[material.to_index()] = true,
+            // The lights emit on both sides, so FlipNormals changes nothing, and the other nodes only hold children
+            _ => (),
+        }
+        if context.in_difference {
+            // Whatever is inside cannot be sampled
+            lights.drain(first..).for_each(|light| excluded[light.material.to_index()] = true);
+        } else {
+            lights[first..].iter_mut().for_each(|light| light.shape.transform(&context.to_world));
+        }
+    });
+}
+
+fn triangle_light(mesh: MeshId, triangle: TriangleId, scene_data: &SceneData) -> AreaLight {
+    let mesh = &scene_data.mesh_table[mesh.to_index()];
+    let edges = mesh.get_edges(triangle);
+    let shape = LightShape::Triangle {a: edges.origin, b: edges.origin + edges.edge1, c: edges.origin + edges.edge2};
+    AreaLight {shape, material: mesh.material}
+}
+
+// ------------------------------------------- Point and spot lights -------------------------------------------
+
+/// A light concentrated in a point, stored in the light table of the scene. The rays cannot hit it, so it is only
+/// found by sampling it from each hit, and it casts hard shadows.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Light {
+    /// Shines the same in all directions, with an intensity per steradian
+    Point {position: Rvec3, color: Color, intensity: Real},
+    /// A point light that only shines in a cone of half angle cone_angle around the direction.
+    /// It fades out over the last edge_angle radians of the cone, zero for a sharp edge.
+    Spot {position: Rvec3, direction: Rvec3, cone_angle: Real, edge_angle: Real, color: Color, intensity: Real},
+}
+
+impl Light {
+    pub fn position(&self) -> &Rvec3 {
+        match self {
+            Self::Point {position, ..} | Self::Spot {position, ..} => position,
+        }
+    }
+
+    /// Light per steradian sent toward a unit direction
+    pub fn intensity(&self, direction: &Rvec3) -> Color {
+        match self {
+            Self::Point {color, intensity, ..} => *intensity * color,
+            Self::Spot {direction: axis, cone_angle, edge_angle, color, intensity, ..} => {
+                let cos_theta = direction.dot(&axis.normalize());
+                let (cos_outer, cos_inner) = (cone_angle.cos(), (cone_angle - edge_angle).max(0.0).cos());
+                let fade = if cos_inner > cos_outer {
+                    let t = ((cos_theta - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+                    t * t * (3.0 - 2.0 * t)
+                } else if cos_theta >= cos_outer {
+                    1.0
+                } else {
+                    0.0
+                };
+                fade * *intensity * color
+            }
+        }
+    }
+}
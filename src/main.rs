@@ -2,131 +2,191 @@ use raytracing2::image::*;
 use raytracing2::utility::*;
 use raytracing2::render::*;
 use raytracing2::randomness::*;
+use raytracing2::example_scenes;
 use std::time::Instant;
-use std::sync::{Arc, Mutex};
-use std::thread;
-use indicatif::ProgressBar;
 
-mod example_scenes;
+mod args;
+#[cfg(feature = "preview")]
+mod preview;
+
+use args::Verbosity;
 
 fn main() {
+    let args = args::Args::parse(std::env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("Error: {}\n{}", error, args::USAGE);
+        std::process::exit(1)
+    });
     let (output_width, output_height) = (800, 600);
 
     // Load the scene
+    let t_load = Instant::now();
     // let mut scene = example_scenes::three_balls();
     // let mut scene = example_scenes::two_balls();
     // let mut scene = example_scenes::more_balls_optimized();
     // let mut scene = example_scenes::earth();
+    // let mut scene = example_scenes::earth_uv_grid();
+    // let mut scene = example_scenes::earth_uvs();
+    // let mut scene = example_scenes::displaced_earth();
     // let mut scene = example_scenes::one_triangle();
+    // let mut scene = example_scenes::prism();
+    // let mut scene = example_scenes::bitten_ball();
+    // let mut scene = example_scenes::clearcoat_balls();
+    // let mut scene = example_scenes::bumpy_ball();
+    // let mut scene = example_scenes::veined_ball();
+    // let mut scene = example_scenes::bunny_orbit();
+    // let mut scene = example_scenes::glowing_panel();
+    // let mut scene = example_scenes::two_lights();
     let mut scene = example_scenes::bunny();
-    scene.camera.aspect_ratio = output_width as Real / output_height as Real;
-
-    // Renderer parameters
-    let max_bounce = 8; 
-    let tile_size = 32;
-    let num_workers = 4;
+    if args.verbosity >= Verbosity::Verbose {
+        println!("Scene loaded in {:.2} seconds", t_load.elapsed().as_secs_f64());
+    }
 
-    let sampler = Multisampler {
+    // Renderer parameters, with enough tiles to keep all the cores of the machine busy
+    let num_threads = available_threads();
+    let mut params = RenderParams {
         width: output_width,
         height: output_height,
         num_samples: 4,
+        filter: Filter::Tent {radius: 1.0},
+        max_bounce: 8,
+        tile_size: auto_tile_size(output_width, output_height, num_threads),
+        num_threads,
+        // Without a given seed, every run is different
+        seed: args.seed.unwrap_or_else(random),
+        frame: 0,
+        alpha_mode: AlphaMode::Opaque,
+        fast: args.fast,
+        min_roughness_after_bounce: args.min_roughness,
+        show_progress: true,
+        progress_template: args.progress_template.clone(),
     };
-    
-    // Put tiles into the job queue
-    let job_queue = Tile::split_in_tiles(output_width, output_height, tile_size, tile_size);
-    let progress_bar = ProgressBar::new(job_queue.len() as _);
-    
-    // Wrap the things into arcs
-    let scene = Arc::new(scene);
-    let job_queue = Arc::new(Mutex::new(job_queue));
-    let complete_jobs = Arc::new(Mutex::new(Vec::new()));
-    
-    // Start the rendering workers
-    let t0 = Instant::now();
-    let workers: Vec<_> = (0..num_workers).map(|_| {
-        let job_queue = Arc::clone(&job_queue);
-        let complete_jobs = Arc::clone(&complete_jobs);
-        let progress_bar = progress_bar.clone();
-        let sampler = sampler.clone();
-        let scene = Arc::clone(&scene);
-        let mut rng = Randomizer::from_entropy();
-
-        thread::spawn(move || {
-            loop {
-                let job = {
-                    // Momentarily lock the job queue to pop a new job
-                    job_queue.lock().unwrap().pop()
-                };
-
-                if let Some(tile) = job {
-                    // Create 3 buffers
-                    let mut color_buffer = Array2d::new(tile.width, tile.height);
-                    let mut foreground_buffer = Array2d::new(tile.width, tile.height);
-                    
-                    // Walk on each pixel of the tile
-                    for tj in 0..tile.height {
-                        for ti in 0..tile.width {
-                            // Jitter the sample inside its pixel
-                            let samples = sampler.make_uv_jitter(ti + tile.offset_i, tj + tile.offset_j, &mut rng);
-                            
-                            // Accumulate the values of each sample
-                            let mut final_color = rgb(0.0, 0.0, 0.0);
-                            let mut foreground = 0.0;
-                            for s in samples {
-                                let ray = scene.camera.shoot(s, &mut rng);
-                                let trace_out = trace_path(
-                                    &scene.root, &ray, max_bounce, &scene.scene_data, &mut rng, &scene.background
-                                );
-                                final_color += trace_out.final_color;
-                                if trace_out.hit {
-                                    foreground += 1.0;
-                                }
-                            }
-                            // Write the final color which is the average of the samples
-                            *color_buffer.get_mut(ti, tj) = final_color / sampler.num_samples as Real;
-                            *foreground_buffer.get_mut(ti, tj) = foreground / sampler.num_samples as Real;
-                        }
+
+    for frame in 0..args.frames {
+        // Put the scene in its state for this frame
+        scene.at_time(args.frame_time(frame));
+        scene.camera.aspect_ratio = output_width as Real / output_height as Real;
+
+        // Move the camera so that the whole scene is visible, handy for a freshly loaded mesh
+        let auto_frame = false;
+        if auto_frame {
+            scene.camera.look_at_scene(&scene.root, &scene.scene_data, &vector![0.0, 1.0, 0.0]);
+        }
+
+        // Put the big lists of objects into bounding volume hierarchies
+        let auto_bvh = true;
+        if auto_bvh {
+            let t_bvh = Instant::now();
+            scene.root.build_acceleration(&scene.scene_data);
+            if args.verbosity >= Verbosity::Verbose {
+                println!("BVH built in {:.2} seconds", t_bvh.elapsed().as_secs_f64());
+                println!("Scene: {} primitives", scene.root.count_primitives());
+            }
+        }
+
+        // Render, showing the progress in a window if asked, which blocks until it is closed
+        params.frame = frame;
+        let t0 = Instant::now();
+        let output = if args.progressive() {
+            let checkpoint_name = args.checkpoint_name(frame);
+            let mut accumulator = if args.resume && std::path::Path::new(&checkpoint_name).exists() {
+                Accumulator::load(&checkpoint_name).unwrap_or_else(|error| {
+                    eprintln!("Error: cannot resume from {}: {}", checkpoint_name, error);
+                    std::process::exit(1)
+                })
+            } else {
+                Accumulator::new(output_width, output_height)
+            };
+            if accumulator.samples().width() != output_width || accumulator.samples().height() != output_height {
+                eprintln!("Error: {} was rendered at another size", checkpoint_name);
+                std::process::exit(1)
+            }
+            let missing_passes = args.passes.saturating_sub(accumulator.passes());
+            if args.verbosity >= Verbosity::Normal && accumulator.passes() > 0 {
+                println!("Resuming after {} passes, {} left", accumulator.passes(), missing_passes);
+            }
+            render_progressive(&scene, &params, &mut accumulator, missing_passes, |accumulator| {
+                if let Err(error) = accumulator.save(&checkpoint_name) {
+                    eprintln!("Could not save the checkpoint {}: {}", checkpoint_name, error);
+                }
+                if args.verbosity >= Verbosity::Verbose {
+                    println!("Pass {} saved in {}", accumulator.passes(), checkpoint_name);
+                }
+            })
+        } else {
+            render_image_watched(&scene, &params, |_jobs| {
+                #[cfg(feature = "preview")]
+                if args.preview {
+                    if let Err(error) = preview::show_while_rendering(output_width, output_height, _jobs) {
+                        eprintln!("Could not show the preview: {}", error);
                     }
-                    // Push the finished job
-                    complete_jobs.lock().unwrap().push((tile, color_buffer, foreground_buffer));
-                    progress_bar.inc(1);
-                } else {
-                    break
                 }
-            }
-        })
-    }).collect();
+            })
+        };
+        let output = output.unwrap_or_else(|error| {
+            eprintln!("Error: {}", error);
+            std::process::exit(1)
+        });
+        let RenderOutput {color: mut color_image, foreground: mut foreground_image, object_id} = output;
+        if args.verbosity >= Verbosity::Normal {
+            println!("Rendering done in {:.2} seconds", t0.elapsed().as_secs_f64());
+        }
+        let t_save = Instant::now();
 
-    // Wait. Wait. Wait.
-    for w in workers {
-        w.join().unwrap();
-    }
+        // Make the highlights glow, while the colors are still linear and unclamped
+        let bloom_intensity = 0.0;
+        if bloom_intensity > 0.0 {
+            apply_bloom(&mut color_image, 1.0, bloom_intensity, 4.0);
+        }
 
-    progress_bar.finish();
-    println!("Rendering done in {:.2} seconds", t0.elapsed().as_secs_f64());
-
-    // Combine the tiles into one image
-    let complete_jobs = Arc::try_unwrap(complete_jobs).unwrap().into_inner().unwrap();
-    let mut output_image = Array2d::new(output_width, output_height);
-    let transparent_background = false;
-    for (tile, color_buffer, foreground_buffer) in complete_jobs {
-        for tj in 0..tile.height {
-            for ti in 0..tile.width {
-                let mut rgba = to_srgb_u8(color_buffer.get(ti, tj));
-                if transparent_background {
-                    rgba[3] = (255.0 * foreground_buffer.get(ti, tj)) as u8; // Transparent background
-                }
-                *output_image.get_mut(ti + tile.offset_i, tj + tile.offset_j) = rgba;
-            }
+        // Optical imperfections of a real camera
+        let vignette_strength = 0.0;
+        if vignette_strength > 0.0 {
+            apply_vignette(&mut color_image, vignette_strength);
+        }
+        let (distortion_k1, distortion_k2) = (0.0, 0.0);
+        if distortion_k1 != 0.0 || distortion_k2 != 0.0 {
+            apply_distortion(&mut color_image, distortion_k1, distortion_k2);
+            apply_distortion(&mut foreground_image, distortion_k1, distortion_k2);
         }
-    }
 
-    // Save the output in a file
-    let output_name = "output.tga";
-    tga::save(&output_image, output_name).unwrap();
+        // Draw the world axes on top of the render
+        let draw_axes = false;
+        if draw_axes {
+            draw_overlay_lines(&mut color_image, &scene.camera, &axes_overlay(&Rvec3::zeros(), 1.0));
+        }
 
-    // Open the output in the default image viewer
-    if cfg!(target_os = "windows") {
-        std::process::Command::new("cmd").args(["/c", output_name]).spawn().unwrap();
+        // Convert to 8 bits per channel
+        let output_image = color_image.zip_map(&foreground_image, |color, &coverage| {
+            to_srgb_u8_alpha(color, coverage, params.alpha_mode)
+        });
+
+        // Save the output in a file
+        tga::save(&output_image, &args.frame_name(frame)).unwrap();
+
+        // Save the object seen in each pixel in false colors next to the output, to make compositing masks.
+        // It is not distorted along with the colors.
+        let save_object_ids = false;
+        if save_object_ids {
+            let object_id_name = args.frame_name(frame).replace(".tga", "_id.tga");
+            tga::save(&object_id_colors(&object_id), &object_id_name).unwrap();
+        }
+        if args.verbosity >= Verbosity::Verbose {
+            println!("Image saved in {:.2} seconds", t_save.elapsed().as_secs_f64());
+        }
+    }
+
+    // Open the output (the first frame of an animation) in the default image viewer
+    if args.open {
+        let output_name = args.frame_name(0);
+        let viewer = if cfg!(target_os = "windows") {
+            std::process::Command::new("cmd").args(["/c", &output_name]).spawn()
+        } else if cfg!(target_os = "macos") {
+            std::process::Command::new("open").arg(&output_name).spawn()
+        } else {
+            std::process::Command::new("xdg-open").arg(&output_name).spawn()
+        };
+        if let Err(error) = viewer {
+            eprintln!("Could not open {}: {}", output_name, error);
+        }
     }
 }
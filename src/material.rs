@@ -10,64 +10,250 @@ use crate::utility::*;
 use crate::randomness::*;
 use crate::render::SceneData;
 use crate::texture::TextureId;
+use std::borrow::Cow;
 
 declare_index_wrapper!(MaterialId, u32);
 
 // ------------------------------------------- Scattering -------------------------------------------
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Scatter {
+    /// The ray stops here: only the emission is seen
     None,
-    Lambert,
+    /// The ray goes on through the surface as if it was not there, for the emitters that must not hide what is
+    /// behind them. The absorption of the material is not used, the light goes through unchanged. The shadow rays go
+    /// through too, so that the lights behind are still sampled, the point lights included.
+    Passthrough,
+    /// A two-sided surface scatters the rays that hit its back too, as if its normal faced them, for thin sheets like
+    /// leaves and paper. Otherwise its back is black.
+    Lambert {two_sided: bool},
     Metal {fuzziness: Real},
-    Dielectric {refraction_index: Real},
+    /// A rough conductor with the GGX microfacet distribution, where alpha = roughness²
+    Ggx {roughness: Real},
+    /// A metal given by its complex refraction index eta + i k for each channel, with the same microfacets as Ggx.
+    /// Its reflectance follows the Fresnel equations, and gets whiter toward the grazing angles. The absorption of
+    /// the material tints it on top of that, so it is usually WhiteBody. See GOLD, COPPER, SILVER and ALUMINIUM.
+    Conductor {eta: Color, k: Color, roughness: Real},
+    /// A glossy reflection with the normalized cosine-power lobe of Phong around the mirror direction, cheaper than
+    /// Ggx. The higher the shininess, the tighter the highlight. The specular color tints it on top of the absorption.
+    Phong {shininess: Real, specular: Color},
+    /// The refraction index is given for the yellow light, the optional Abbe number makes it vary with the wavelength
+    Dielectric {refraction_index: Real, abbe_number: Option<Real>},
+    /// A single interface with no thickness, like a window pane: the transmitted ray is not bent
+    ThinDielectric {refraction_index: Real},
+    /// Behave as the whole material a with probability factor, otherwise as the whole material b.
+    /// The absorption, emission, clearcoat and bump of the material holding the Mix are not used.
+    Mix {a: MaterialId, b: MaterialId, factor: Real},
 }
 
 impl Scatter {
-    pub fn evaluate(&self, incident: &Ray, hit: &Hit, _scene_data: &SceneData, rng: &mut Randomizer) -> Option<Ray> {
+    pub fn evaluate(&self, incident: &Ray, hit: &Hit, scene_data: &SceneData, rng: &mut Randomizer) -> Option<Ray> {
         match self {
             Self::None => None,
-            Self::Lambert => evaluate_lambert(incident, hit, rng),
+            Self::Passthrough => Some(incident.scatter(hit, incident.direction)),
+            Self::Lambert {two_sided} => evaluate_lambert(incident, &facing(incident, hit, *two_sided), rng),
             Self::Metal {fuzziness} => evaluate_metal(incident, hit, rng, *fuzziness),
-            Self::Dielectric {refraction_index} => evaluate_dielectric(incident, hit, rng, *refraction_index),
+            Self::Ggx {roughness} | Self::Conductor {roughness, ..} => evaluate_ggx(incident, hit, rng, *roughness),
+            Self::Phong {shininess, ..} => evaluate_phong(incident, hit, rng, *shininess),
+            Self::Dielectric {refraction_index, abbe_number}
+                => evaluate_dielectric(incident, hit, rng, *refraction_index, *abbe_number),
+            Self::ThinDielectric {refraction_index} => evaluate_thin_dielectric(incident, hit, rng, *refraction_index),
+            Self::Mix {a, b, factor} => {
+                let picked = scene_data.material(pick_mix(*a, *b, *factor, rng));
+                picked.scatter.evaluate(incident, hit, scene_data, rng)
+            }
+        }
+    }
+
+    /// The same scattering made at least this rough, see RenderParams::min_roughness_after_bounce
+    pub fn with_min_roughness(&self, min_roughness: Real) -> Scatter {
+        match self {
+            Self::Metal {fuzziness} => Self::Metal {fuzziness: fuzziness.max(min_roughness)},
+            Self::Ggx {roughness} => Self::Ggx {roughness: roughness.max(min_roughness)},
+            Self::Conductor {eta, k, roughness}
+                => Self::Conductor {eta: *eta, k: *k, roughness: roughness.max(min_roughness)},
+            other => other.clone(),
+        }
+    }
+
+    /// Probability density, over the solid angle, that evaluate picks the outgoing direction. It is 0 for the
+    /// mirrors and refractions, which pick a single direction, and for the scatterings that have no lobe: the fuzzy
+    /// metal and the mix, whose materials are only known from the scene data.
+    pub fn pdf(&self, incident: &Ray, hit: &Hit, outgoing: &Rvec3) -> Real {
+        self.lobe(incident, hit).map_or(0.0, |lobe| lobe.pdf(&incident.direction, outgoing))
+    }
+
+    /// The scattering as a function of the direction, if it is not a perfect mirror or refraction
+    /// that can only be found by sampling it
+    pub fn lobe(&self, incident: &Ray, hit: &Hit) -> Option<Lobe> {
+        match self {
+            Self::Lambert {two_sided} => Lobe::lambert(incident, &facing(incident, hit, *two_sided)),
+            Self::Ggx {roughness} | Self::Conductor {roughness, ..} => Lobe::ggx(incident, hit, *roughness),
+            Self::Phong {shininess, ..} => Lobe::phong(incident, hit, *shininess),
+            _ => None,
+        }
+    }
+}
+
+/// The hit seen from the side of the incident ray, when flip allows turning it around
+fn facing<'a>(incident: &Ray, hit: &'a Hit, flip: bool) -> Cow<'a, Hit> {
+    if flip && hit.normal.dot(&incident.direction) > 0.0 {
+        Cow::Owned(Hit {normal: -hit.normal, geometric_normal: -hit.geometric_normal, ..hit.clone()})
+    } else {
+        Cow::Borrowed(hit)
+    }
+}
+
+/// Below this alpha, the GGX distribution is too sharp to be evaluated in a given direction
+const MIN_GGX_ALPHA: Real = 1e-3;
+
+/// A scattering function that can be evaluated in any direction, so that the light sources can be sampled directly
+/// and weighted against the directions picked by the scattering itself
+#[derive(Debug, Clone)]
+pub enum Lobe {
+    Lambert {normal: Rvec3},
+    Ggx {normal: Rvec3, alpha: Real},
+    Phong {normal: Rvec3, reflected: Rvec3, shininess: Real},
+}
+
+impl Lobe {
+    fn lambert(incident: &Ray, hit: &Hit) -> Option<Lobe> {
+        (hit.normal.dot(&incident.direction) < 0.0).then_some(Lobe::Lambert {normal: hit.normal})
+    }
+
+    fn ggx(incident: &Ray, hit: &Hit, roughness: Real) -> Option<Lobe> {
+        let alpha = roughness * roughness;
+        (hit.normal.dot(&incident.direction) < 0.0 && alpha >= MIN_GGX_ALPHA)
+            .then_some(Lobe::Ggx {normal: hit.normal, alpha})
+    }
+
+    fn phong(incident: &Ray, hit: &Hit, shininess: Real) -> Option<Lobe> {
+        let reflected = reflect(&incident.direction, &hit.normal).normalize();
+        (hit.normal.dot(&incident.direction) < 0.0).then_some(Lobe::Phong {normal: hit.normal, reflected, shininess})
+    }
+
+    /// Probability density, over the solid angle, that the scattering of the incident direction picks the direction
+    pub fn pdf(&self, incident: &Rvec3, direction: &Rvec3) -> Real {
+        match self {
+            Self::Lambert {normal} => normal.dot(direction).max(0.0) / PI,
+            Self::Ggx {normal, alpha} => match ggx_terms(normal, *alpha, incident, direction) {
+                // Visible normals: D_v(h) = G1(v) max(0, v.h) D(h) / (v.n), and the reflection divides by 4 v.h
+                Some((d, g1, _, cos_view)) => g1 * d / (4.0 * cos_view),
+                None => 0.0,
+            }
+            Self::Phong {normal, reflected, shininess} if normal.dot(direction) > 0.0 => {
+                (shininess + 1.0) / TAU * reflected.dot(direction).max(0.0).powf(*shininess)
+            }
+            Self::Phong {..} => 0.0,
+        }
+    }
+
+    /// BSDF times the cosine with the normal, relative to the absorption of the material.
+    /// A ray scattered in this direction carries this value divided by its pdf.
+    pub fn value(&self, incident: &Rvec3, direction: &Rvec3) -> Real {
+        match self {
+            Self::Lambert {normal} => normal.dot(direction).max(0.0) / PI,
+            Self::Ggx {normal, alpha} => match ggx_terms(normal, *alpha, incident, direction) {
+                Some((d, _, g2, cos_view)) => d * g2 / (4.0 * cos_view),
+                None => 0.0,
+            }
+            // The factor (n + 2) / (n + 1) that normalizes the BRDF is in the absorption
+            Self::Phong {normal, ..} => self.pdf(incident, direction) * normal.dot(direction).max(0.0),
         }
     }
 }
 
+/// The distribution D(h), the masking G1(v), the masking-shadowing G2(v, l) and the cosine of the view direction,
+/// or None if either direction is below the surface
+fn ggx_terms(normal: &Rvec3, alpha: Real, incident: &Rvec3, direction: &Rvec3) -> Option<(Real, Real, Real, Real)> {
+    let cos_view = -normal.dot(incident);
+    let cos_light = normal.dot(direction);
+    if cos_view <= 0.0 || cos_light <= 0.0 {
+        return None
+    }
+    let half = (direction - incident).normalize();
+    let cos_half = normal.dot(&half);
+    let alpha2 = alpha * alpha;
+    let d = alpha2 / (PI * (cos_half * cos_half * (alpha2 - 1.0) + 1.0).powi(2));
+    let lambda = |cos: Real| 0.5 * ((1.0 + alpha2 * (1.0 - cos * cos) / (cos * cos)).sqrt() - 1.0);
+    let g1 = 1.0 / (1.0 + lambda(cos_view));
+    let g2 = 1.0 / (1.0 + lambda(cos_view) + lambda(cos_light));
+    Some((d, g1, g2, cos_view))
+}
+
 // ------------------------------------------- Emission -------------------------------------------
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Emit {
     None,
     DebugNormals,
+    DebugUVs,
     Color(Color),
-    SkyGradient,
-    SkySphere(TextureId),
+    /// Blends the horizon color into the top color as the rays go up, and into the bottom color as they go down
+    SkyGradient {top: Color, horizon: Color, bottom: Color},
+    /// An environment texture, turned by yaw around the up axis after being tilted by pitch around the X axis
+    SkySphere {texture: TextureId, yaw: Real, pitch: Real},
 }
 
 impl Emit {
+    /// A sky that goes linearly from the bottom color straight down to the top color straight up
+    pub fn sky_gradient(bottom: Color, top: Color) -> Emit {
+        Emit::SkyGradient {top, horizon: 0.5 * (bottom + top), bottom}
+    }
+
     pub fn evaluate(&self, incident: &Ray, hit: &Hit, scene_data: &SceneData, rng: &mut Randomizer) -> Color {
         match self {
             Self::None => rgb(0.0, 0.0, 0.0),
             Self::Color(color) => *color,
             Self::DebugNormals => hit.normal,
-            Self::SkyGradient => {
-                let t = 0.5 * (incident.direction.y / incident.direction.magnitude() + 1.0);
-                (1.0 - t) * rgb(1.0, 1.0, 1.0) + t * rgb(0.5, 0.7, 1.0)
+            Self::DebugUVs => rgb(hit.uv.x, hit.uv.y, 0.0),
+            Self::SkyGradient {top, horizon, bottom} => {
+                let y = incident.direction.y / incident.direction.magnitude();
+                let (t, end) = if y >= 0.0 { (y, top) } else { (-y, bottom) };
+                (1.0 - t) * horizon + t * end
+            }
+            Self::SkySphere {texture, yaw, pitch} => {
+                // Look up the texture in the frame of the sky
+                let direction = sky_rotation(*yaw, *pitch).transpose() * incident.direction;
+                let hit = Hit::at_infinity(&direction);
+                scene_data.texture(*texture).sample(incident, &hit, scene_data, rng)
             }
-            Self::SkySphere(tid) => scene_data.texture_table[tid.to_index()].sample(incident, hit, scene_data, rng),
         }
     }
 }
 
+/// Rotation from the frame of a sky sphere to the world frame.
+/// Anything that maps between world directions and sky texels must go through it.
+pub fn sky_rotation(yaw: Real, pitch: Real) -> Rmat3 {
+    let (sin_yaw, cos_yaw) = yaw.sin_cos();
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+    let yaw_rotation = matrix![
+        cos_yaw, 0.0, sin_yaw;
+        0.0, 1.0, 0.0;
+        -sin_yaw, 0.0, cos_yaw
+    ];
+    let pitch_rotation = matrix![
+        1.0, 0.0, 0.0;
+        0.0, cos_pitch, -sin_pitch;
+        0.0, sin_pitch, cos_pitch
+    ];
+    yaw_rotation * pitch_rotation
+}
+
 // ------------------------------------------- Absorption -------------------------------------------
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Absorb {
     BlackBody,
     WhiteBody,
     Albedo(Color),
+    /// The procedural textures follow the texture coordinates of the surface only with TextureSpace::Uv
     AlbedoMap(TextureId),
+    /// The color painted on the vertices of the mesh, interpolated across the triangles
+    VertexColor,
 }
 
 impl Absorb {
@@ -76,42 +262,214 @@ impl Absorb {
             Self::BlackBody => rgb(0.0, 0.0, 0.0),
             Self::WhiteBody => rgb(1.0, 1.0, 1.0),
             Self::Albedo(color) => *color,
-            Self::AlbedoMap(tid) => scene_data.texture_table[tid.to_index()].sample(incident, hit, scene_data, rng),
+            Self::AlbedoMap(tid) => scene_data.texture(*tid).sample(incident, hit, scene_data, rng),
+            Self::VertexColor => hit.color,
         }
     }
 }
 
 // ------------------------------------------- Material -------------------------------------------
 
+/// A smooth transparent layer on top of a material, like a varnish
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Clearcoat {
+    pub refraction_index: Real,
+    /// Same as the roughness of Scatter::Ggx
+    pub roughness: Real,
+}
+
+/// A height map that perturbs the normal of a material
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bump {
+    /// The height is the average of the channels
+    pub texture: TextureId,
+    pub strength: Real,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Material {
     scatter: Scatter,
     absorb: Absorb,
     emit: Emit,
+    clearcoat: Option<Clearcoat>,
+    bump: Option<Bump>,
+    /// How many more bounces the paths may take after this material, within RenderParams::max_bounce
+    max_bounce: Option<usize>,
 }
 
 pub struct MaterialOutput {
     pub scatter: Option<Ray>,
     pub absorb: Color,
     pub emit: Color,
+    /// The scattering function that produced the scatter ray, when it can be evaluated in other directions
+    pub lobe: Option<Lobe>,
 }
 
 impl Material {
     pub fn new(scatter: Scatter, absorb: Absorb, emit: Emit) -> Material {
-        Material {scatter, emit, absorb}
+        Material {scatter, emit, absorb, clearcoat: None, bump: None, max_bounce: None}
+    }
+
+    pub fn with_clearcoat(self, clearcoat: Clearcoat) -> Material {
+        Material {clearcoat: Some(clearcoat), ..self}
     }
 
-    pub fn evaluate(&self, incident: &Ray, hit: &Hit, scene_data: &SceneData, rng: &mut Randomizer) -> MaterialOutput
+    pub fn with_bump(self, bump: Bump) -> Material {
+        Material {bump: Some(bump), ..self}
+    }
+
+    /// Stop the paths at most max_bounce bounces after this material, to spend the time where it shows: a high limit
+    /// for the glass, which needs many bounces to be seen through, a low one for the diffuse surfaces.
+    /// With 0, only the lights are sampled from here.
+    pub fn with_max_bounce(self, max_bounce: usize) -> Material {
+        Material {max_bounce: Some(max_bounce), ..self}
+    }
+
+    /// The number of rays left to a path hitting this material, counting the one that hit it
+    pub fn limit_depth(&self, depth: usize) -> usize {
+        self.max_bounce.map_or(depth, |max_bounce| depth.min(max_bounce + 1))
+    }
+
+    /// The two materials that a Mix picks from
+    pub fn mixed_materials(&self) -> Option<(MaterialId, MaterialId)> {
+        match self.scatter {
+            Scatter::Mix {a, b, ..} => Some((a, b)),
+            _ => None,
+        }
+    }
+
+    /// Whether the material glows at all. A mix glows through the materials it mixes, never by itself, and a black
+    /// emission is no glow.
+    pub fn is_emissive(&self) -> bool {
+        match (&self.scatter, &self.emit) {
+            (Scatter::Mix {..}, _) | (_, Emit::None) => false,
+            (_, Emit::Color(color)) => *color != Color::zeros(),
+            _ => true,
+        }
+    }
+
+    /// Whether the rays, the shadow rays included, go through the surface as if it was not there
+    pub fn is_passthrough(&self) -> bool {
+        matches!(self.scatter, Scatter::Passthrough)
+    }
+
+    /// The emission of a material that glows the same everywhere, the only kind that can be sampled as a light
+    pub fn constant_emission(&self) -> Option<Color> {
+        match (&self.scatter, &self.emit) {
+            (Scatter::Mix {..}, _) => None,
+            (_, Emit::Color(color)) => Some(*color),
+            _ => None,
+        }
+    }
+
+    /// The metals, the GGX and the clearcoat are made at least min_roughness rough, 0 keeps them as they are
+    pub fn evaluate(&self, incident: &Ray, hit: &Hit, scene_data: &SceneData, rng: &mut Randomizer,
+        min_roughness: Real) -> MaterialOutput
     {
-        let scatter = self.scatter.evaluate(incident, hit, scene_data, rng);
-        let absorb = self.absorb.evaluate(incident, hit, scene_data, rng);
+        if let Scatter::Mix {a, b, factor} = &self.scatter {
+            // The absorption and emission must come from the same material as the scattering
+            let picked = scene_data.material(pick_mix(*a, *b, *factor, rng));
+            return picked.evaluate(incident, hit, scene_data, rng, min_roughness)
+        }
+
+        let bumped_hit;
+        let hit = match &self.bump {
+            Some(bump) => {
+                bumped_hit = bump_hit(incident, hit, scene_data, rng, bump);
+                &bumped_hit
+            }
+            None => hit,
+        };
+
+        if let Some(clearcoat) = &self.clearcoat {
+            // The ray either bounces on the coat with the Fresnel reflectance, or goes through to the base material
+            let cos_incident = -hit.normal.dot(&incident.direction);
+            if cos_incident > 0.0 {
+                let reflectance = fresnel_schlick(cos_incident, fresnel_r0(clearcoat.refraction_index));
+                if rng.sample(Bernoulli(reflectance)) {
+                    let roughness = clearcoat.roughness.max(min_roughness);
+                    let scatter = evaluate_ggx(incident, hit, rng, roughness);
+                    let absorb = rgb(1.0, 1.0, 1.0);
+                    let emit = self.emit.evaluate(incident, hit, scene_data, rng);
+                    let lobe = Lobe::ggx(incident, hit, roughness);
+                    return MaterialOutput {scatter, emit, absorb, lobe}
+                }
+            }
+        }
+
+        let rougher;
+        let scatter_function = if min_roughness > 0.0 {
+            rougher = self.scatter.with_min_roughness(min_roughness);
+            &rougher
+        } else {
+            &self.scatter
+        };
+
+        let scatter = scatter_function.evaluate(incident, hit, scene_data, rng);
+        let mut absorb = match scatter_function {
+            Scatter::Passthrough => rgb(1.0, 1.0, 1.0),
+            Scatter::Phong {shininess, specular} => {
+                // The lobe is sampled with the density (n + 1) / 2π cos^n of the normalized BRDF (n + 2) / 2π cos^n
+                let normalization = (shininess + 2.0) / (shininess + 1.0);
+                normalization * specular.component_mul(&self.absorb.evaluate(incident, hit, scene_data, rng))
+            }
+            Scatter::Conductor {eta, k, ..} => {
+                // Taken for the normal of the surface rather than for the microfacet that reflected the ray, so that
+                // it does not depend on the direction and applies to the light samples of the lobe too
+                let reflectance = fresnel_conductor(-hit.normal.dot(&incident.direction), eta, k);
+                reflectance.component_mul(&self.absorb.evaluate(incident, hit, scene_data, rng))
+            }
+            _ => self.absorb.evaluate(incident, hit, scene_data, rng),
+        };
+        if let (None, Some(Ray {wavelength: Some(wavelength), ..})) = (incident.wavelength, &scatter) {
+            // The scattered ray took one of the 3 channels at random, it carries only this one from now on
+            absorb = 3.0 * absorb.component_mul(&wavelength.mask());
+        }
         let emit = self.emit.evaluate(incident, hit, scene_data, rng);
-        MaterialOutput {scatter, emit, absorb}
+        let lobe = scatter_function.lobe(incident, hit);
+        MaterialOutput {scatter, emit, absorb, lobe}
     }
 }
 
+/// Tilt the normal of a hit against the gradient of the height, estimated with finite differences
+fn bump_hit(incident: &Ray, hit: &Hit, scene_data: &SceneData, rng: &mut Randomizer, bump: &Bump) -> Hit {
+    const STEP: Real = 1e-3;
+    let texture = scene_data.texture(bump.texture);
+    let mut height = |position: Rvec3, uv: Rvec2| {
+        let hit = Hit {position, uv, ..hit.clone()};
+        texture.sample(incident, &hit, scene_data, rng).sum() / 3.0
+    };
+
+    // The procedural textures are sampled at offset positions, the image textures at offset texture coordinates.
+    // The tangent frame of the hit tells how U and V are oriented on the surface.
+    let (tangent, bitangent) = (hit.tangent, hit.bitangent());
+    let h = height(hit.position, hit.uv);
+    let dh_dt = (height(hit.position + STEP * tangent, hit.uv + vector![STEP, 0.0]) - h) / STEP;
+    let dh_db = (height(hit.position + STEP * bitangent, hit.uv + vector![0.0, STEP]) - h) / STEP;
+
+    let normal = (hit.normal - bump.strength * (dh_dt * tangent + dh_db * bitangent)).normalize();
+    if normal.dot(&incident.direction).signum() != hit.normal.dot(&incident.direction).signum() {
+        // The perturbed normal must not turn the surface around, or it would appear black
+        return hit.clone()
+    }
+    let tangent = tangent_along(&normal, &hit.tangent);
+    Hit {normal, tangent, ..hit.clone()}
+}
+
 // ------------------------------------------- Scattering implementations -------------------------------------------
 
+/// Pick a with probability factor, otherwise b. No random number is drawn when factor is 0 or 1.
+fn pick_mix(a: MaterialId, b: MaterialId, factor: Real, rng: &mut Randomizer) -> MaterialId {
+    if factor >= 1.0 || (factor > 0.0 && rng.sample(Bernoulli(factor))) {
+        a
+    } else {
+        b
+    }
+}
+
 fn evaluate_lambert(incident: &Ray, hit: &Hit, rng: &mut Randomizer) -> Option<Ray> {
     if hit.normal.dot(&incident.direction) > 0.0 {
         return None
@@ -120,13 +478,7 @@ fn evaluate_lambert(incident: &Ray, hit: &Hit, rng: &mut Randomizer) -> Option<R
     // Compute the scatter direction with lambertian distribution
     let scatter_dir = (hit.normal + rng.sample(UnitSphere)).normalize();
     
-    let scattered = Ray {
-        direction: scatter_dir,
-        origin: hit.position,
-        t_min: RAY_EPSILON,
-        t_max: INFINITY,
-    };
-    Some(scattered)
+    Some(incident.scatter(hit, scatter_dir))
 }
 
 fn evaluate_metal(incident: &Ray, hit: &Hit, rng: &mut Randomizer, fuzziness: Real) -> Option<Ray> {
@@ -142,16 +494,146 @@ fn evaluate_metal(incident: &Ray, hit: &Hit, rng: &mut Randomizer, fuzziness: Re
         return None
     }
 
-    let reflected = Ray {
-        direction: reflect_dir,
-        origin: hit.position,
-        t_min: RAY_EPSILON,
-        t_max: INFINITY,
+    Some(incident.scatter(hit, reflect_dir))
+}
+
+fn evaluate_ggx(incident: &Ray, hit: &Hit, rng: &mut Randomizer, roughness: Real) -> Option<Ray> {
+    // Work in the local frame of the surface, where the normal is Z
+    let (tangent, bitangent) = orthonormal_basis(&hit.normal);
+    let to_local = |v: &Rvec3| vector![v.dot(&tangent), v.dot(&bitangent), v.dot(&hit.normal)];
+    let view = -to_local(&incident.direction);
+    if view.z <= 0.0 {
+        return None
+    }
+
+    // Reflect on a microfacet that is visible from the view direction
+    let alpha = roughness * roughness;
+    let microfacet = sample_ggx_vndf(&view, alpha, rng);
+    let light = reflect(&-view, &microfacet);
+    if light.z <= 0.0 {
+        return None
+    }
+
+    // With the visible normals, the weight of the sample is G2 / G1, which is at most 1,
+    // so it is applied by terminating the path with probability 1 - G2 / G1
+    let lambda = |w: &Rvec3| {
+        let tan2 = (w.x * w.x + w.y * w.y) / (w.z * w.z);
+        0.5 * ((1.0 + alpha * alpha * tan2).sqrt() - 1.0)
     };
-    Some(reflected)
+    let weight = (1.0 + lambda(&view)) / (1.0 + lambda(&view) + lambda(&light));
+    if !rng.sample(Bernoulli(weight)) {
+        return None
+    }
+
+    let direction = light.x * tangent + light.y * bitangent + light.z * hit.normal;
+    Some(incident.scatter(hit, direction))
 }
 
-fn evaluate_dielectric(incident: &Ray, hit: &Hit, rng: &mut Randomizer, refraction_index: Real) -> Option<Ray> {
+fn evaluate_phong(incident: &Ray, hit: &Hit, rng: &mut Randomizer, shininess: Real) -> Option<Ray> {
+    if hit.normal.dot(&incident.direction) > 0.0 {
+        return None
+    }
+
+    // Pick the angle to the mirror direction with the density of cos^n
+    let reflected = reflect(&incident.direction, &hit.normal).normalize();
+    let (tangent, bitangent) = orthonormal_basis(&reflected);
+    let cos_theta = rng.gen::<Real>().powf(1.0 / (shininess + 1.0));
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = TAU * rng.gen::<Real>();
+    let direction = sin_theta * (phi.cos() * tangent + phi.sin() * bitangent) + cos_theta * reflected;
+
+    // The weight of the sample is the cosine with the normal, applied by terminating the path otherwise.
+    // The directions below the surface are lost.
+    let cos_light = hit.normal.dot(&direction);
+    if cos_light <= 0.0 || !rng.sample(Bernoulli(cos_light.min(1.0))) {
+        return None
+    }
+
+    Some(incident.scatter(hit, direction))
+}
+
+/// Sample a microfacet normal of the GGX distribution among those that are visible from the view direction.
+/// Both vectors are in the local frame of the surface, where the normal is Z.
+/// https://jcgt.org/published/0007/04/01/ (Heitz, Sampling the GGX Distribution of Visible Normals)
+pub fn sample_ggx_vndf(view: &Rvec3, alpha: Real, rng: &mut Randomizer) -> Rvec3 {
+    // Stretch the view direction so that the distribution becomes the hemisphere configuration
+    let view = vector![alpha * view.x, alpha * view.y, view.z].normalize();
+
+    // Orthonormal basis around the view direction
+    let len2 = view.x * view.x + view.y * view.y;
+    let t1 = if len2 > 0.0 { vector![-view.y, view.x, 0.0] / len2.sqrt() } else { vector![1.0, 0.0, 0.0] };
+    let t2 = view.cross(&t1);
+
+    // Sample the projected area of the hemisphere
+    let r = rng.gen::<Real>().sqrt();
+    let phi = TAU * rng.gen::<Real>();
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + view.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+    let normal = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * view;
+
+    // Unstretch back to the ellipsoid configuration
+    vector![alpha * normal.x, alpha * normal.y, normal.z.max(0.0)].normalize()
+}
+
+/// Schlick's approximation of the Fresnel reflectance, for the cosine of the angle between the ray and the normal,
+/// from r0 at normal incidence up to 1 at grazing angles
+pub fn fresnel_schlick(cos_theta: Real, r0: Real) -> Real {
+    r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
+}
+
+/// Same as fresnel_schlick for each channel, for the colored reflectance of the metals
+pub fn fresnel_schlick_rgb(cos_theta: Real, f0: Color) -> Color {
+    f0.map(|r0| fresnel_schlick(cos_theta, r0))
+}
+
+/// Complex refraction indices (eta, k) of a few metals for Scatter::Conductor, one per color channel, which give the
+/// reflectance of each channel at normal incidence
+pub const GOLD: (Color, Color) = (Color::new(0.143119, 0.374957, 1.44248), Color::new(3.98316, 2.38572, 1.60322));
+pub const COPPER: (Color, Color) = (Color::new(0.200438, 0.924033, 1.10221), Color::new(3.91295, 2.45285, 2.14219));
+pub const SILVER: (Color, Color) = (Color::new(0.155265, 0.116723, 0.138342), Color::new(4.82835, 3.12225, 2.14696));
+pub const ALUMINIUM: (Color, Color) = (Color::new(1.65746, 0.880369, 0.521229), Color::new(9.22387, 8.04695, 6.69533));
+
+/// Fresnel reflectance of a metal in the air for each channel, for the cosine of the angle between the ray and the
+/// normal, with the exact equations for the complex refraction index eta + i k, averaged over both polarizations
+pub fn fresnel_conductor(cos_theta: Real, eta: &Color, k: &Color) -> Color {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    Color::from_fn(|c, _| {
+        let (eta2, k2) = (eta[c] * eta[c], k[c] * k[c]);
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        // Perpendicular polarization
+        let t1 = a2_plus_b2 + cos2;
+        let t2 = 2.0 * cos2.sqrt() * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        // Parallel polarization
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rs + rp)
+    })
+}
+
+/// Reflectance at normal incidence of the interface between two media, given the ratio of their refraction indices,
+/// either way round
+pub fn fresnel_r0(refraction_index: Real) -> Real {
+    ((1.0 - refraction_index) / (1.0 + refraction_index)).powi(2)
+}
+
+fn evaluate_dielectric(incident: &Ray, hit: &Hit, rng: &mut Randomizer, refraction_index: Real,
+    abbe_number: Option<Real>) -> Option<Ray>
+{
+    // With dispersion, the refraction depends on the wavelength, so the ray must pick one if it has none yet
+    let (refraction_index, wavelength) = match abbe_number {
+        Some(abbe_number) => {
+            let wavelength = incident.wavelength.unwrap_or_else(|| rng.sample(UniformWavelength));
+            (cauchy_refraction_index(refraction_index, abbe_number, wavelength), Some(wavelength))
+        }
+        None => (refraction_index, incident.wavelength),
+    };
+
     let (eta, normal) = if hit.normal.dot(&incident.direction) > 0.0 {
         // Interior
         (refraction_index, -hit.normal)
@@ -160,21 +642,39 @@ fn evaluate_dielectric(incident: &Ray, hit: &Hit, rng: &mut Randomizer, refracti
         (1.0 / refraction_index, hit.normal)
     };
 
-    let reflectance = {
-        let r0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
-        r0 + (1.0 - r0) * (1.0 + normal.dot(&incident.direction)).powi(5)
-    };
+    let reflectance = fresnel_schlick(-normal.dot(&incident.direction), fresnel_r0(eta));
 
     let bounce_direction = if rng.sample(Bernoulli(reflectance)) {
         reflect(&incident.direction, &normal)
     } else {
         refract(&incident.direction, &normal, eta).unwrap_or(reflect(&incident.direction, &normal))
     };
-    let bounce = Ray {
-        direction: bounce_direction,
-        origin: hit.position,
-        t_min: RAY_EPSILON,
-        t_max: INFINITY,
-    };
+    let mut bounce = incident.scatter(hit, bounce_direction);
+    bounce.wavelength = wavelength;
     Some(bounce)
+}
+
+/// Cauchy's equation n = A + B / wavelength^2, fitted on the refraction index at the Fraunhofer d line
+/// and on the Abbe number, which is (n_d - 1) / (n_F - n_C)
+fn cauchy_refraction_index(refraction_index: Real, abbe_number: Real, wavelength: Wavelength) -> Real {
+    const D_LINE: Real = 0.5876;
+    const F_LINE: Real = 0.4861;
+    const C_LINE: Real = 0.6563;
+    let b = (refraction_index - 1.0) / (abbe_number * (F_LINE.powi(-2) - C_LINE.powi(-2)));
+    let a = refraction_index - b * D_LINE.powi(-2);
+    a + b * wavelength.micrometers().powi(-2)
+}
+
+fn evaluate_thin_dielectric(incident: &Ray, hit: &Hit, rng: &mut Randomizer, refraction_index: Real) -> Option<Ray> {
+    // Both sides behave the same
+    let normal = if hit.normal.dot(&incident.direction) > 0.0 { -hit.normal } else { hit.normal };
+
+    let reflectance = fresnel_schlick(-normal.dot(&incident.direction), fresnel_r0(refraction_index));
+
+    let bounce_direction = if rng.sample(Bernoulli(reflectance)) {
+        reflect(&incident.direction, &normal)
+    } else {
+        incident.direction
+    };
+    Some(incident.scatter(hit, bounce_direction))
 }
\ No newline at end of file
@@ -8,6 +8,8 @@ pub struct Vertex {
     pub position: Rvec3,
     pub normal: Rvec3,
     pub uv: Rvec2,
+    /// Painted on the vertices by some exporters, white otherwise
+    pub color: Color,
 }
 
 declare_index_wrapper!(MeshId, u32);
@@ -15,13 +17,48 @@ declare_index_wrapper!(TriangleId, u32);
 
 // ------------------------------------------- Mesh storage -------------------------------------------
 
+/// Per-triangle data precomputed for the intersection test
+#[derive(Debug, Clone)]
+pub struct TriangleEdges {
+    pub origin: Rvec3,
+    pub edge1: Rvec3,
+    pub edge2: Rvec3,
+}
+
+/// The vertices and indices are private so that the edges cannot go out of date
 pub struct Mesh {
-    pub vertices: Vec<Vertex>,
-    pub indices: Vec<u32>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
     pub material: MaterialId,
+    /// One entry per triangle, to be indexed by TriangleId / 3
+    edges: Vec<TriangleEdges>,
 }
 
 impl Mesh {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, material: MaterialId) -> Self {
+        let edges = compute_edges(&vertices, &indices);
+        Mesh {vertices, indices, material, edges}
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Move the vertices in place, the edges are computed again afterwards.
+    /// The Bvhs over the mesh must be refit before the next ray.
+    pub fn update_vertices(&mut self, update: impl FnOnce(&mut [Vertex])) {
+        update(&mut self.vertices);
+        self.edges = compute_edges(&self.vertices, &self.indices);
+    }
+
+    pub fn get_edges(&self, triangle: TriangleId) -> &TriangleEdges {
+        &self.edges[triangle.to_index() / 3]
+    }
+
     pub fn get_triangle(&self, triangle: TriangleId) -> (Vertex, Vertex, Vertex) {
         let a = self.vertices[self.indices[triangle.to_index() + 0] as usize].clone();
         let b = self.vertices[self.indices[triangle.to_index() + 1] as usize].clone();
@@ -34,21 +71,144 @@ impl Mesh {
     }
 }
 
+fn compute_edges(vertices: &[Vertex], indices: &[u32]) -> Vec<TriangleEdges> {
+    indices.chunks_exact(3).map(|t| {
+        let a = vertices[t[0] as usize].position;
+        let b = vertices[t[1] as usize].position;
+        let c = vertices[t[2] as usize].position;
+        TriangleEdges {origin: a, edge1: b - a, edge2: c - a}
+    }).collect()
+}
+
+/// Triangles with a smaller area cannot be hit reliably and are dropped when loading a mesh
+pub const MIN_TRIANGLE_AREA: Real = 1e-12;
+
+/// True if the triangle has (almost) no area, e.g. because two of its vertices coincide or all are collinear
+pub fn is_degenerate(a: &Rvec3, b: &Rvec3, c: &Rvec3) -> bool {
+    0.5 * (b - a).cross(&(c - a)).norm() < MIN_TRIANGLE_AREA
+}
+
+// ------------------------------------------- Mesh cleanup -------------------------------------------
+
+/// Options applied to a mesh after it is read from a file
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Merge the vertices that are closer than this distance (and have the same texture coordinates and colors),
+    /// averaging their normals
+    pub weld_tolerance: Option<Real>,
+}
+
+impl LoadOptions {
+    /// A weld tolerance must be a positive distance, it sets the size of the cells of the search grid
+    fn check(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.weld_tolerance {
+            Some(tolerance) if !(tolerance.is_finite() && tolerance > 0.0) =>
+                Err(format!("The weld tolerance must be finite and positive, not {}", tolerance).into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Merge the vertices closer than the tolerance, using a grid of cells of the size of the tolerance.
+/// The first vertex of each group is kept, with the average normal of the group.
+fn weld_vertices(vertices: Vec<Vertex>, indices: &mut [u32], tolerance: Real) -> Vec<Vertex> {
+    use std::collections::HashMap;
+    let cell_of = |p: &Rvec3| p.map(|x| (x / tolerance).floor() as i64);
+
+    let mut grid = HashMap::<Ivec3, Vec<u32>>::new();
+    let mut welded = Vec::<Vertex>::new();
+    let mut normal_sums = Vec::<Rvec3>::new();
+    let mut remap = Vec::with_capacity(vertices.len());
+
+    for v in vertices {
+        let cell = cell_of(&v.position);
+
+        // Search the neighboring cells for a vertex to merge with
+        let mut found = None;
+        'search: for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let neighbor = cell + vector![dx, dy, dz];
+                    for &candidate in grid.get(&neighbor).into_iter().flatten() {
+                        let w = &welded[candidate as usize];
+                        if (w.position - v.position).norm() <= tolerance
+                            && (w.uv - v.uv).norm() <= tolerance
+                            && (w.color - v.color).norm() <= tolerance
+                        {
+                            found = Some(candidate);
+                            break 'search
+                        }
+                    }
+                }
+            }
+        }
+
+        let index = found.unwrap_or_else(|| {
+            let index = welded.len() as u32;
+            grid.entry(cell).or_default().push(index);
+            welded.push(v.clone());
+            normal_sums.push(Rvec3::zeros());
+            index
+        });
+        normal_sums[index as usize] += v.normal;
+        remap.push(index);
+    }
+
+    for (v, normal_sum) in welded.iter_mut().zip(normal_sums) {
+        if normal_sum.norm_squared() > 0.0 {
+            v.normal = normal_sum.normalize();
+        }
+    }
+    for i in indices.iter_mut() {
+        *i = remap[*i as usize];
+    }
+    welded
+}
+
+/// Apply the load options, drop the degenerate triangles and build the mesh
+fn finish_mesh(path: &str, mut vertices: Vec<Vertex>, mut indices: Vec<u32>, options: &LoadOptions) -> Mesh {
+    if let Some(tolerance) = options.weld_tolerance {
+        let num_vertices = vertices.len();
+        vertices = weld_vertices(vertices, &mut indices, tolerance);
+        if vertices.len() < num_vertices {
+            eprintln!("{}: welded {} vertices", path, num_vertices - vertices.len());
+        }
+    }
+
+    let num_triangles = indices.len() / 3;
+    let indices = indices.chunks_exact(3)
+        .filter(|t| {
+            let position = |i: u32| &vertices[i as usize].position;
+            !is_degenerate(position(t[0]), position(t[1]), position(t[2]))
+        })
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+    if indices.len() / 3 < num_triangles {
+        eprintln!("{}: removed {} degenerate triangles", path, num_triangles - indices.len() / 3);
+    }
+
+    let material = MaterialId(0);
+    Mesh::new(vertices, indices, material)
+}
+
 // ------------------------------------------- Mesh loading -------------------------------------------
 
 mod obj_parser {
-    use std::{io::BufRead, error::Error};
+    use std::{io::BufRead, error::Error, collections::BTreeSet};
+    use rayon::prelude::*;
     use nom::{
         IResult,
-        bytes::complete::{tag, take_while},
-        sequence::tuple,
-        combinator::{map_res, map, opt},
-        character::complete::space1,
+        bytes::complete::tag,
+        sequence::{tuple, preceded},
+        combinator::{map_res, map, opt, all_consuming},
+        character::complete::{space1, i64 as integer},
         number::complete::double,
         multi::separated_list1,
         branch::alt,
     };
 
+    /// Zero-based indices into the lists of positions, normals and texture coordinates
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct Index {
         pub position: u32,
@@ -56,25 +216,51 @@ mod obj_parser {
         pub texcoord: Option<u32>,
     }
 
-    fn parse_index(input: &str) -> IResult<&str, Index> {
-        let integer = map_res(take_while(|c: char| c.is_ascii_digit()), |x| u32::from_str_radix(x, 10));
+    /// Indices as written in the file: one-based, or negative to count backward from the end of the lists
+    #[derive(Debug, Clone, Copy)]
+    struct RawIndex {
+        position: i64,
+        normal: Option<i64>,
+        texcoord: Option<i64>,
+    }
+
+    impl RawIndex {
+        fn resolve(&self, parsed_obj: &ParsedObj) -> Result<Index, String> {
+            Ok(Index {
+                position: resolve_index(self.position, parsed_obj.positions.len())?,
+                normal: self.normal.map(|x| resolve_index(x, parsed_obj.normals.len())).transpose()?,
+                texcoord: self.texcoord.map(|x| resolve_index(x, parsed_obj.texcoords.len())).transpose()?,
+            })
+        }
+    }
+
+    /// Convert an index to a zero-based index in a list that currently has `count` elements
+    fn resolve_index(index: i64, count: usize) -> Result<u32, String> {
+        let resolved = if index > 0 { index - 1 } else { count as i64 + index };
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(format!("Index {} is out of range, only {} elements are defined", index, count))
+        }
+        Ok(resolved as u32)
+    }
 
+    fn parse_index(input: &str) -> IResult<&str, RawIndex> {
         map_res(
             separated_list1(tag("/"), opt(integer)),
-            |indices: Vec<Option<u32>>| -> Result<_, &str> {
-                let position = indices.get(0).cloned().flatten().ok_or("Position index not provided").map(|x| x - 1)?;
-                let normal = indices.get(2).cloned().flatten().map(|x| x - 1);
-                let texcoord = indices.get(1).cloned().flatten().map(|x| x - 1);
-                Ok(Index {position, normal, texcoord})
+            |indices: Vec<Option<i64>>| -> Result<_, &str> {
+                let position = indices.first().cloned().flatten().ok_or("Position index not provided")?;
+                let normal = indices.get(2).cloned().flatten();
+                let texcoord = indices.get(1).cloned().flatten();
+                Ok(RawIndex {position, normal, texcoord})
             }
         )(input)
     }
 
     enum Line {
-        V([f64; 3]),
+        /// A position, with the color that some exporters write after it
+        V([f64; 3], Option<[f64; 3]>),
         Vn([f64; 3]),
         Vt([f64; 2]),
-        F(Vec<Index>),
+        F(Vec<RawIndex>),
     }
     
     fn parse_vec3(input: &str) -> IResult<&str, [f64; 3]> {
@@ -86,12 +272,18 @@ mod obj_parser {
     }
 
     fn parse_line(input: &str) -> IResult<&str, Line> {
-        let v = map(tuple((tag("v"), space1, parse_vec3)), |(_, _, v)| Line::V(v));
+        // After a position, either a color or the optional w, which is not used
+        let color = map(preceded(space1, parse_vec3), Some);
+        let w = map(opt(preceded(space1, double)), |_| None);
+        let v = map(tuple((tag("v"), space1, parse_vec3, alt((color, w)))), |(_, _, v, color)| Line::V(v, color));
         let vn = map(tuple((tag("vn"), space1, parse_vec3)), |(_, _, vn)| Line::Vn(vn));
-        let vt = map(tuple((tag("vt"), space1, parse_vec2)), |(_, _, vt)| Line::Vt(vt));
+        // The optional w of a texture coordinate is not used either
+        let vt_w = opt(preceded(space1, double));
+        let vt = map(tuple((tag("vt"), space1, parse_vec2, vt_w)), |(_, _, vt, _)| Line::Vt(vt));
         let f = map(tuple((tag("f"), space1, separated_list1(space1, parse_index))), |(_, _, f)| Line::F(f));
 
-        alt((v, vn, vt, f))(input)
+        // Anything left after the values means the line is corrupt rather than longer
+        all_consuming(alt((v, vn, vt, f)))(input)
     }
 
     #[derive(Debug, Clone, Copy)]
@@ -103,82 +295,385 @@ mod obj_parser {
     #[derive(Default, Clone)]
     pub struct ParsedObj {
         pub positions: Vec<[f64; 3]>,
+        /// One per position, white when the file gives none
+        pub colors: Vec<[f64; 3]>,
         pub normals: Vec<[f64; 3]>,
         pub texcoords: Vec<[f64; 2]>,
         pub vertices: Vec<Index>,
         pub faces: Vec<Face>,
     }
 
-    pub fn parse_obj<B: BufRead>(obj: B) -> Result<ParsedObj, Box<dyn Error>> {
+    /// What a line of the file turned out to be
+    enum ParsedLine<'a> {
+        Geometry(Line),
+        Ignored,
+        Unknown(&'a str),
+        Malformed(&'a str),
+    }
+
+    fn classify_line(line: &str) -> ParsedLine<'_> {
+        // A comment can also end a line
+        let line = line.split('#').next().unwrap_or("").trim();
+        let keyword = line.split_whitespace().next().unwrap_or("");
+        match keyword {
+            // Lines carrying geometry must parse correctly
+            "v" | "vn" | "vt" | "f" => match parse_line(line) {
+                Ok((_, parsed_line)) => ParsedLine::Geometry(parsed_line),
+                Err(_) => ParsedLine::Malformed(keyword),
+            },
+            // Lines that can safely be ignored
+            "" | "o" | "g" | "s" | "mtllib" | "usemtl" => ParsedLine::Ignored,
+            _ => ParsedLine::Unknown(keyword),
+        }
+    }
+
+    pub fn parse_obj<B: BufRead>(mut obj: B) -> Result<ParsedObj, Box<dyn Error>> {
+        let mut text = String::new();
+        obj.read_to_string(&mut text)?;
+
+        // The lines are independent, so they are parsed in parallel. Putting them together must stay in order,
+        // because the negative indices refer to the elements defined before.
+        let lines: Vec<&str> = text.lines().collect();
+        let parsed_lines: Vec<ParsedLine> = lines.par_iter().map(|line| classify_line(line)).collect();
+
         let mut parsed_obj = ParsedObj::default();
-        
-        for line in obj.lines() {
-            let line = line?;
-            let parsed_line = match parse_line(&line) {
-                Ok((_, parsed_line)) => parsed_line,
-                Err(_) => continue
-            };
+        let mut unknown_keywords = BTreeSet::new();
+        for (line_number, parsed_line) in parsed_lines.into_iter().enumerate() {
             match parsed_line {
-                Line::V(v) => parsed_obj.positions.push(v),
-                Line::Vn(vn) => parsed_obj.normals.push(vn),
-                Line::Vt(vt) => parsed_obj.texcoords.push(vt),
-                Line::F(f) => {
+                ParsedLine::Geometry(Line::V(v, color)) => {
+                    parsed_obj.positions.push(v);
+                    parsed_obj.colors.push(color.unwrap_or([1.0, 1.0, 1.0]));
+                }
+                ParsedLine::Geometry(Line::Vn(vn)) => parsed_obj.normals.push(vn),
+                ParsedLine::Geometry(Line::Vt(vt)) => parsed_obj.texcoords.push(vt),
+                ParsedLine::Geometry(Line::F(f)) => {
+                    // Negative indices are relative to the elements defined so far
                     let first_vertex = parsed_obj.vertices.len() as _;
                     let num_vertices = f.len() as _;
+                    for index in f.iter() {
+                        let index = index.resolve(&parsed_obj).map_err(|e| format!("Line {}: {}", line_number + 1, e))?;
+                        parsed_obj.vertices.push(index);
+                    }
                     parsed_obj.faces.push(Face {first_vertex, num_vertices});
-                    parsed_obj.vertices.extend(f.iter());
+                }
+                ParsedLine::Ignored => (),
+                ParsedLine::Unknown(keyword) => {
+                    unknown_keywords.insert(keyword.to_string());
+                }
+                ParsedLine::Malformed(keyword) => {
+                    let line = lines[line_number].trim();
+                    return Err(format!("Line {}: malformed `{}` directive: {}", line_number + 1, keyword, line).into())
                 }
             }
         }
 
+        if !unknown_keywords.is_empty() {
+            eprintln!("Ignored unsupported obj directives: {:?}", unknown_keywords);
+        }
         Ok(parsed_obj)
     }
 }
 
 pub mod obj {
     use super::*;
-    use std::collections::HashMap;
     use std::fs::File;
     use std::io::BufReader;
+    use rayon::prelude::*;
     use std::error::Error;
 
     pub fn load(path: &str) -> Result<Mesh, Box<dyn Error>> {
+        load_with_options(path, &LoadOptions::default())
+    }
+
+    pub fn load_with_options(path: &str, options: &LoadOptions) -> Result<Mesh, Box<dyn Error>> {
         const DEFAULT_NORMAL: Rvec3 = vector![0.0, 0.0, 0.0];
         const DEFAULT_UV: Rvec2 = vector![0.0, 0.0];
 
+        options.check()?;
         let parsed_obj = obj_parser::parse_obj(BufReader::new(File::open(path)?))?;
-
-        let mut unique_vertices = HashMap::<obj_parser::Index, u32>::new();
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
+        let (unique_vertices, remap) = deduplicate(&parsed_obj.vertices);
 
         // Fill in the mesh's vertices
-        for v in parsed_obj.vertices.iter() {
-            if unique_vertices.get(&v).is_none() {
-                // New vertex encountered, add it to the mesh
-                let new_index = vertices.len() as u32;
-                unique_vertices.insert(*v, new_index);
-                let position = parsed_obj.positions[v.position as usize].into();
-                let normal = v.normal.map_or(DEFAULT_NORMAL, |x| parsed_obj.normals[x as usize].into());
-                let uv = v.texcoord.map_or(DEFAULT_UV, |x| parsed_obj.texcoords[x as usize].into());
-                vertices.push(Vertex {position, normal, uv});
-            }
-        }
+        let vertices = unique_vertices.par_iter().map(|v| {
+            let position = parsed_obj.positions[v.position as usize].into();
+            let normal = v.normal.map_or(DEFAULT_NORMAL, |x| parsed_obj.normals[x as usize].into());
+            let uv = v.texcoord.map_or(DEFAULT_UV, |x| parsed_obj.texcoords[x as usize].into());
+            let color = parsed_obj.colors[v.position as usize].into();
+            Vertex {position, normal, uv, color}
+        }).collect();
 
         // Fill in the mesh's indices
+        let mut indices = Vec::with_capacity(3 * parsed_obj.faces.len());
         for f in parsed_obj.faces.iter() {
             if f.num_vertices != 3 {
                 return Err("Non-triangular face are not supported".into())
             }
-            let a = unique_vertices[&parsed_obj.vertices[f.first_vertex as usize + 0]];
-            let b = unique_vertices[&parsed_obj.vertices[f.first_vertex as usize + 1]];
-            let c = unique_vertices[&parsed_obj.vertices[f.first_vertex as usize + 2]];
-            indices.push(a);
-            indices.push(b);
-            indices.push(c);
+            let first = f.first_vertex as usize;
+            indices.extend_from_slice(&remap[first..first + 3]);
         }
         
-        let material = MaterialId(0);
-        Ok(Mesh {vertices, indices, material})
+        Ok(finish_mesh(path, vertices, indices, options))
+    }
+
+    /// The distinct indices in the order of their first appearance, and where each index went in this list.
+    /// Sorting the copies next to each other does the same job as a hash map, but in parallel.
+    fn deduplicate(indices: &[obj_parser::Index]) -> (Vec<obj_parser::Index>, Vec<u32>) {
+        let key = |i: &obj_parser::Index| (i.position, i.normal, i.texcoord);
+        let mut order: Vec<u32> = (0..indices.len() as u32).collect();
+        order.par_sort_unstable_by_key(|&i| (key(&indices[i as usize]), i));
+
+        // For each index, the position of its first copy
+        let mut first_copy = vec![0; indices.len()];
+        for group in order.chunk_by(|&a, &b| key(&indices[a as usize]) == key(&indices[b as usize])) {
+            group.iter().for_each(|&i| first_copy[i as usize] = group[0]);
+        }
+
+        // The first copies are numbered in the order of the file
+        let mut unique = Vec::new();
+        let mut remap = vec![0; indices.len()];
+        for i in 0..indices.len() {
+            let first = first_copy[i] as usize;
+            remap[i] = if first == i {
+                unique.push(indices[i]);
+                unique.len() as u32 - 1
+            } else {
+                remap[first]
+            };
+        }
+        (unique, remap)
+    }
+}
+
+pub mod ply {
+    use super::*;
+    use std::error::Error;
+
+    #[derive(Debug, Clone, Copy)]
+    enum Scalar {
+        Char, UChar, Short, UShort, Int, UInt, Float, Double,
     }
-}
\ No newline at end of file
+
+    impl Scalar {
+        fn from_name(name: &str) -> Result<Scalar, Box<dyn Error>> {
+            match name {
+                "char" | "int8" => Ok(Scalar::Char),
+                "uchar" | "uint8" => Ok(Scalar::UChar),
+                "short" | "int16" => Ok(Scalar::Short),
+                "ushort" | "uint16" => Ok(Scalar::UShort),
+                "int" | "int32" => Ok(Scalar::Int),
+                "uint" | "uint32" => Ok(Scalar::UInt),
+                "float" | "float32" => Ok(Scalar::Float),
+                "double" | "float64" => Ok(Scalar::Double),
+                _ => Err(format!("Unknown ply type: {}", name).into())
+            }
+        }
+
+        /// The value of a full color channel: the largest value of the integers, 1 for the floats
+        fn color_range(&self) -> f64 {
+            match self {
+                Scalar::Char => i8::MAX as f64,
+                Scalar::UChar => u8::MAX as f64,
+                Scalar::Short => i16::MAX as f64,
+                Scalar::UShort => u16::MAX as f64,
+                Scalar::Int => i32::MAX as f64,
+                Scalar::UInt => u32::MAX as f64,
+                Scalar::Float | Scalar::Double => 1.0,
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    enum Property {
+        Scalar {name: String, ty: Scalar},
+        List {name: String, count_ty: Scalar, item_ty: Scalar},
+    }
+
+    #[derive(Debug)]
+    struct Element {
+        name: String,
+        count: usize,
+        properties: Vec<Property>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Format {
+        Ascii,
+        BinaryLittleEndian,
+    }
+
+    /// Reads the values of the body one at a time, whatever their encoding
+    enum BodyReader<'a> {
+        Ascii(std::str::SplitAsciiWhitespace<'a>),
+        BinaryLittleEndian(&'a [u8]),
+    }
+
+    impl<'a> BodyReader<'a> {
+        fn read(&mut self, ty: Scalar) -> Result<f64, Box<dyn Error>> {
+            match self {
+                Self::Ascii(tokens) => {
+                    let token = tokens.next().ok_or("Unexpected end of ply data")?;
+                    Ok(token.parse::<f64>()?)
+                }
+                Self::BinaryLittleEndian(bytes) => {
+                    let size = match ty {
+                        Scalar::Char | Scalar::UChar => 1,
+                        Scalar::Short | Scalar::UShort => 2,
+                        Scalar::Int | Scalar::UInt | Scalar::Float => 4,
+                        Scalar::Double => 8,
+                    };
+                    if bytes.len() < size {
+                        return Err("Unexpected end of ply data".into())
+                    }
+                    let (value, rest) = bytes.split_at(size);
+                    *bytes = rest;
+                    Ok(match ty {
+                        Scalar::Char => value[0] as i8 as f64,
+                        Scalar::UChar => value[0] as f64,
+                        Scalar::Short => i16::from_le_bytes([value[0], value[1]]) as f64,
+                        Scalar::UShort => u16::from_le_bytes([value[0], value[1]]) as f64,
+                        Scalar::Int => i32::from_le_bytes([value[0], value[1], value[2], value[3]]) as f64,
+                        Scalar::UInt => u32::from_le_bytes([value[0], value[1], value[2], value[3]]) as f64,
+                        Scalar::Float => f32::from_le_bytes([value[0], value[1], value[2], value[3]]) as f64,
+                        Scalar::Double => f64::from_le_bytes([
+                            value[0], value[1], value[2], value[3], value[4], value[5], value[6], value[7]
+                        ]),
+                    })
+                }
+            }
+        }
+    }
+
+    fn parse_header(header: &str) -> Result<(Format, Vec<Element>), Box<dyn Error>> {
+        let mut lines = header.lines();
+        if lines.next().map(str::trim) != Some("ply") {
+            return Err("Not a ply file".into())
+        }
+
+        let mut format = None;
+        let mut elements = Vec::<Element>::new();
+        for line in lines {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words.as_slice() {
+                ["format", "ascii", _] => format = Some(Format::Ascii),
+                ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+                ["format", other, _] => return Err(format!("This ply format is not supported: {}", other).into()),
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: count.parse()?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count_ty, item_ty, name] => {
+                    let element = elements.last_mut().ok_or("Property declared before any element")?;
+                    element.properties.push(Property::List {
+                        name: name.to_string(),
+                        count_ty: Scalar::from_name(count_ty)?,
+                        item_ty: Scalar::from_name(item_ty)?,
+                    });
+                }
+                ["property", ty, name] => {
+                    let element = elements.last_mut().ok_or("Property declared before any element")?;
+                    element.properties.push(Property::Scalar {name: name.to_string(), ty: Scalar::from_name(ty)?});
+                }
+                ["comment", ..] | ["obj_info", ..] | [] => (),
+                _ => return Err(format!("Unexpected line in ply header: {}", line).into())
+            }
+        }
+
+        Ok((format.ok_or("Missing format in ply header")?, elements))
+    }
+
+    pub fn load(path: &str) -> Result<Mesh, Box<dyn Error>> {
+        load_with_options(path, &LoadOptions::default())
+    }
+
+    pub fn load_with_options(path: &str, options: &LoadOptions) -> Result<Mesh, Box<dyn Error>> {
+        const DEFAULT_NORMAL: Rvec3 = vector![0.0, 0.0, 0.0];
+        const DEFAULT_UV: Rvec2 = vector![0.0, 0.0];
+
+        options.check()?;
+
+        // Split the header from the body at the end_header line. Going line by line, a comment mentioning
+        // end_header does not end the header.
+        let data = std::fs::read(path)?;
+        let mut header_end = None;
+        let mut line_start = 0;
+        for line in data.split(|&c| c == b'\n') {
+            let line_end = (line_start + line.len() + 1).min(data.len());
+            if std::str::from_utf8(line).map(str::trim) == Ok("end_header") {
+                header_end = Some((line_start, line_end));
+                break
+            }
+            line_start = line_end;
+        }
+        let (header_end, body_start) = header_end.ok_or("Missing end_header in ply file")?;
+        let (format, elements) = parse_header(std::str::from_utf8(&data[..header_end])?)?;
+        let mut body = match format {
+            Format::Ascii => BodyReader::Ascii(std::str::from_utf8(&data[body_start..])?.split_ascii_whitespace()),
+            Format::BinaryLittleEndian => BodyReader::BinaryLittleEndian(&data[body_start..]),
+        };
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        // Read the elements in the order of the header, the unknown ones are read and discarded
+        for element in elements.iter() {
+            for _ in 0..element.count {
+                let mut position = Rvec3::zeros();
+                let mut normal = DEFAULT_NORMAL;
+                let mut uv = DEFAULT_UV;
+                let mut color = WHITE;
+                let mut face = Vec::new();
+
+                for property in element.properties.iter() {
+                    match property {
+                        Property::Scalar {name, ty} => {
+                            let value = body.read(*ty)?;
+                            match name.as_str() {
+                                "x" => position.x = value,
+                                "y" => position.y = value,
+                                "z" => position.z = value,
+                                "nx" => normal.x = value,
+                                "ny" => normal.y = value,
+                                "nz" => normal.z = value,
+                                "u" | "s" | "texture_u" => uv.x = value,
+                                "v" | "t" | "texture_v" => uv.y = value,
+                                "red" => color.x = value / ty.color_range(),
+                                "green" => color.y = value / ty.color_range(),
+                                "blue" => color.z = value / ty.color_range(),
+                                _ => ()
+                            }
+                        }
+                        Property::List {name, count_ty, item_ty} => {
+                            let count = body.read(*count_ty)? as usize;
+                            for _ in 0..count {
+                                let value = body.read(*item_ty)?;
+                                if name == "vertex_indices" || name == "vertex_index" {
+                                    if value < 0.0 {
+                                        return Err(format!("Negative vertex index in ply face: {}", value).into())
+                                    }
+                                    face.push(value as u32);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                match element.name.as_str() {
+                    "vertex" => vertices.push(Vertex {position, normal, uv, color}),
+                    "face" => {
+                        // Triangulate the polygon as a fan around its first vertex
+                        if face.iter().any(|&i| i as usize >= vertices.len()) {
+                            return Err("Face refers to a missing vertex".into())
+                        }
+                        for k in 1..face.len().saturating_sub(1) {
+                            indices.extend_from_slice(&[face[0], face[k], face[k + 1]]);
+                        }
+                    }
+                    _ => ()
+                }
+            }
+        }
+
+        Ok(finish_mesh(path, vertices, indices, options))
+    }
+}
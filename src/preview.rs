@@ -0,0 +1,44 @@
+use raytracing2::image::{Array2d, Tile};
+use raytracing2::render::RenderJobs;
+use raytracing2::utility::*;
+use minifb::{Window, WindowOptions};
+use std::error::Error;
+use std::time::Duration;
+
+/// Show the tiles in a window as the workers complete them, until the window is closed.
+/// If it is closed before the end, the tiles left in the job queue are dropped, so that the render stops with
+/// what is already done.
+pub fn show_while_rendering(width: u32, height: u32, jobs: &RenderJobs) -> Result<(), Box<dyn Error>>
+{
+    let mut window = Window::new("raytracing2 preview", width as usize, height as usize, WindowOptions::default())?;
+    window.limit_update_rate(Some(Duration::from_millis(50)));
+
+    // 0RGB pixels, from the top row to the bottom row
+    let mut buffer = vec![0u32; width as usize * height as usize];
+    let mut num_drawn = 0;
+    while window.is_open() {
+        {
+            // Only the new tiles are drawn
+            let complete_jobs = jobs.complete.lock().unwrap();
+            for (tile, color_buffer, ..) in &complete_jobs[num_drawn..] {
+                draw_tile(&mut buffer, width, height, tile, color_buffer);
+            }
+            num_drawn = complete_jobs.len();
+        }
+        window.update_with_buffer(&buffer, width as usize, height as usize)?;
+    }
+
+    jobs.queue.lock().unwrap().clear();
+    Ok(())
+}
+
+fn draw_tile(buffer: &mut [u32], width: u32, height: u32, tile: &Tile, color_buffer: &Array2d<Color>) {
+    for tj in 0..tile.height {
+        // The images start from the bottom row, the window from the top row
+        let row = (height - 1 - (tj + tile.offset_j)) as usize;
+        for ti in 0..tile.width {
+            let [r, g, b, _] = to_srgb_u8(color_buffer.get(ti, tj));
+            buffer[row * width as usize + (ti + tile.offset_i) as usize] = u32::from_be_bytes([0, r, g, b]);
+        }
+    }
+}
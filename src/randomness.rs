@@ -72,6 +72,19 @@ impl Distribution<Rvec3> for UnitSphere {
     }
 }
 
+/// A uniform distribution of the wavelengths of the 3 color channels
+pub struct UniformWavelength;
+
+impl Distribution<Wavelength> for UniformWavelength {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Wavelength {
+        match rng.gen_range(0..3) {
+            0 => Wavelength::Red,
+            1 => Wavelength::Green,
+            _ => Wavelength::Blue,
+        }
+    }
+}
+
 /// A distribution with a probability p for true and 1-p of false
 pub struct Bernoulli(pub Real);
 
@@ -81,31 +94,155 @@ impl Distribution<bool> for Bernoulli {
     }
 }
 
+// ------------------------------------------- Stratified samples -------------------------------------------
+
+/// Correlated multi-jittered samples of the unit square, from Kensler's "Correlated Multi-Jittered Sampling".
+/// Each of the num_samples columns and rows holds exactly one sample, and so does each cell of a grid of about
+/// sqrt(num_samples) by sqrt(num_samples). The samples of two sets with different patterns are paired at random.
+pub struct CorrelatedMultiJitter {
+    pub num_samples: u32,
+    /// Picks one of the many sets with these properties
+    pub pattern: u32,
+}
+
+impl CorrelatedMultiJitter {
+    /// The sample of the given index, in [0, 1) along each axis. The index must be less than num_samples.
+    pub fn sample(&self, index: u32) -> Rvec2 {
+        let num_samples = self.num_samples;
+        let m = ((num_samples as Real).sqrt() as u32).max(1);
+        let n = num_samples.div_ceil(m);
+        let p = self.pattern;
+        let s = permute(index, num_samples, p.wrapping_mul(0x51633e2d));
+        let sx = permute(s % m, m, p.wrapping_mul(0x68bc21eb));
+        let sy = permute(s / m, n, p.wrapping_mul(0x02e5be93));
+        let jx = hash_to_unit(s, p.wrapping_mul(0x967a889b));
+        let jy = hash_to_unit(s, p.wrapping_mul(0x368cc8b7));
+        vector![
+            (sx as Real + (sy as Real + jx) / n as Real) / m as Real,
+            (s as Real + jy) / num_samples as Real
+        ]
+    }
+}
+
+/// A permutation of [0, len) picked by p, applied to i
+fn permute(mut i: u32, len: u32, p: u32) -> u32 {
+    if len <= 1 {
+        return 0
+    }
+    // Hash inside the smallest power of 2 above len, until the result falls in the range
+    let mask = u32::MAX >> (len - 1).leading_zeros();
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= mask;
+        i ^= i >> 5;
+        if i < len {
+            return i.wrapping_add(p) % len;
+        }
+    }
+}
+
+/// A number in [0, 1) picked by p, for i
+fn hash_to_unit(mut i: u32, p: u32) -> Real {
+    i ^= p;
+    i ^= i >> 17;
+    i ^= i >> 10;
+    i = i.wrapping_mul(0xb36534e5);
+    i ^= i >> 12;
+    i ^= i >> 21;
+    i = i.wrapping_mul(0x93fc4795);
+    i ^= 0xdf6e307f;
+    i ^= i >> 17;
+    i = i.wrapping_mul(1 | p >> 18);
+    i as Real / (u32::MAX as Real + 1.0)
+}
+
+/// Map the unit square onto the unit disk, keeping the cells of a grid compact, so that stratified samples of the
+/// square stay stratified on the disk (Shirley and Chiu's concentric mapping)
+pub fn square_to_disk(uv: Rvec2) -> Rvec2 {
+    let a = 2.0 * uv.x - 1.0;
+    let b = 2.0 * uv.y - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return Rvec2::zeros();
+    }
+    let (radius, angle) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * b / a)
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * a / b)
+    };
+    radius * vector![angle.cos(), angle.sin()]
+}
+
 // ------------------------------------------- Noise -------------------------------------------
 
 pub mod noise {
     use super::*;
 
+    /// Final mix of MurmurHash3, a bijection where every bit of the input flips each bit of the output with a
+    /// probability close to one half
+    fn finalize(mut h: u64) -> u64 {
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xff51afd7ed558ccd);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+        h ^= h >> 33;
+        h
+    }
+
+    /// Mix the coordinates one after the other, so that no two of them can cancel out
+    fn hash(coordinates: &[isize], seed: isize) -> u64 {
+        coordinates.iter().fold(finalize(seed as u64), |h, &c| {
+            finalize(h ^ (c as u64).wrapping_mul(0x9e3779b97f4a7c15))
+        })
+    }
+
+    /// A number in [0, 1) from 32 bits of the hash
+    fn unit(bits: u64) -> Real {
+        (bits & 0xffffffff) as Real / (u32::MAX as Real + 1.0)
+    }
+
     /// Generates an integer in the range [isize::MIN, isize::MAX]
-    // http://libnoise.sourceforge.net/noisegen/index.html#coherentnoise
     pub fn integer(x: isize, y: isize, z: isize, seed: isize) -> isize {
-        use std::num::Wrapping as W;
-        const A: W<isize> = W(0x369E6D3B899E43CF);
-        const B: W<isize> = W(0x53F89E7FFDA3B07D);
-        const C: W<isize> = W(0x3B13C1CA4937E629);
-        const D: W<isize> = W(0x577C2C6E4019D645);
-        const E: W<isize> = W(60493);
-        const F: W<isize> = W(19990303);
-        const G: W<isize> = W(1376312589);
-
-        let mut h = A * W(x) + B * W(y) + C * W(z) + D * W(seed);
-        h = (h >> 13) ^ h;
-        h = h * (h * h * E + F) + G;
-        h.0
+        hash(&[x, y, z], seed) as isize
     }
 
     /// Generates a real number in the range [-1, 1]
     pub fn real(x: isize, y: isize, z: isize, seed: isize) -> Real {
-        integer(x, y, z, seed) as Real / std::isize::MAX as Real
+        2.0 * unit(hash(&[x, y, z], seed)) - 1.0
+    }
+
+    /// Generates a real number in the range [-1, 1], for a 1D lattice
+    pub fn real1(x: isize, seed: isize) -> Real {
+        2.0 * unit(hash(&[x], seed)) - 1.0
+    }
+
+    /// Generates a real number in the range [-1, 1], for a 2D lattice
+    pub fn real2(x: isize, y: isize, seed: isize) -> Real {
+        2.0 * unit(hash(&[x, y], seed)) - 1.0
+    }
+
+    /// Generates a unit vector, uniformly distributed over the directions, for the gradients of Perlin noise.
+    /// The components come from a single hash, unlike 3 calls to real with different seeds, which may correlate.
+    pub fn vec3(x: isize, y: isize, z: isize, seed: isize) -> Rvec3 {
+        let h = hash(&[x, y, z], seed);
+        // Archimedes: the height of a uniform point of the sphere is uniform
+        let height = 2.0 * unit(h) - 1.0;
+        let angle = TAU * unit(h >> 32);
+        let radius = (1.0 - height * height).sqrt();
+        vector![radius * angle.cos(), radius * angle.sin(), height]
     }
-}
\ No newline at end of file
+}
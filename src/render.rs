@@ -1,26 +1,107 @@
 use crate::utility::*;
 use crate::randomness::*;
 use crate::hittable::Hittable;
-use crate::material::Material;
-use crate::texture::Texture;
+use crate::material::{Material, MaterialId};
+use crate::texture::{Texture, TextureId};
 use crate::mesh::Mesh;
-use crate::material::Emit;
+use crate::material::{Emit, Lobe, MaterialOutput};
+use crate::light::{Light, LightList, LightSample};
+use crate::ambient::Ambient;
+use crate::image::{Array2d, Tile, assemble_tiles};
+use crate::example_scenes::ExampleScene;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use indicatif::{ProgressBar, ProgressStyle};
 
 /// Global data to be shared by the rendering workers.
 pub struct SceneData {
     pub material_table: Vec<Material>,
     pub texture_table: Vec<Texture>,
     pub mesh_table: Vec<Mesh>,
+    /// The point and spot lights, the lights with an area are emissive objects
+    pub light_table: Vec<Light>,
+}
+
+impl SceneData {
+    /// No materials, textures, meshes nor lights, to be filled with the struct update syntax
+    pub fn empty() -> Self {
+        SceneData {
+            material_table: Vec::new(), texture_table: Vec::new(), mesh_table: Vec::new(), light_table: Vec::new()
+        }
+    }
+
+    /// The materials alone, without textures, meshes nor lights
+    pub fn new(material_table: Vec<Material>) -> Self {
+        SceneData {material_table, ..SceneData::empty()}
+    }
+
+    /// Panics with the id and the size of the table if the id is out of range
+    pub fn material(&self, id: MaterialId) -> &Material {
+        self.material_table.get(id.to_index()).unwrap_or_else(|| {
+            panic!("{:?} out of range, the material table has {} entries", id, self.material_table.len())
+        })
+    }
+
+    /// Panics with the id and the size of the table if the id is out of range
+    pub fn texture(&self, id: TextureId) -> &Texture {
+        self.texture_table.get(id.to_index()).unwrap_or_else(|| {
+            panic!("{:?} out of range, the texture table has {} entries", id, self.texture_table.len())
+        })
+    }
+
+    /// Check that the materials of each Scatter::Mix exist, and that no Mix ends up mixing itself, directly or
+    /// through other mixes, which would recurse forever when it is evaluated
+    pub fn check_materials(&self) -> Result<(), Box<dyn Error>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            /// On the path of mixes being followed
+            InProgress,
+            Checked,
+        }
+
+        fn check(scene_data: &SceneData, id: MaterialId, states: &mut [State]) -> Result<(), Box<dyn Error>> {
+            let state = states.get(id.to_index()).copied()
+                .ok_or_else(|| format!("Mix of {:?}, which is out of range", id))?;
+            match state {
+                State::Checked => return Ok(()),
+                State::InProgress => return Err(format!("{:?} is mixed into itself", id).into()),
+                State::Unvisited => (),
+            }
+            states[id.to_index()] = State::InProgress;
+            if let Some((a, b)) = scene_data.material_table[id.to_index()].mixed_materials() {
+                check(scene_data, a, states)?;
+                check(scene_data, b, states)?;
+            }
+            states[id.to_index()] = State::Checked;
+            Ok(())
+        }
+
+        let mut states = vec![State::Unvisited; self.material_table.len()];
+        (0..self.material_table.len()).try_for_each(|i| check(self, MaterialId(i as u32), &mut states))
+    }
 }
 
 // ------------------------------------------- Camera -------------------------------------------
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Camera {
     pub aspect_ratio: Real,
     pub fov: Real,
-    pub focal_dist: Real,
+    /// Distance to the plane in focus, where the rays from all the points of the lens meet. The field of view alone
+    /// sets the framing, so changing it only moves the blur around.
+    #[cfg_attr(feature = "serde", serde(alias = "focal_dist"))]
+    pub focus_distance: Real,
+    /// 0 for a pinhole camera, where everything is sharp whatever the focus distance
     pub lens_radius: Real,
+    /// Difference of magnification between the red and the blue images, relative to the green one, for the color
+    /// fringes of a cheap lens that grow toward the edges of the frame. 0 for a perfect lens.
+    pub chromatic_aberration: Real,
     pub transformation: Transformation,
 }
 
@@ -29,55 +110,210 @@ pub struct Camera {
 // Y axis points up
 // Z axis points behind
 impl Camera {
-    pub fn shoot(&self, image_uv: Rvec2, rng: &mut Randomizer) -> Ray {
+    /// Precompute everything that does not depend on the sample, to be done once before rendering
+    pub fn prepare(&self) -> CameraCache {
+        let tan_fov = (0.5 * self.fov).tan();
+        // The image is laid on the plane in focus, scaled so that it spans the field of view
+        let half_height = tan_fov * self.focus_distance;
+        let half_width = half_height * self.aspect_ratio;
+        // Only the directions of the axes matter, should the transformation have a scale
+        let right = self.transformation.linear().column(0).normalize();
+        let up = self.transformation.linear().column(1).normalize();
+        let behind = self.transformation.linear().column(2).normalize();
+        CameraCache {
+            position: *self.transformation.position(),
+            lens_right: self.lens_radius * right,
+            lens_up: self.lens_radius * up,
+            bottom_left: -half_width * right - half_height * up - self.focus_distance * behind,
+            image_right: 2.0 * half_width * right,
+            image_up: 2.0 * half_height * up,
+            pixel_steps: None,
+            chromatic_aberration: self.chromatic_aberration,
+        }
+    }
+
+    /// Shoot a single ray through a point of the unit disk of the lens. Prefer prepare when shooting many rays.
+    pub fn shoot(&self, image_uv: Rvec2, lens: Rvec2, rng: &mut Randomizer) -> Ray {
+        self.prepare().shoot(image_uv, lens, rng)
+    }
+
+    /// Shoot the ray through the center of the lens, like a pinhole camera, to find exactly what is seen at image_uv
+    pub fn shoot_center(&self, image_uv: Rvec2) -> Ray {
+        self.prepare().shoot_center(image_uv)
+    }
+
+    /// Keep the view direction, but move the camera so that the whole scene fits in the view and is in focus.
+    /// Beware that a huge object like a ground sphere will push the camera very far.
+    pub fn look_at_scene(&mut self, root: &Hittable, scene_data: &SceneData, up: &Rvec3) {
+        // Fit the bounding sphere of the scene's bounding box in the narrowest field of view
+        let aabb = root.bounding_box(scene_data);
+        let target = 0.5 * (aabb.min + aabb.max);
+        let radius = 0.5 * (aabb.max - aabb.min).norm();
+        let tan_fov = (0.5 * self.fov).tan();
+        let half_fov = tan_fov.min(tan_fov * self.aspect_ratio).atan();
+        let distance = radius / half_fov.sin();
+
+        let behind = self.transformation.linear().column(2).normalize();
+        self.transformation = Transformation::lookat(&(target + distance * behind), &target, up);
+        self.focus_distance = distance;
+    }
+
+    /// Get the image coordinates of a point as seen through a pinhole, or None if it is behind the camera
+    pub fn project(&self, point: &Rvec3) -> Option<Rvec2> {
+        let local = self.transformation.inverse().transform_point(point);
+        if local.z < 0.0 {
+            Some(self.project_local(&local))
+        } else {
+            None
+        }
+    }
+
+    /// Same as project, with a point in the local camera frame that must be in front of the camera
+    fn project_local(&self, local: &Rvec3) -> Rvec2 {
         let tan_fov = (0.5 * self.fov).tan();
-        
-        // Ray origin in local frame
-        let origin = self.lens_radius * rng.sample(UnitDisk);
-        let origin = vector![origin.x, origin.y, 0.0];
-
-        // Ray direction in local frame
-        let direction = (vector![
-            (2.0 * image_uv.x - 1.0) * tan_fov * self.focal_dist * self.aspect_ratio,
-            (2.0 * image_uv.y - 1.0) * tan_fov * self.focal_dist,
-            -self.focal_dist
-        ] - origin).normalize();
-        
+        vector![
+            0.5 * (local.x / (-local.z * tan_fov * self.aspect_ratio) + 1.0),
+            0.5 * (local.y / (-local.z * tan_fov) + 1.0)
+        ]
+    }
+}
+
+/// The camera basis in world space, scaled so that shooting a ray is just a few multiply-adds
+#[derive(Debug, Clone)]
+pub struct CameraCache {
+    position: Rvec3,
+    /// Lens displacement for a unit disk sample
+    lens_right: Rvec3,
+    lens_up: Rvec3,
+    /// Point of the focal plane at image_uv = (0, 0), relative to the camera position
+    bottom_left: Rvec3,
+    /// Focal plane displacement for image_uv going from 0 to 1
+    image_right: Rvec3,
+    image_up: Rvec3,
+    /// Focal plane displacement from one pixel to the next, None unless given by with_resolution
+    pixel_steps: Option<(Rvec3, Rvec3)>,
+    chromatic_aberration: Real,
+}
+
+impl CameraCache {
+    /// Let the rays know the size of a pixel, so that they carry differentials for the textures to filter with
+    pub fn with_resolution(self, width: u32, height: u32) -> Self {
+        let pixel_steps = Some((self.image_right / width as Real, self.image_up / height as Real));
+        CameraCache {pixel_steps, ..self}
+    }
+
+    /// With chromatic aberration, the ray carries one of the 3 channels, picked at random, and its image is scaled
+    /// around the center of the frame by the magnification of this channel
+    pub fn shoot(&self, image_uv: Rvec2, lens: Rvec2, rng: &mut Randomizer) -> Ray {
+        if self.chromatic_aberration == 0.0 {
+            return self.shoot_through(image_uv, lens);
+        }
+        let wavelength = rng.sample(UniformWavelength);
+        let red_to_blue = Wavelength::Red.micrometers() - Wavelength::Blue.micrometers();
+        let magnification = self.chromatic_aberration
+            * (wavelength.micrometers() - Wavelength::Green.micrometers()) / red_to_blue;
+        // The point of the scene that this channel shows at image_uv is seen closer to the center by a perfect lens
+        let center = vector![0.5, 0.5];
+        let image_uv = center + (image_uv - center) / (1.0 + magnification);
+        Ray {wavelength: Some(wavelength), ..self.shoot_through(image_uv, lens)}
+    }
+
+    pub fn shoot_center(&self, image_uv: Rvec2) -> Ray {
+        self.shoot_through(image_uv, Rvec2::zeros())
+    }
+
+    /// The ray from a point of the unit disk of the lens to a point of the focal plane
+    fn shoot_through(&self, image_uv: Rvec2, lens: Rvec2) -> Ray {
+        let lens_offset = lens.x * self.lens_right + lens.y * self.lens_up;
+        let target = self.bottom_left + image_uv.x * self.image_right + image_uv.y * self.image_up;
+        let to_target = target - lens_offset;
+        let direction = to_target.normalize();
+
+        // The rays of the neighboring pixels go through the same point of the lens, so only the direction changes:
+        // the derivative of to_target / |to_target| when to_target moves by one pixel step
+        let differentials = self.pixel_steps.map(|(step_x, step_y)| {
+            let derivative = |step: Rvec3| (step - step.dot(&direction) * direction) / to_target.norm();
+            RayDifferentials {
+                dp_dx: Rvec3::zeros(),
+                dp_dy: Rvec3::zeros(),
+                dd_dx: derivative(step_x),
+                dd_dy: derivative(step_y),
+            }
+        });
+
         Ray {
-            direction: self.transformation.transform_vector(&direction),
-            origin: self.transformation.transform_point(&origin),
+            direction,
+            origin: self.position + lens_offset,
             t_min: RAY_EPSILON,
-            t_max: INFINITY,
+            t_max: Real::INFINITY,
+            wavelength: None,
+            differentials,
         }
     }
 }
 
 // ------------------------------------------- Image sampling -------------------------------------------
 
+/// Reconstruction filter, to weight the samples of a pixel by their distance to its center
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// All the samples have the same weight
+    Box,
+    /// The weight decreases linearly, down to 0 at radius pixels from the center
+    Tent {radius: Real},
+    /// The weight follows a bell curve, with a standard deviation in pixels
+    Gaussian {sigma: Real},
+}
+
+impl Filter {
+    /// The offset is relative to the center of the pixel, in pixels
+    pub fn weight(&self, offset: &Rvec2) -> Real {
+        match self {
+            Self::Box => 1.0,
+            Self::Tent {radius} => {
+                let tent = |x: Real| (1.0 - x.abs() / radius).max(0.0);
+                tent(offset.x) * tent(offset.y)
+            }
+            Self::Gaussian {sigma} => (-offset.norm_squared() / (2.0 * sigma * sigma)).exp(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Multisampler {
     pub width: u32,
     pub height: u32,
-    pub num_samples: u32
+    pub num_samples: u32,
+    pub filter: Filter,
 }
 
 impl Multisampler {
-    /// Get the sample coordinates of a pixel, in the range [0, 1]
+    /// Get the coordinates of the center of a pixel, in the range [0, 1].
+    /// The image spans [0, 1] from the edge of its first pixel to the edge of its last one, so the center of pixel
+    /// (i, j) is at ((i + 0.5) / width, (j + 0.5) / height), where the jittered samples are centered too.
     pub fn make_uv(&self, i: u32, j: u32) -> Rvec2 {
         vector![
-            i as Real / self.width as Real,
-            j as Real / self.height as Real
+            (i as Real + 0.5) / self.width as Real,
+            (j as Real + 0.5) / self.height as Real
         ]
     }
 
-    /// Get multiple samples coordinates for a pixel, in the range [0, 1]
-    pub fn make_uv_jitter(&self, i: u32, j: u32, rng: &mut Randomizer) -> impl Iterator<Item=Rvec2> + '_ {
-        let mut rng = rng.clone();
-        (0..self.num_samples).map(move |_| {
-            vector![
-                (i as Real + rng.gen::<Real>()) / self.width as Real,
-                (j as Real + rng.gen::<Real>()) / self.height as Real
-            ]
+    /// Get multiple samples coordinates for a pixel, in the range [0, 1], along with their offset to the center of
+    /// the pixel to be given to the filter, and a point of the unit disk to shoot through the lens.
+    /// Both the points of the pixel and of the lens are correlated multi-jittered, so that they are well spread
+    /// even with few samples, and they are paired at random.
+    pub fn make_uv_jitter(&self, i: u32, j: u32, rng: &mut Randomizer)
+        -> impl Iterator<Item=(Rvec2, Rvec2, Rvec2)> + '_
+    {
+        let pixel = CorrelatedMultiJitter {num_samples: self.num_samples, pattern: rng.gen()};
+        let lens = CorrelatedMultiJitter {num_samples: self.num_samples, pattern: rng.gen()};
+        (0..self.num_samples).map(move |s| {
+            let offset = pixel.sample(s) - vector![0.5, 0.5];
+            let uv = vector![
+                (i as Real + 0.5 + offset.x) / self.width as Real,
+                (j as Real + 0.5 + offset.y) / self.height as Real
+            ];
+            (uv, offset, square_to_disk(lens.sample(s)))
         })
     }
 }
@@ -88,42 +324,57 @@ pub struct PathTraceOutput {
     pub final_color: Color,
     pub normal: Rvec3,
     pub hit: bool,
+    /// Material of the first hit, which stands for the id of the object for the compositing masks
+    pub material: Option<MaterialId>,
 }
 
 // TODO: could the background be a material too?
+/// With an ambient light, the paths stop at the first diffuse hit, which is lit by the ambient light and the lights
+/// only. It is much faster and less noisy, but there are no shadows nor light bounced by the other objects.
+/// After the first hit, the materials are made at least min_roughness rough, 0 to keep them as they are.
+#[allow(clippy::too_many_arguments)]
 pub fn trace_path(scene: &Hittable, ray: &Ray, depth: usize, scene_data: &SceneData, rng: &mut Randomizer,
-    background: &Emit) -> PathTraceOutput
+    background: &Emit, lights: &LightList, ambient: Option<&Ambient>, min_roughness: Real) -> PathTraceOutput
 {
     assert!(depth >= 1);
-    trace_path_first(scene, ray, depth, scene_data, rng, background)
+    let mut output = trace_path_first(scene, ray, depth, scene_data, rng, background, lights, ambient, min_roughness);
+    if let Some(wavelength) = ray.wavelength {
+        // A ray that picked one of the 3 channels before being traced, like with the chromatic aberration, only
+        // brings back this channel
+        output.final_color = 3.0 * output.final_color.component_mul(&wavelength.mask());
+    }
+    output
 }
 
 // The first ray of the path tracing provides additional noiseless data like albedo and normal
+#[allow(clippy::too_many_arguments)]
 fn trace_path_first(scene: &Hittable, ray: &Ray, depth: usize, scene_data: &SceneData, rng: &mut Randomizer,
-    background: &Emit) -> PathTraceOutput
+    background: &Emit, lights: &LightList, ambient: Option<&Ambient>, min_roughness: Real) -> PathTraceOutput
 {
     if let Some((hit, material)) = scene.hit(ray, scene_data) {
-        let mut mat_out = scene_data.material_table[material.to_index()].evaluate(ray, &hit, scene_data, rng);
+        // The surfaces seen directly are never made rougher
+        let mat_out = scene_data.material(material).evaluate(ray, &hit, scene_data, rng, 0.0);
+        let depth = scene_data.material(material).limit_depth(depth);
         let normal = hit.normal;
-        let final_color = mat_out.emit + mat_out.scatter.take().map_or(
-            // Absorb
-            rgb(0.0, 0.0, 0.0),
-            // Bounce
-            |scatter| mat_out.absorb.component_mul(
-                &trace_path_continue(scene, &scatter, depth-1, scene_data, rng, background)
-            )
-        );
-        PathTraceOutput {final_color, normal, hit: true}
+        let throughput = mat_out.absorb;
+        let final_color = mat_out.emit + mat_out.absorb.component_mul(&scatter_and_sample_lights(
+            scene, ray, &hit, &mat_out, &throughput, depth, scene_data, rng, background, lights, ambient, min_roughness
+        ));
+        PathTraceOutput {final_color, normal, hit: true, material: Some(material)}
     } else {
         let final_color = background.evaluate(ray, &Hit::at_infinity(&ray.direction), scene_data, rng);
         let normal = rgb(0.0, 0.0, 0.0); // What to put here? Will advise later
-        PathTraceOutput {final_color, normal, hit: false}
+        PathTraceOutput {final_color, normal, hit: false, material: None}
     }
 }
 
-// The rays that come after the first provide just a color
-fn trace_path_continue(scene: &Hittable, ray: &Ray, depth: usize, scene_data: &SceneData, rng: &mut Randomizer,
-    background: &Emit) -> Color
+// The rays that come after the first provide just a color.
+// The pdf is the density of the scattering that picked the ray, None if the ray could not have been found by
+// sampling the lights. The throughput is the product of the absorptions of the previous hits.
+#[allow(clippy::too_many_arguments)]
+fn trace_path_continue(scene: &Hittable, ray: &Ray, pdf: Option<Real>, throughput: &Color, depth: usize,
+    scene_data: &SceneData, rng: &mut Randomizer, background: &Emit, lights: &LightList, ambient: Option<&Ambient>,
+    min_roughness: Real) -> Color
 {
     if depth == 0 {
         // This ray did not reach any light
@@ -131,16 +382,803 @@ fn trace_path_continue(scene: &Hittable, ray: &Ray, depth: usize, scene_data: &S
     }
 
     if let Some((hit, material)) = scene.hit(ray, scene_data) {
-        let mut mat_out = scene_data.material_table[material.to_index()].evaluate(ray, &hit, scene_data, rng);
-        mat_out.emit + mat_out.scatter.take().map_or(
-            // Absorb
-            rgb(0.0, 0.0, 0.0),
-            // Bounce
-            |scatter| mat_out.absorb.component_mul(
-                &trace_path_continue(scene, &scatter, depth-1, scene_data, rng, background)
+        let mat_out = scene_data.material(material).evaluate(ray, &hit, scene_data, rng, min_roughness);
+        let depth = scene_data.material(material).limit_depth(depth);
+
+        // The lights were also sampled from the previous hit, both ways of finding this one are weighted. The density
+        // is turned into a solid angle with the face normal, as for the light samples, not the interpolated one.
+        let light_pdf = lights.pdf_area(material) * hit.t * hit.t / hit.geometric_normal.dot(&ray.direction).abs();
+        let emit_weight = match pdf {
+            Some(pdf) if light_pdf > 0.0 => balance_heuristic(pdf, light_pdf),
+            _ => 1.0,
+        };
+
+        if scene_data.material(material).is_passthrough() {
+            // The path goes on from the same origin, without a bounce, so that the surface behind is weighted with the
+            // density and the distance of the last scattering, as the light samples taken there
+            let mut through = ray.clone();
+            through.t_min = hit.t + hit.ray_epsilon();
+            return emit_weight * mat_out.emit + trace_path_continue(
+                scene, &through, pdf, throughput, depth, scene_data, rng, background, lights, ambient, min_roughness
             )
-        )
+        }
+
+        let throughput = throughput.component_mul(&mat_out.absorb);
+        emit_weight * mat_out.emit + mat_out.absorb.component_mul(&scatter_and_sample_lights(
+            scene, ray, &hit, &mat_out, &throughput, depth, scene_data, rng, background, lights, ambient, min_roughness
+        ))
     } else {
         background.evaluate(ray, &Hit::at_infinity(&ray.direction), scene_data, rng)
     }
-}
\ No newline at end of file
+}
+
+/// Weight of a sample picked with the density pdf when another strategy could have picked it with other_pdf
+fn balance_heuristic(pdf: Real, other_pdf: Real) -> Real {
+    pdf / (pdf + other_pdf)
+}
+
+/// Below this, whatever light reaches the hit is absorbed before it gets to the camera
+const MIN_THROUGHPUT: Real = 1e-9;
+
+// The light coming to a hit, before the absorption: the scattered ray is followed, and when the scattering can be
+// evaluated in any direction, a point is also picked on the lights. Both are combined with multiple importance
+// sampling, so that the small lights are found by the light samples and the sharp reflections by the scattering.
+// The throughput includes the absorption of this hit: when it is black, no ray is cast at all.
+#[allow(clippy::too_many_arguments)]
+fn scatter_and_sample_lights(scene: &Hittable, ray: &Ray, hit: &Hit, mat_out: &MaterialOutput, throughput: &Color,
+    depth: usize, scene_data: &SceneData, rng: &mut Randomizer, background: &Emit, lights: &LightList,
+    ambient: Option<&Ambient>, min_roughness: Real) -> Color
+{
+    if throughput.max() < MIN_THROUGHPUT {
+        return rgb(0.0, 0.0, 0.0)
+    }
+
+    if let (Some(ambient), Some(lobe @ Lobe::Lambert {normal})) = (ambient, &mat_out.lobe) {
+        // The path stops here: the background is replaced by the ambient light, and the lights are sampled alone,
+        // without a scattered ray to be weighted against
+        let mut color = ambient.irradiance(normal) / PI;
+        if let Some(light) = lights.sample(scene_data, rng) {
+            color += sample_light(scene, ray, hit, lobe, &light, scene_data, false);
+        }
+        for light in &scene_data.light_table {
+            color += sample_point_light(scene, ray, hit, lobe, light, scene_data);
+        }
+        return color
+    }
+
+    let lobe = mat_out.lobe.as_ref().filter(|_| !lights.is_empty());
+    let mut color = rgb(0.0, 0.0, 0.0);
+
+    if let Some(lobe) = lobe {
+        if let Some(light) = lights.sample(scene_data, rng) {
+            color += sample_light(scene, ray, hit, lobe, &light, scene_data, true);
+        }
+    }
+
+    // The scattered rays cannot find the point lights, so all of them are sampled, without weights
+    if let Some(lobe) = &mat_out.lobe {
+        for light in &scene_data.light_table {
+            color += sample_point_light(scene, ray, hit, lobe, light, scene_data);
+        }
+    }
+
+    if let Some(scatter) = &mat_out.scatter {
+        let pdf = lobe.map(|lobe| lobe.pdf(&ray.direction, &scatter.direction));
+        color += trace_path_continue(
+            scene, scatter, pdf, throughput, depth-1, scene_data, rng, background, lights, ambient, min_roughness
+        );
+    }
+    color
+}
+
+/// The light sample is weighted against the scattering with multiple importance sampling when mis is true
+fn sample_light(scene: &Hittable, ray: &Ray, hit: &Hit, lobe: &Lobe, light: &LightSample, scene_data: &SceneData,
+    mis: bool) -> Color
+{
+    let to_light = light.position - hit.position;
+    let distance = to_light.norm();
+    let direction = to_light / distance;
+    let value = lobe.value(&ray.direction, &direction);
+    let cos_light = light.normal.dot(&direction).abs();
+    if value <= 0.0 || cos_light <= 0.0 {
+        return rgb(0.0, 0.0, 0.0)
+    }
+
+    // Anything opaque between the hit and the point on the light casts a shadow. The nudge of the start may bring the
+    // light closer by as much, so the shadow ray stops short by twice the nudge.
+    let mut shadow_ray = ray.scatter(hit, direction);
+    shadow_ray.t_max = distance - 2.0 * hit.ray_epsilon();
+    if shadowed(scene, &shadow_ray, scene_data) {
+        return rgb(0.0, 0.0, 0.0)
+    }
+
+    let light_pdf = light.pdf_area * distance * distance / cos_light;
+    let weight = if mis {balance_heuristic(light_pdf, lobe.pdf(&ray.direction, &direction))} else {1.0};
+    weight * value / light_pdf * light.emission
+}
+
+/// Whether anything but the passthrough surfaces stops the shadow ray before its end
+fn shadowed(scene: &Hittable, shadow_ray: &Ray, scene_data: &SceneData) -> bool {
+    if !scene.hit_any(shadow_ray, scene_data) {
+        return false
+    }
+    if !scene_data.material_table.iter().any(Material::is_passthrough) {
+        return true
+    }
+    // Only the closest hits tell whether the surfaces in the way are all passthrough
+    let mut ray = shadow_ray.clone();
+    while let Some((hit, material)) = scene.hit(&ray, scene_data) {
+        if !scene_data.material(material).is_passthrough() {
+            return true
+        }
+        ray.t_min = hit.t + hit.ray_epsilon();
+    }
+    false
+}
+
+/// The light of a point light, with the inverse square falloff, unless something casts a shadow
+fn sample_point_light(scene: &Hittable, ray: &Ray, hit: &Hit, lobe: &Lobe, light: &Light, scene_data: &SceneData)
+    -> Color
+{
+    let to_light = light.position() - hit.position;
+    let distance = to_light.norm();
+    let direction = to_light / distance;
+    let value = lobe.value(&ray.direction, &direction);
+    let intensity = light.intensity(&-direction);
+    if value <= 0.0 || intensity == Color::zeros() {
+        return rgb(0.0, 0.0, 0.0)
+    }
+
+    let mut shadow_ray = ray.scatter(hit, direction);
+    shadow_ray.t_max = distance - 2.0 * hit.ray_epsilon();
+    if shadowed(scene, &shadow_ray, scene_data) {
+        return rgb(0.0, 0.0, 0.0)
+    }
+    value / (distance * distance) * intensity
+}
+
+// ------------------------------------------- Image rendering -------------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct RenderParams {
+    pub width: u32,
+    pub height: u32,
+    pub num_samples: u32,
+    pub filter: Filter,
+    pub max_bounce: usize,
+    pub tile_size: u32,
+    /// At least one worker renders the tiles, even with 0
+    pub num_threads: usize,
+    /// The image only depends on the seed and the frame, not on the number of threads nor on the tile size
+    pub seed: u64,
+    pub frame: u32,
+    /// Opaque, or a transparent background with the foreground coverage in the alpha channel
+    pub alpha_mode: AlphaMode,
+    /// Light the diffuse surfaces with an ambient light baked from the background, instead of following the bounces
+    pub fast: bool,
+    /// Make the metals, the GGX and the clearcoats at least this rough after the first bounce, None to keep them as
+    /// they are. The light that bounces between smooth surfaces before reaching a diffuse one, like the caustics,
+    /// is found by chance and shows as fireflies. Rougher surfaces spread it, and the GGX ones can then be reached by
+    /// the light samples. This is biased: the reflections seen in other surfaces and the caustics are blurred.
+    pub min_roughness_after_bounce: Option<Real>,
+    pub show_progress: bool,
+    /// Layout of the progress bar, with the keys of indicatif, or None for DEFAULT_PROGRESS_TEMPLATE
+    pub progress_template: Option<String>,
+}
+
+/// Bounds of auto_tile_size: smaller tiles cost more to schedule and to assemble, larger ones leave threads idle
+const MIN_TILE_SIZE: u32 = 8;
+const MAX_TILE_SIZE: u32 = 64;
+/// Enough tiles for each thread that the last ones to finish do not keep the others waiting for long
+const TILES_PER_THREAD: u32 = 4;
+
+/// The largest power of 2 tile size, from MIN_TILE_SIZE to MAX_TILE_SIZE, that splits the image in at least
+/// TILES_PER_THREAD tiles for each thread
+pub fn auto_tile_size(width: u32, height: u32, num_threads: usize) -> u32 {
+    let min_tiles = TILES_PER_THREAD as u64 * num_threads as u64;
+    let num_tiles = |size: u32| width.div_ceil(size) as u64 * height.div_ceil(size) as u64;
+    let mut size = MAX_TILE_SIZE;
+    while size > MIN_TILE_SIZE && num_tiles(size) < min_tiles {
+        size /= 2;
+    }
+    size
+}
+
+/// The number of threads that can run at once on this machine, 1 if it cannot be known
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Percentage, elapsed time, estimated time left, and the throughput in the message
+pub const DEFAULT_PROGRESS_TEMPLATE: &str =
+    "{wide_bar} {percent:>3}% | {elapsed_precise} elapsed | ETA {eta_precise} | {msg}";
+
+/// The camera samples traced per second, the bounces are not counted
+fn format_throughput(samples: u64, elapsed: Duration) -> String {
+    let per_second = samples as f64 / elapsed.as_secs_f64().max(1e-3);
+    if per_second >= 1e6 {
+        format!("{:.2} M samples/s", per_second / 1e6)
+    } else {
+        format!("{:.1} k samples/s", per_second / 1e3)
+    }
+}
+
+/// A small image on a single thread, without a progress bar, to be changed with the struct update syntax
+impl Default for RenderParams {
+    fn default() -> Self {
+        RenderParams {
+            width: 320,
+            height: 240,
+            num_samples: 4,
+            filter: Filter::Tent {radius: 1.0},
+            max_bounce: 8,
+            tile_size: 16,
+            num_threads: 1,
+            seed: 0,
+            frame: 0,
+            alpha_mode: AlphaMode::Opaque,
+            fast: false,
+            min_roughness_after_bounce: None,
+            show_progress: false,
+            progress_template: None,
+        }
+    }
+}
+
+impl RenderParams {
+    pub fn sampler(&self) -> Multisampler {
+        Multisampler {
+            width: self.width, height: self.height, num_samples: self.num_samples, filter: self.filter.clone()
+        }
+    }
+}
+
+/// The linear colors of the image, how much each pixel is covered by the foreground, and the object seen in each
+/// pixel, None for the background
+pub struct RenderOutput {
+    pub color: Array2d<Color>,
+    pub foreground: Array2d<Real>,
+    pub object_id: Array2d<Option<MaterialId>>,
+}
+
+/// A tile rendered by a worker, with its colors, foreground coverage and object ids
+pub type CompleteJob = (Tile, Array2d<Color>, Array2d<Real>, Array2d<Option<MaterialId>>);
+
+/// The tiles left to render, popped from the end, and the ones completed by the workers
+pub struct RenderJobs {
+    pub queue: Mutex<Vec<Tile>>,
+    pub complete: Mutex<Vec<CompleteJob>>,
+}
+
+/// Render the scene as it is: the acceleration structures must already be built. Fails before rendering anything if
+/// the materials do not pass SceneData::check_materials.
+pub fn render_image(scene: &ExampleScene, params: &RenderParams) -> Result<RenderOutput, Box<dyn Error>> {
+    render_image_watched(scene, params, |_| ())
+}
+
+/// Same as render_image, but watch is run on the calling thread while the workers render, to look at the jobs.
+/// Clearing the queue stops the render early, with the tiles that were not rendered left black.
+pub fn render_image_watched(scene: &ExampleScene, params: &RenderParams, watch: impl FnOnce(&RenderJobs))
+    -> Result<RenderOutput, Box<dyn Error>>
+{
+    let (lights, ambient) = prepare_lighting(scene, params)?;
+    Ok(render_view(scene, &scene.camera, &lights, ambient.as_ref(), params, watch))
+}
+
+/// Render the scene through each of the cameras, with the same parameters. The lists of the scene are turned into
+/// Bvhs first, and the lights are gathered, only once for all the views. Fails on invalid materials like render_image.
+pub fn render_views(scene: &mut ExampleScene, cameras: &[Camera], params: &RenderParams)
+    -> Result<Vec<Array2d<Color>>, Box<dyn Error>>
+{
+    scene.root.build_acceleration(&scene.scene_data);
+    let (lights, ambient) = prepare_lighting(scene, params)?;
+    Ok(cameras.iter()
+        .map(|camera| render_view(scene, camera, &lights, ambient.as_ref(), params, |_| ()).color)
+        .collect())
+}
+
+/// What the lighting needs before rendering, which does not depend on the camera. The materials are checked there,
+/// for all the render entry points.
+fn prepare_lighting(scene: &ExampleScene, params: &RenderParams)
+    -> Result<(LightList, Option<Ambient>), Box<dyn Error>>
+{
+    scene.scene_data.check_materials().map_err(|error| format!("Invalid materials: {}", error))?;
+
+    // The emissive spheres and triangles are sampled directly, along with the scattered rays
+    let lights = LightList::new(&scene.root, &scene.scene_data);
+
+    // In fast mode, the background is baked into an ambient light for the diffuse surfaces
+    let ambient = params.fast.then(|| {
+        let mut rng = Randomizer::seed_from_u64(params.seed);
+        Ambient::from_background(&scene.background, &scene.scene_data, &mut rng)
+    });
+    Ok((lights, ambient))
+}
+
+/// Same as render_image_watched, through any camera, with the lighting already prepared
+fn render_view(scene: &ExampleScene, camera: &Camera, lights: &LightList, ambient: Option<&Ambient>,
+    params: &RenderParams, watch: impl FnOnce(&RenderJobs)) -> RenderOutput
+{
+    let camera = camera.prepare().with_resolution(params.width, params.height);
+
+    // Put tiles into the job queue, which is popped from the end, so that the center is rendered first
+    let mut queue = Tile::split_spiral(params.width, params.height, params.tile_size, params.tile_size);
+    queue.reverse();
+    // The progress counts the samples, so that the smaller tiles on the edges weigh less in the time estimate
+    let num_samples = params.width as u64 * params.height as u64 * params.num_samples as u64;
+    let progress_bar = if params.show_progress {ProgressBar::new(num_samples)} else {ProgressBar::hidden()};
+    let template = params.progress_template.as_deref().unwrap_or(DEFAULT_PROGRESS_TEMPLATE);
+    progress_bar.set_style(ProgressStyle::default_bar().template(template));
+    let jobs = RenderJobs {queue: Mutex::new(queue), complete: Mutex::new(Vec::new())};
+
+    // The workers stop when the queue is empty
+    thread::scope(|scope| {
+        for _ in 0..params.num_threads.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let job = {
+                        // Momentarily lock the job queue to pop a new job
+                        jobs.queue.lock().unwrap().pop()
+                    };
+
+                    if let Some(tile) = job {
+                        let (color_buffer, foreground_buffer, object_id_buffer) = render_tile(
+                            scene, &camera, lights, ambient, params, &tile
+                        );
+                        let tile_samples = tile.width as u64 * tile.height as u64 * params.num_samples as u64;
+                        // Push the finished job
+                        jobs.complete.lock().unwrap().push((tile, color_buffer, foreground_buffer, object_id_buffer));
+                        progress_bar.inc(tile_samples);
+                        progress_bar.set_message(format_throughput(progress_bar.position(), progress_bar.elapsed()));
+                    } else {
+                        break
+                    }
+                }
+            });
+        }
+        watch(&jobs);
+    });
+    progress_bar.finish();
+
+    // Combine the tiles into one image
+    let complete = jobs.complete.into_inner().unwrap();
+    RenderOutput {
+        color: assemble_tiles(params.width, params.height, complete.iter().map(|(tile, color, ..)| (tile, color))),
+        foreground: assemble_tiles(
+            params.width, params.height, complete.iter().map(|(tile, _, foreground, _)| (tile, foreground))
+        ),
+        object_id: assemble_tiles(
+            params.width, params.height, complete.iter().map(|(tile, .., object_id)| (tile, object_id))
+        ),
+    }
+}
+
+/// Render the pixels of a tile, along with how much each of them is covered by the foreground and the object seen
+/// by the sample with the greatest weight.
+/// Each pixel has its own random numbers, so that the image only depends on the seed, and not on which worker
+/// rendered which tile.
+pub fn render_tile(scene: &ExampleScene, camera: &CameraCache, lights: &LightList, ambient: Option<&Ambient>,
+    params: &RenderParams, tile: &Tile) -> (Array2d<Color>, Array2d<Real>, Array2d<Option<MaterialId>>)
+{
+    let sampler = params.sampler();
+    let mut color_buffer = Array2d::new(tile.width, tile.height);
+    let mut foreground_buffer = Array2d::new(tile.width, tile.height);
+    let mut object_id_buffer = Array2d::new(tile.width, tile.height);
+
+    // Walk on each pixel of the tile
+    for tj in 0..tile.height {
+        for ti in 0..tile.width {
+            // Each pixel has its own random numbers, so that the image does not depend on how it is split in tiles
+            let (i, j) = (ti + tile.offset_i, tj + tile.offset_j);
+            let pixel_seed = noise::integer(i as _, j as _, params.frame as _, params.seed as _);
+            let mut rng = Randomizer::seed_from_u64(pixel_seed as u64);
+
+            // Jitter the sample inside its pixel
+            let samples = sampler.make_uv_jitter(i, j, &mut rng);
+
+            // Accumulate the values of each sample, weighted by the reconstruction filter
+            let mut final_color = rgb(0.0, 0.0, 0.0);
+            let mut foreground = 0.0;
+            let mut total_weight = 0.0;
+            // The id cannot be averaged, it is taken from the sample that counts the most, near the center
+            let mut object_id = None;
+            let mut best_weight = 0.0;
+            for (s, offset, lens) in samples {
+                let weight = sampler.filter.weight(&offset);
+                total_weight += weight;
+                let ray = camera.shoot(s, lens, &mut rng);
+                let trace_out = trace_path(
+                    &scene.root, &ray, params.max_bounce, &scene.scene_data, &mut rng, &scene.background, lights,
+                    ambient, params.min_roughness_after_bounce.unwrap_or(0.0)
+                );
+                if weight > best_weight {
+                    best_weight = weight;
+                    object_id = trace_out.material;
+                }
+                let color = sanitize(&trace_out.final_color);
+                if trace_out.hit {
+                    final_color += weight * color;
+                    foreground += weight;
+                } else if params.alpha_mode == AlphaMode::Opaque {
+                    // The background is only visible when it is not transparent
+                    final_color += weight * color;
+                }
+            }
+            // Write the final color which is the weighted average of the samples
+            if total_weight > 0.0 {
+                *color_buffer.get_mut(ti, tj) = final_color / total_weight;
+                *foreground_buffer.get_mut(ti, tj) = foreground / total_weight;
+            }
+            *object_id_buffer.get_mut(ti, tj) = object_id;
+        }
+    }
+    (color_buffer, foreground_buffer, object_id_buffer)
+}
+
+// ------------------------------------------- Progressive rendering -------------------------------------------
+
+/// The passes of a long render added together, to be saved to disk in between and resumed after a crash
+#[derive(Debug, Clone)]
+pub struct Accumulator {
+    /// Sum of the colors of the passes, each weighted by its number of samples
+    color_sum: Array2d<Color>,
+    samples: Array2d<u32>,
+    /// Number of passes added, which sets the seed of the next one
+    passes: u32,
+}
+
+/// Start of a checkpoint file, followed by the width, the height and the number of passes, then for each pixel its
+/// color sum as 3 Real and its number of samples as a u32, all little endian
+const CHECKPOINT_MAGIC: &[u8; 4] = b"RTCK";
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Self {
+        Accumulator {color_sum: Array2d::new(width, height), samples: Array2d::new(width, height), passes: 0}
+    }
+
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    pub fn samples(&self) -> &Array2d<u32> {
+        &self.samples
+    }
+
+    /// Add a pass, whose pixels are each the average of num_samples samples
+    pub fn add(&mut self, image: &Array2d<Color>, num_samples: u32) {
+        assert!(
+            (image.width(), image.height()) == (self.color_sum.width(), self.color_sum.height()),
+            "Cannot add a {}x{} pass to a {}x{} render",
+            image.width(), image.height(), self.color_sum.width(), self.color_sum.height()
+        );
+        self.color_sum = self.color_sum.zip_map(image, |sum, color| sum + num_samples as Real * color);
+        self.samples.as_mut_slice().iter_mut().for_each(|samples| *samples += num_samples);
+        self.passes += 1;
+    }
+
+    /// The average of all the samples, black where there are none yet
+    pub fn image(&self) -> Array2d<Color> {
+        self.color_sum.zip_map(&self.samples, |sum, &samples| {
+            if samples > 0 {sum / samples as Real} else {rgb(0.0, 0.0, 0.0)}
+        })
+    }
+
+    /// Write the checkpoint next to path first, then move it over, so that a crash while saving keeps the previous
+    /// checkpoint whole
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let temporary_path = format!("{}.tmp", path);
+        self.write(&temporary_path)?;
+        std::fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+
+    fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(CHECKPOINT_MAGIC)?;
+        for x in [self.color_sum.width(), self.color_sum.height(), self.passes] {
+            file.write_all(&x.to_le_bytes())?;
+        }
+        for (sum, samples) in self.color_sum.as_slice().iter().zip(self.samples.as_slice()) {
+            for channel in sum.iter() {
+                file.write_all(&channel.to_le_bytes())?;
+            }
+            file.write_all(&samples.to_le_bytes())?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(format!("{} is not a checkpoint", path).into())
+        }
+        let read_u32 = |file: &mut BufReader<File>| -> Result<u32, Box<dyn Error>> {
+            let mut bytes = [0; 4];
+            file.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+        let (width, height, passes) = (read_u32(&mut file)?, read_u32(&mut file)?, read_u32(&mut file)?);
+
+        // Check the size before allocating, a damaged header could ask for anything
+        let pixel_size = 3 * std::mem::size_of::<Real>() as u64 + 4;
+        let expected_len = (width as u64 * height as u64).checked_mul(pixel_size).and_then(|x| x.checked_add(16));
+        let len = file.get_ref().metadata()?.len();
+        if expected_len != Some(len) {
+            return Err(format!("The {} bytes of {} do not make a {}x{} checkpoint", len, path, width, height).into())
+        }
+
+        let num_pixels = width as usize * height as usize;
+        let mut color_sum = Vec::with_capacity(num_pixels);
+        let mut samples = Vec::with_capacity(num_pixels);
+        for _ in 0..num_pixels {
+            let mut channels = [0.0; 3];
+            for channel in channels.iter_mut() {
+                let mut bytes = [0; std::mem::size_of::<Real>()];
+                file.read_exact(&mut bytes)?;
+                *channel = Real::from_le_bytes(bytes);
+            }
+            color_sum.push(Color::from(channels));
+            samples.push(read_u32(&mut file)?);
+        }
+        Ok(Accumulator {
+            color_sum: Array2d::from_raw(width, height, color_sum)?,
+            samples: Array2d::from_raw(width, height, samples)?,
+            passes,
+        })
+    }
+}
+
+/// The seed of a pass of a progressive render. The first one keeps the seed of the parameters, so that a single pass
+/// gives the same image as render_image.
+pub fn pass_seed(seed: u64, pass: u32) -> u64 {
+    if pass == 0 {
+        seed
+    } else {
+        noise::integer(pass as _, 0, 0, seed as _) as u64
+    }
+}
+
+/// Add passes of params.num_samples samples to the accumulator, calling checkpoint after each of them, to save it.
+/// The passes carry on from those already in the accumulator, so that a render resumed from a checkpoint gives the
+/// same image as if it had never stopped. The foreground and the object ids of the last pass are returned, along
+/// with the average of all the passes. Fails on invalid materials like render_image, before the first pass.
+pub fn render_progressive(scene: &ExampleScene, params: &RenderParams, accumulator: &mut Accumulator, passes: u32,
+    mut checkpoint: impl FnMut(&Accumulator)) -> Result<RenderOutput, Box<dyn Error>>
+{
+    let (lights, ambient) = prepare_lighting(scene, params)?;
+    let mut last = None;
+    for _ in 0..passes {
+        let pass_params = RenderParams {seed: pass_seed(params.seed, accumulator.passes), ..params.clone()};
+        let output = render_view(scene, &scene.camera, &lights, ambient.as_ref(), &pass_params, |_| ());
+        accumulator.add(&output.color, params.num_samples);
+        checkpoint(accumulator);
+        last = Some(output);
+    }
+    let RenderOutput {foreground, object_id, ..} = last.unwrap_or_else(|| RenderOutput {
+        color: Array2d::new(params.width, params.height),
+        foreground: Array2d::new(params.width, params.height),
+        object_id: Array2d::new(params.width, params.height),
+    });
+    Ok(RenderOutput {color: accumulator.image(), foreground, object_id})
+}
+
+// ------------------------------------------- Overlay -------------------------------------------
+
+/// A segment in world space to be drawn on top of the render
+#[derive(Debug, Clone)]
+pub struct OverlayLine {
+    pub start: Rvec3,
+    pub end: Rvec3,
+    pub color: Color,
+}
+
+/// The X, Y and Z axes in red, green and blue
+pub fn axes_overlay(origin: &Rvec3, length: Real) -> Vec<OverlayLine> {
+    vec![
+        OverlayLine {start: *origin, end: origin + vector![length, 0.0, 0.0], color: rgb(1.0, 0.0, 0.0)},
+        OverlayLine {start: *origin, end: origin + vector![0.0, length, 0.0], color: rgb(0.0, 1.0, 0.0)},
+        OverlayLine {start: *origin, end: origin + vector![0.0, 0.0, length], color: rgb(0.0, 0.0, 1.0)},
+    ]
+}
+
+/// Project the segments through the camera and draw them as anti-aliased lines over the image
+pub fn draw_overlay_lines(image: &mut Array2d<Color>, camera: &Camera, lines: &[OverlayLine]) {
+    const NEAR: Real = 1e-3;
+    let to_local = camera.transformation.inverse();
+    let (width, height) = (image.width() as Real, image.height() as Real);
+
+    for line in lines {
+        // Clip the segment to the part that is in front of the camera
+        let mut a = to_local.transform_point(&line.start);
+        let mut b = to_local.transform_point(&line.end);
+        if a.z > -NEAR && b.z > -NEAR {
+            continue
+        } else if a.z > -NEAR {
+            a = b + (a - b) * ((-NEAR - b.z) / (a.z - b.z));
+        } else if b.z > -NEAR {
+            b = a + (b - a) * ((-NEAR - a.z) / (b.z - a.z));
+        }
+
+        // Pixel coordinates, with the pixel centers on integer values
+        let to_pixel = |p: &Rvec3| {
+            let uv = camera.project_local(p);
+            vector![uv.x * width - 0.5, uv.y * height - 0.5]
+        };
+        draw_line(image, to_pixel(&a), to_pixel(&b), &line.color);
+    }
+}
+
+/// Xiaolin Wu's line: each step along the major axis is shared between the two closest pixels across it
+fn draw_line(image: &mut Array2d<Color>, a: Rvec2, b: Rvec2, color: &Color) {
+    let steep = (b.y - a.y).abs() > (b.x - a.x).abs();
+    let (mut a, mut b) = if steep { (vector![a.y, a.x], vector![b.y, b.x]) } else { (a, b) };
+    if a.x > b.x {
+        std::mem::swap(&mut a, &mut b);
+    }
+    let gradient = if b.x - a.x > 0.0 { (b.y - a.y) / (b.x - a.x) } else { 0.0 };
+
+    // Only walk the part of the line that is inside the image
+    let (major_len, minor_len) = if steep {
+        (image.height() as Real, image.width() as Real)
+    } else {
+        (image.width() as Real, image.height() as Real)
+    };
+    let start = a.x.round().max(0.0);
+    let end = b.x.round().min(major_len - 1.0);
+
+    let mut blend = |major: Real, minor: Real, coverage: Real| {
+        if minor < 0.0 || minor >= minor_len {
+            return
+        }
+        let (i, j) = if steep { (minor as u32, major as u32) } else { (major as u32, minor as u32) };
+        let pixel = image.get_mut(i, j);
+        *pixel = *pixel * (1.0 - coverage) + color * coverage;
+    };
+
+    let mut x = start;
+    while x <= end {
+        let y = a.y + gradient * (x - a.x);
+        let y_floor = y.floor();
+        let fraction = y - y_floor;
+        blend(x, y_floor, 1.0 - fraction);
+        blend(x, y_floor + 1.0, fraction);
+        x += 1.0;
+    }
+}
+
+// ------------------------------------------- Post-processing -------------------------------------------
+
+/// Position of a pixel center relative to the image center, scaled so that the corners are at a distance of 1
+fn centered_coordinates<T: Clone + Default>(image: &Array2d<T>, i: u32, j: u32) -> Rvec2 {
+    let half_size = vector![image.width() as Real, image.height() as Real] / 2.0;
+    let p = vector![i as Real + 0.5, j as Real + 0.5] - half_size;
+    p / half_size.norm()
+}
+
+/// Color between the pixels, with the pixel centers on integer values. Black outside of the image.
+fn sample_bilinear<T>(image: &Array2d<T>, x: Real, y: Real) -> T
+    where T: Clone + Default + std::ops::Add<Output = T> + std::ops::Mul<Real, Output = T>
+{
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let texel = |i: Real, j: Real| {
+        if i < 0.0 || j < 0.0 || i >= image.width() as Real || j >= image.height() as Real {
+            T::default()
+        } else {
+            image.get(i as u32, j as u32).clone()
+        }
+    };
+    let bottom = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1.0, y0) * fx;
+    let top = texel(x0, y0 + 1.0) * (1.0 - fx) + texel(x0 + 1.0, y0 + 1.0) * fx;
+    bottom * (1.0 - fy) + top * fy
+}
+
+/// Darken the image toward the corners: the center is untouched, and the corners are multiplied by 1 - strength.
+/// The falloff is quadratic with the distance to the center.
+pub fn apply_vignette(image: &mut Array2d<Color>, strength: Real) {
+    for j in 0..image.height() {
+        for i in 0..image.width() {
+            let r2 = centered_coordinates(image, i, j).norm_squared();
+            *image.get_mut(i, j) *= (1.0 - strength * r2).clamp(0.0, 1.0);
+        }
+    }
+}
+
+/// Radial lens distortion: each pixel at the distance r of the center takes the color found at
+/// r * (1 + k1 r² + k2 r⁴), with r = 1 at the corners. Positive coefficients give a barrel distortion,
+/// negative ones a pincushion distortion. What comes from outside of the image is zero (black or transparent).
+pub fn apply_distortion<T>(image: &mut Array2d<T>, k1: Real, k2: Real)
+    where T: Clone + Default + std::ops::Add<Output = T> + std::ops::Mul<Real, Output = T>
+{
+    let source = image.clone();
+    let half_size = vector![image.width() as Real, image.height() as Real] / 2.0;
+    for j in 0..image.height() {
+        for i in 0..image.width() {
+            let p = centered_coordinates(image, i, j);
+            let r2 = p.norm_squared();
+            let q = p * (1.0 + k1 * r2 + k2 * r2 * r2) * half_size.norm() + half_size;
+            *image.get_mut(i, j) = sample_bilinear(&source, q.x - 0.5, q.y - 0.5);
+        }
+    }
+}
+
+/// Blur with a gaussian of standard deviation sigma (in pixels), horizontally then vertically.
+/// The vertical pass is a horizontal pass on the transposed image. What would come from outside of the image is zero.
+/// A sigma of 0 or less leaves the image as it is.
+fn blur_gaussian(image: &Array2d<Color>, sigma: Real) -> Array2d<Color> {
+    if sigma <= 0.0 {
+        return image.clone()
+    }
+    let radius = (3.0 * sigma).ceil() as i64;
+    let kernel: Vec<Real> = (-radius..=radius).map(|x| (-0.5 * (x as Real / sigma).powi(2)).exp()).collect();
+    let total: Real = kernel.iter().sum();
+    let kernel: Vec<Real> = kernel.iter().map(|k| k / total).collect();
+
+    let horizontal_pass = |source: &Array2d<Color>| {
+        let mut target = Array2d::new(source.width(), source.height());
+        let width = source.width() as i64;
+        for j in 0..source.height() {
+            let (source_row, target_row) = (source.row(j), target.row_mut(j));
+            for (i, target) in (0..width).zip(target_row) {
+                let mut sum = rgb(0.0, 0.0, 0.0);
+                for (k, weight) in (-radius..=radius).zip(&kernel) {
+                    if (0..width).contains(&(i + k)) {
+                        sum += *weight * source_row[(i + k) as usize];
+                    }
+                }
+                *target = sum;
+            }
+        }
+        target
+    };
+    horizontal_pass(&horizontal_pass(image).transpose()).transpose()
+}
+
+/// Make the bright parts of a linear image glow: the luminance above the threshold is blurred at the scales
+/// radius, 2 radius and 4 radius (in pixels), and added back multiplied by the intensity.
+pub fn apply_bloom(image: &mut Array2d<Color>, threshold: Real, intensity: Real, radius: Real) {
+    // Keep only what is above the threshold, with the hue of the pixel
+    let mut bright = Array2d::new(image.width(), image.height());
+    for j in 0..image.height() {
+        for i in 0..image.width() {
+            let color = image.get(i, j);
+            let lum = luminance(color);
+            if lum > threshold {
+                *bright.get_mut(i, j) = color * ((lum - threshold) / lum);
+            }
+        }
+    }
+
+    const SCALES: [Real; 3] = [1.0, 2.0, 4.0];
+    for scale in SCALES {
+        let glow = blur_gaussian(&bright, scale * radius);
+        for j in 0..image.height() {
+            for i in 0..image.width() {
+                *image.get_mut(i, j) += (intensity / SCALES.len() as Real) * glow.get(i, j);
+            }
+        }
+    }
+}
+
+/// A distinct and bright color for each object, black for the background. The hue turns by the golden ratio from
+/// one id to the next, so that neighboring ids get far apart colors.
+pub fn object_id_colors(object_id: &Array2d<Option<MaterialId>>) -> Array2d<[u8; 4]> {
+    const SATURATION: Real = 0.7;
+    const VALUE: Real = 0.95;
+    object_id.map(|id| match id {
+        Some(id) => {
+            let hue = (id.to_index() as Real * 0.618034).fract() * 6.0;
+            // https://en.wikipedia.org/wiki/HSL_and_HSV#HSV_to_RGB_alternative
+            let channel = |n: Real| {
+                let k = (n + hue) % 6.0;
+                let value = VALUE * (1.0 - SATURATION * k.min(4.0 - k).clamp(0.0, 1.0));
+                (255.0 * value) as u8
+            };
+            [channel(5.0), channel(3.0), channel(1.0), 0xff]
+        }
+        None => [0, 0, 0, 0xff],
+    })
+}
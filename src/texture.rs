@@ -7,14 +7,30 @@ declare_index_wrapper!(TextureId, u32);
 
 // ------------------------------------------- Texture -------------------------------------------
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Texture {
     Missing,
     DebugUVs,
     Solid(Color),
-    Image(Array2d<[u8; 4]>),
-    Checker {odd: TextureId, even: TextureId},
-    Noise {seed: isize},
-    Perlin {seed: isize},
+    /// The wrap modes tell what to do with the texture coordinates outside of [0, 1], along U and along V.
+    /// With mips, the texture is blurred where it is seen from afar, instead of shimmering.
+    Image {image: Array2d<[u8; 4]>, wrap_u: WrapMode, wrap_v: WrapMode, mips: Option<MipChain>},
+    /// An image of floats, sampled as is: the colors are linear and may be brighter than white or negative.
+    /// For environment maps loaded with hdr::load, and for height maps and masks that need more than 8 bits.
+    HdrImage {image: Array2d<Color>, wrap_u: WrapMode, wrap_v: WrapMode},
+    /// Unit cells alternating between two textures
+    Checker {odd: TextureId, even: TextureId, space: TextureSpace},
+    /// Goes from a to b as the luminance of the factor goes from 0 to 1
+    Blend {a: TextureId, b: TextureId, factor: TextureId},
+    /// A random gray level for each unit cell
+    Noise {seed: isize, space: TextureSpace},
+    /// Smooth noise with a feature size of about 1
+    Perlin {seed: isize, space: TextureSpace},
+    /// Cellular noise: a distance to the feature points scattered one per cell of a grid with scale cells per unit,
+    /// measured in cells, for stones, scales and cracks
+    Worley {seed: isize, scale: Real, feature: WorleyFeature, space: TextureSpace},
+    /// A reference grid to check the texture coordinates, with cells x cells cells over the unit square
+    UvGrid {cells: u32, line_color: Color, bg_color: Color},
 }
 
 impl Texture {
@@ -23,56 +39,216 @@ impl Texture {
             Self::Missing => rgb(0.0, 0.0, 0.0),
             Self::DebugUVs => rgb(hit.uv.x, hit.uv.y, 0.0),
             Self::Solid(color) => *color,
-            Self::Image(image)
-                => sample_image(incident, hit, scene_data, rng, image),
-            Self::Checker {odd, even}
-                => sample_checker(incident, hit, scene_data, rng, *odd, *even),
-            Self::Noise {seed}
-                => sample_noise(incident, hit, scene_data, rng, *seed),
-            Self::Perlin {seed}
-                => sample_perlin(incident, hit, scene_data, rng, *seed),
+            Self::Image {image, wrap_u, wrap_v, mips}
+                => sample_image(incident, hit, scene_data, rng, image, *wrap_u, *wrap_v, mips.as_ref()),
+            Self::HdrImage {image, wrap_u, wrap_v}
+                => sample_hdr_image(incident, hit, scene_data, rng, image, *wrap_u, *wrap_v),
+            Self::Checker {odd, even, space}
+                => sample_checker(incident, hit, scene_data, rng, *odd, *even, *space),
+            Self::Blend {a, b, factor}
+                => sample_blend(incident, hit, scene_data, rng, *a, *b, *factor),
+            Self::Noise {seed, space}
+                => sample_noise(incident, hit, scene_data, rng, *seed, *space),
+            Self::Perlin {seed, space}
+                => sample_perlin(incident, hit, scene_data, rng, *seed, *space),
+            Self::Worley {seed, scale, feature, space}
+                => sample_worley(incident, hit, scene_data, rng, *seed, *scale, *feature, *space),
+            Self::UvGrid {cells, line_color, bg_color}
+                => sample_uv_grid(incident, hit, scene_data, rng, *cells, line_color, bg_color),
         }
     }
 }
 
+/// Where the procedural textures are evaluated. The images always follow the texture coordinates.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextureSpace {
+    /// The position of the hit in world space: the texture is carved out of a solid block, and the objects slide
+    /// through it when they move
+    Position,
+    /// The texture coordinates of the surface, scaled, as (u, v, 0): the texture is painted on the surface and
+    /// follows it, like on a mesh with texture coordinates
+    Uv {scale: Real},
+}
+
+impl TextureSpace {
+    /// The point where a procedural texture is evaluated for a hit
+    pub fn point(self, hit: &Hit) -> Rvec3 {
+        match self {
+            Self::Position => hit.position,
+            Self::Uv {scale} => vector![scale * hit.uv.x, scale * hit.uv.y, 0.0],
+        }
+    }
+}
+
+/// Which distance a Worley texture shows
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WorleyFeature {
+    /// Distance to the nearest feature point, 0 at the points and brightest at the edges of the cells
+    F1,
+    /// Distance to the second nearest feature point, for rounder blobs
+    F2,
+    /// F2 - F1, 0 along the edges of the cells, for veins and cracks
+    Edge,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WrapMode {
+    /// Tile the texture
+    Repeat,
+    /// Extend the border texels
+    Clamp,
+    /// Tile the texture, flipping every other tile
+    Mirror,
+}
+
+impl WrapMode {
+    /// Bring a texture coordinate into [0, 1]
+    pub fn apply(self, x: Real) -> Real {
+        match self {
+            Self::Repeat => x.rem_euclid(1.0),
+            Self::Clamp => x.clamp(0.0, 1.0),
+            Self::Mirror => 1.0 - (x.rem_euclid(2.0) - 1.0).abs(),
+        }
+    }
+}
+
+/// Smaller and smaller copies of an image, each half the size of the previous one, down to a single texel
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MipChain {
+    /// The levels after the full image, which is level 0
+    levels: Vec<Array2d<[u8; 4]>>,
+    /// Size of a texel of the full image on the surface, in world units, to compare it with the footprint of a ray
+    texel_size: Real,
+}
+
+impl MipChain {
+    pub fn new(image: &Array2d<[u8; 4]>, texel_size: Real) -> Self {
+        let mut levels: Vec<Array2d<[u8; 4]>> = Vec::new();
+        loop {
+            let last = levels.last().unwrap_or(image);
+            // A side of 0 stays 0 when halved, so an empty image never gets to 1x1
+            if last.width() <= 1 && last.height() <= 1 {
+                break
+            }
+            let next = box_downscale(last);
+            levels.push(next);
+        }
+        MipChain {levels, texel_size}
+    }
+
+    /// The number of levels after the full image
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The level where a texel covers the footprint, with a fractional part to blend two levels
+    fn level(&self, footprint: Real) -> Real {
+        (footprint / self.texel_size).log2().clamp(0.0, self.levels.len() as Real)
+    }
+}
+
+/// Halve the size of an image, each texel being the average of up to 2x2 texels
+fn box_downscale(image: &Array2d<[u8; 4]>) -> Array2d<[u8; 4]> {
+    let width = image.width().div_ceil(2);
+    let height = image.height().div_ceil(2);
+    let mut result = Array2d::new(width, height);
+    for j in 0..height {
+        for i in 0..width {
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for (si, sj) in [(2 * i, 2 * j), (2 * i + 1, 2 * j), (2 * i, 2 * j + 1), (2 * i + 1, 2 * j + 1)] {
+                if si < image.width() && sj < image.height() {
+                    let texel = image.get(si, sj);
+                    (0..4).for_each(|c| sum[c] += texel[c] as u32);
+                    count += 1;
+                }
+            }
+            *result.get_mut(i, j) = sum.map(|x| ((x + count / 2) / count) as u8);
+        }
+    }
+    result
+}
+
 // ------------------------------------------- Texture implementations -------------------------------------------
 
+#[allow(clippy::too_many_arguments)]
 pub fn sample_image(_incident: &Ray, hit: &Hit, _scene_data: &SceneData, _rng: &mut Randomizer,
-    image: &Array2d<[u8; 4]>) -> Color
+    image: &Array2d<[u8; 4]>, wrap_u: WrapMode, wrap_v: WrapMode, mips: Option<&MipChain>) -> Color
+{
+    let sample_level = |level: &Array2d<[u8; 4]>| {
+        let pixel = texel(level, &hit.uv, wrap_u, wrap_v);
+        rgb(pixel[0] as Real, pixel[1] as Real, pixel[2] as Real) / 255.0
+    };
+
+    let (mips, differentials) = match (mips, &hit.differentials) {
+        (Some(mips), Some(differentials)) => (mips, differentials),
+        _ => return sample_level(image),
+    };
+    let level = mips.level(differentials.footprint());
+
+    // Blend the two closest levels
+    let get_level = |k: usize| if k == 0 {image} else {&mips.levels[k - 1]};
+    let k = level.floor() as usize;
+    let color = sample_level(get_level(k));
+    let t = level - k as Real;
+    if t > 0.0 {
+        color.lerp(&sample_level(get_level(k + 1)), t)
+    } else {
+        color
+    }
+}
+
+pub fn sample_hdr_image(_incident: &Ray, hit: &Hit, _scene_data: &SceneData, _rng: &mut Randomizer,
+    image: &Array2d<Color>, wrap_u: WrapMode, wrap_v: WrapMode) -> Color
 {
+    *texel(image, &hit.uv, wrap_u, wrap_v)
+}
+
+/// The texel under the texture coordinates, without filtering
+fn texel<'a, T: Clone + Default>(image: &'a Array2d<T>, uv: &Rvec2, wrap_u: WrapMode, wrap_v: WrapMode) -> &'a T {
     let w = image.width() as Real;
     let h = image.height() as Real;
-    let i = (hit.uv.x * w).clamp(0.0, w-1.0) as u32;
-    let j = (hit.uv.y * h).clamp(0.0, h-1.0) as u32;
-    let pixel = image.get(i, j);
-    rgb(pixel[0] as Real, pixel[1] as Real, pixel[2] as Real) / 255.0
+    let i = (wrap_u.apply(uv.x) * w).clamp(0.0, w-1.0) as u32;
+    let j = (wrap_v.apply(uv.y) * h).clamp(0.0, h-1.0) as u32;
+    image.get(i, j)
 }
 
 pub fn sample_checker(incident: &Ray, hit: &Hit, scene_data: &SceneData, rng: &mut Randomizer, odd: TextureId,
-    even: TextureId) -> Color
+    even: TextureId, space: TextureSpace) -> Color
 {
-    let p = hit.position;
+    let p = space.point(hit);
     if (p.x.floor() + p.y.floor() + p.z.floor()) % 2.0 == 0.0 {
-        scene_data.texture_table[even.to_index()].sample(incident, hit, scene_data, rng)
+        scene_data.texture(even).sample(incident, hit, scene_data, rng)
     } else {
-        scene_data.texture_table[odd.to_index()].sample(incident, hit, scene_data, rng)
+        scene_data.texture(odd).sample(incident, hit, scene_data, rng)
     }
 }
 
-pub fn sample_noise(_incident: &Ray, hit: &Hit, _scene_data: &SceneData, _rng: &mut Randomizer, seed: isize) -> Color
+pub fn sample_blend(incident: &Ray, hit: &Hit, scene_data: &SceneData, rng: &mut Randomizer, a: TextureId,
+    b: TextureId, factor: TextureId) -> Color
 {
-    let p = hit.position;
+    let sample = |id: TextureId, rng: &mut Randomizer|
+        scene_data.texture(id).sample(incident, hit, scene_data, rng);
+    let t = luminance(&sample(factor, rng)).clamp(0.0, 1.0);
+    let a = sample(a, rng);
+    let b = sample(b, rng);
+    a.lerp(&b, t)
+}
+
+pub fn sample_noise(_incident: &Ray, hit: &Hit, _scene_data: &SceneData, _rng: &mut Randomizer, seed: isize,
+    space: TextureSpace) -> Color
+{
+    let p = space.point(hit);
     let mut x = noise::real(p.x.floor() as isize, p.y.floor() as isize, p.z.floor() as isize, seed);
     x = 0.5 * x + 0.5;
     rgb(x, x, x)
 }
 
 fn grad_dot(p: &Rvec3, corner_x: isize, corner_y: isize, corner_z: isize, seed: isize) -> Real {
-    let grad = vector![
-        noise::real(corner_x, corner_y, corner_z, seed + 1),
-        noise::real(corner_x, corner_y, corner_z, seed + 2),
-        noise::real(corner_x, corner_y, corner_z, seed + 3)
-    ];
+    let grad = noise::vec3(corner_x, corner_y, corner_z, seed);
     (p - vector![corner_x as Real, corner_y as Real, corner_z as Real]).dot(&grad)
 }
 
@@ -80,9 +256,10 @@ fn mix(a: Real, b: Real, t: Real) -> Real {
     (b - a) * t + a
 }
 
-pub fn sample_perlin(_incident: &Ray, hit: &Hit, _scene_data: &SceneData, _rng: &mut Randomizer, seed: isize) -> Color
+pub fn sample_perlin(_incident: &Ray, hit: &Hit, _scene_data: &SceneData, _rng: &mut Randomizer, seed: isize,
+    space: TextureSpace) -> Color
 {
-    let p = hit.position;
+    let p = space.point(hit);
     let fp = p.map(|x| x.floor());
     let fl_x = fp.x as isize;
     let fl_y = fp.y as isize;
@@ -116,4 +293,62 @@ pub fn sample_perlin(_incident: &Ray, hit: &Hit, _scene_data: &SceneData, _rng:
 
     let x = 0.5 * k12345678 + 0.5;
     rgb(x, x, x)
-}
\ No newline at end of file
+}
+
+/// The feature point of a Worley cell, somewhere inside it, in cells
+pub fn worley_feature_point(cell_x: isize, cell_y: isize, cell_z: isize, seed: isize) -> Rvec3 {
+    // Three 21 bit numbers out of a single hash
+    let h = noise::integer(cell_x, cell_y, cell_z, seed) as u64;
+    let unit = |bits: u64| (bits & 0x1fffff) as Real / (1 << 21) as Real;
+    vector![cell_x as Real + unit(h), cell_y as Real + unit(h >> 21), cell_z as Real + unit(h >> 42)]
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn sample_worley(_incident: &Ray, hit: &Hit, _scene_data: &SceneData, _rng: &mut Randomizer, seed: isize,
+    scale: Real, feature: WorleyFeature, space: TextureSpace) -> Color
+{
+    let p = scale * space.point(hit);
+    let cell = p.map(|x| x.floor() as isize);
+    // A feature point is inside its cell, so the nearest one is within the neighboring cells. The second nearest
+    // may rarely be further, which only shows as a faint seam.
+    let (mut f1, mut f2) = (Real::INFINITY, Real::INFINITY);
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                let point = worley_feature_point(cell.x + dx, cell.y + dy, cell.z + dz, seed);
+                let distance = (point - p).norm();
+                if distance < f1 {
+                    f2 = f1;
+                    f1 = distance;
+                } else if distance < f2 {
+                    f2 = distance;
+                }
+            }
+        }
+    }
+    let x = match feature {
+        WorleyFeature::F1 => f1,
+        WorleyFeature::F2 => f2,
+        WorleyFeature::Edge => f2 - f1,
+    };
+    rgb(x, x, x)
+}
+
+/// Width of the lines of the UV grid, relative to the size of a cell
+const UV_GRID_LINE_WIDTH: Real = 0.05;
+
+pub fn sample_uv_grid(_incident: &Ray, hit: &Hit, _scene_data: &SceneData, _rng: &mut Randomizer, cells: u32,
+    line_color: &Color, bg_color: &Color) -> Color
+{
+    let cell_uv = hit.uv * cells as Real;
+    let cell = cell_uv.map(|x| x.floor());
+    let in_cell = cell_uv - cell;
+    let half_width = 0.5 * UV_GRID_LINE_WIDTH;
+    if in_cell.iter().any(|&x| x < half_width || x > 1.0 - half_width) {
+        *line_color
+    } else {
+        // A subtle gradient across the cells tells the U and V directions apart
+        let gradient = cell / cells as Real;
+        bg_color.component_mul(&rgb(0.7 + 0.3 * gradient.x, 0.7 + 0.3 * gradient.y, 0.7))
+    }
+}
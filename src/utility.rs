@@ -16,6 +16,7 @@ pub use std::f64::{consts::*, INFINITY}; // <-- and here as well
 pub type Rvec2 = nalgebra::Vector2<Real>;
 pub type Rvec3 = nalgebra::Vector3<Real>;
 pub type Bvec3 = nalgebra::Vector3<bool>;
+pub type Ivec3 = nalgebra::Vector3<i64>;
 pub type Rmat3 = nalgebra::Matrix3<Real>;
 
 /*
@@ -26,8 +27,14 @@ I do not use naglebra's fancy wrappers like Point and Unit because:
 
 pub use nalgebra::{vector, matrix};
 
-/// Nudge the start of the ray to avoid self-intersection
+/// Nudge the start of the camera rays, and of the rays made by hand
 pub const RAY_EPSILON: Real = 1e-3;
+/// The rays leaving a surface start a bit away from it, so that the rounding errors do not make them hit it again.
+/// The errors grow with the coordinates and with the distance travelled, and so does the nudge: a fixed one would
+/// leak through the thin gaps of a scene modeled in millimeters, and still hit again the surfaces of a huge one.
+pub const RAY_EPSILON_RELATIVE: Real = 1e-9;
+/// The smallest nudge, for the hits next to the origin
+pub const RAY_EPSILON_MIN: Real = 1e-12;
 pub const SMOL: Real = 1e-7;
 
 /// A macro to quickly declare an index wrapper
@@ -35,6 +42,7 @@ pub const SMOL: Real = 1e-7;
 macro_rules! declare_index_wrapper {
     ($WrapperType: ident, $InnerType: ident) => {
         #[derive(Debug, Clone, Copy)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub struct $WrapperType(pub $InnerType);
 
         impl $WrapperType {
@@ -54,6 +62,78 @@ pub struct Ray {
     pub direction: Rvec3, // <-- Keep this vector normalized
     pub t_min: Real,
     pub t_max: Real,
+    /// None while the ray carries all the colors, or the single one it carries after a dispersion
+    pub wavelength: Option<Wavelength>,
+    /// How the ray changes from one pixel to the next, only known for the rays that come from the camera
+    pub differentials: Option<RayDifferentials>,
+}
+
+/// Derivatives of the origin and direction of a ray with respect to the pixel coordinates x and y.
+/// See Igehy, Tracing Ray Differentials.
+#[derive(Debug, Clone)]
+pub struct RayDifferentials {
+    pub dp_dx: Rvec3,
+    pub dp_dy: Rvec3,
+    pub dd_dx: Rvec3,
+    pub dd_dy: Rvec3,
+}
+
+/// Derivatives of the hit position with respect to the pixel coordinates, which span the footprint of the pixel
+#[derive(Debug, Clone)]
+pub struct SurfaceDifferentials {
+    pub dp_dx: Rvec3,
+    pub dp_dy: Rvec3,
+}
+
+impl RayDifferentials {
+    /// The same differentials in another frame, with the linear part of the change of frame
+    pub fn rotate(&self, rotation: &Rmat3) -> Self {
+        RayDifferentials {
+            dp_dx: rotation * self.dp_dx,
+            dp_dy: rotation * self.dp_dy,
+            dd_dx: rotation * self.dd_dx,
+            dd_dy: rotation * self.dd_dy,
+        }
+    }
+}
+
+impl SurfaceDifferentials {
+    /// The same differentials in another frame, with a linear map that keeps the lengths
+    pub fn rotate(&self, rotation: &Rmat3) -> Self {
+        SurfaceDifferentials {dp_dx: rotation * self.dp_dx, dp_dy: rotation * self.dp_dy}
+    }
+
+    /// Side of a square with the same area as the footprint, a compromise between its length and its width
+    pub fn footprint(&self) -> Real {
+        self.dp_dx.cross(&self.dp_dy).norm().sqrt()
+    }
+}
+
+/// A wavelength for each color channel, for an approximation of the spectral rendering with only 3 samples
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wavelength {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Wavelength {
+    pub fn micrometers(self) -> Real {
+        match self {
+            Self::Red => 0.610,
+            Self::Green => 0.550,
+            Self::Blue => 0.465,
+        }
+    }
+
+    /// The color channel that carries this wavelength
+    pub fn mask(self) -> Color {
+        match self {
+            Self::Red => rgb(1.0, 0.0, 0.0),
+            Self::Green => rgb(0.0, 1.0, 0.0),
+            Self::Blue => rgb(0.0, 0.0, 1.0),
+        }
+    }
 }
 
 /// A ray with some additional cached information
@@ -68,6 +148,52 @@ impl Ray {
         self.origin + t * self.direction
     }
 
+    /// A ray leaving a surface after a scattering, which keeps the wavelength of the incident ray.
+    /// It starts a bit off the surface, along the geometric normal and on the side it goes to, rather than a bit
+    /// along its direction: at grazing angles, that would barely move it away from the surface.
+    pub fn scatter(&self, hit: &Hit, direction: Rvec3) -> Ray {
+        let side = if direction.dot(&hit.geometric_normal) >= 0.0 {1.0} else {-1.0};
+        Ray {
+            origin: hit.position + side * hit.ray_epsilon() * hit.geometric_normal,
+            direction,
+            t_min: 0.0,
+            t_max: Real::INFINITY,
+            wavelength: self.wavelength,
+            differentials: None,
+        }
+    }
+
+    /// The same ray in another frame: the origin is transformed as a point, the direction and the differentials as
+    /// vectors. The direction is not normalized again, so that t keeps its meaning and a hit at t in one frame is at t
+    /// in the other. It stays normalized as long as the transformation does not scale.
+    pub fn transform(&self, transformation: &Transformation) -> Ray {
+        Ray {
+            origin: transformation.transform_point(&self.origin),
+            direction: transformation.transform_vector(&self.direction),
+            differentials: self.differentials.as_ref().map(|d| d.rotate(transformation.linear())),
+            ..self.clone()
+        }
+    }
+
+    /// Carry the differentials of the ray to the point at t, on a surface approximated by its tangent plane
+    pub fn surface_differentials(&self, t: Real, normal: &Rvec3) -> Option<SurfaceDifferentials> {
+        let differentials = self.differentials.as_ref()?;
+        let cos_theta = self.direction.dot(normal);
+        if cos_theta.abs() < SMOL {
+            return None
+        }
+        // The neighboring ray travels a bit more or less than t, so that it ends up on the same plane
+        let transfer = |dp: &Rvec3, dd: &Rvec3| {
+            let dp = dp + t * dd;
+            let dt = -dp.dot(normal) / cos_theta;
+            dp + dt * self.direction
+        };
+        Some(SurfaceDifferentials {
+            dp_dx: transfer(&differentials.dp_dx, &differentials.dd_dx),
+            dp_dy: transfer(&differentials.dp_dy, &differentials.dd_dy),
+        })
+    }
+
     pub fn expand(self) -> RayExpanded {
         let inv_direction = vector![1.0 / self.direction.x, 1.0 / self.direction.y, 1.0 / self.direction.z];
         RayExpanded {
@@ -85,7 +211,16 @@ pub struct Hit {
     pub t: Real,
     pub position: Rvec3,
     pub normal: Rvec3, // <-- Keep this vector normalized
+    /// Normal of the actual surface, before the interpolation of the triangle normals and the bumps, on the same
+    /// side as the normal. The rays leaving the surface are nudged along it.
+    pub geometric_normal: Rvec3,
     pub uv: Rvec2,
+    /// Unit vector perpendicular to the normal, along which u increases
+    pub tangent: Rvec3,
+    /// Color of the vertices interpolated at the hit, white on the surfaces that have none
+    pub color: Color,
+    /// Footprint of the pixel on the surface, when the ray knows its differentials
+    pub differentials: Option<SurfaceDifferentials>,
 }
 
 impl Hit {
@@ -95,9 +230,24 @@ impl Hit {
             t: INFINITY,
             position: direction.clone(),
             normal: direction.clone(),
+            geometric_normal: *direction,
             uv: vector![0.5 - direction.z.atan2(direction.x) / TAU, direction.y.asin() / PI + 0.5],
+            tangent: tangent_along(direction, &vector![direction.z, 0.0, -direction.x]),
+            color: WHITE,
+            differentials: None,
         }
     }
+
+    /// How far from the surface the rays leaving it must start, relative to the size of the coordinates and to the
+    /// distance travelled by the ray that hit
+    pub fn ray_epsilon(&self) -> Real {
+        RAY_EPSILON_MIN.max(RAY_EPSILON_RELATIVE * (self.t + self.position.amax()))
+    }
+
+    /// Completes the tangent frame, pointing along increasing v unless the texture coordinates are mirrored
+    pub fn bitangent(&self) -> Rvec3 {
+        self.normal.cross(&self.tangent)
+    }
 }
 
 // ------------------------------------------- Some math -------------------------------------------
@@ -107,26 +257,55 @@ pub fn reflect(incident: &Rvec3, normal: &Rvec3) -> Rvec3 {
     incident - 2.0 * incident.dot(&normal) * normal
 }
 
-/// Normal and incident must be unit vectors, then it returns a unit vector
+/// Normal and incident must be unit vectors, then it returns a unit vector.
+/// None means total internal reflection, which starts at the critical angle where the refracted ray would only graze
+/// the surface: the caller is expected to reflect the ray instead.
 pub fn refract(incident: &Rvec3, normal: &Rvec3, eta: Real) -> Option<Rvec3> {
     let cos_theta = normal.dot(&incident);
     let k = 1.0 - eta * eta * (1.0 - cos_theta * cos_theta);
-    if k < 0.0 {
+    if k <= 0.0 {
         None // Total reflection
     } else {
         Some(eta * incident - (eta * cos_theta + k.sqrt()) * normal)
     }
 }
 
+/// Normal must be a unit vector, then it returns two unit vectors that form an orthonormal basis with it
+pub fn orthonormal_basis(normal: &Rvec3) -> (Rvec3, Rvec3) {
+    // https://graphics.pixar.com/library/OrthonormalB/paper.pdf
+    let sign = 1.0_f64.copysign(normal.z);
+    let a = -1.0 / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    let tangent = vector![1.0 + sign * normal.x * normal.x * a, sign * b, -sign * normal.x];
+    let bitangent = vector![b, sign + normal.y * normal.y * a, -normal.y];
+    (tangent, bitangent)
+}
+
+/// The direction made perpendicular to the normal and normalized.
+/// If it is (almost) parallel to the normal, an arbitrary tangent is returned instead.
+pub fn tangent_along(normal: &Rvec3, direction: &Rvec3) -> Rvec3 {
+    let tangent = direction - normal * (normal.dot(direction) / normal.norm_squared());
+    if tangent.norm_squared() > SMOL {
+        tangent.normalize()
+    } else {
+        orthonormal_basis(&normal.normalize()).0
+    }
+}
+
 // ------------------------------------------- Bounding boxes -------------------------------------------
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct AABB {
     pub min: Rvec3,
     pub max: Rvec3,
 }
 
 impl AABB {
+    /// The box around nothing, inside out so that its union with any box is that box
+    pub fn empty() -> AABB {
+        AABB {min: Rvec3::repeat(Real::INFINITY), max: Rvec3::repeat(-Real::INFINITY)}
+    }
+
     pub fn union(&self, other: &AABB) -> AABB {
         AABB {
             min: vector![self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)],
@@ -134,7 +313,46 @@ impl AABB {
         }
     }
 
+    /// The box around the 8 corners of this one, once multiplied by the matrix
+    pub fn transform(&self, matrix: &Rmat3) -> AABB {
+        let mut min = Rvec3::repeat(Real::INFINITY);
+        let mut max = Rvec3::repeat(-Real::INFINITY);
+        for k in 0..8 {
+            let corner = vector![
+                if k & 1 == 0 {self.min.x} else {self.max.x},
+                if k & 2 == 0 {self.min.y} else {self.max.y},
+                if k & 4 == 0 {self.min.z} else {self.max.z}
+            ];
+            let corner = matrix * corner;
+            min = min.inf(&corner);
+            max = max.sup(&corner);
+        }
+        AABB {min, max}
+    }
+
+    /// Size along each axis, negative for the empty box
+    pub fn extent(&self) -> Rvec3 {
+        self.max - self.min
+    }
+
+    /// Not a number for the empty box
+    pub fn centroid(&self) -> Rvec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    /// Zero for the empty box
+    pub fn surface_area(&self) -> Real {
+        let extent = self.extent().sup(&Rvec3::zeros());
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
     pub fn collide(&self, ray: &RayExpanded) -> bool {
+        self.collide_t(ray).is_some()
+    }
+
+    /// Returns the distance at which the ray enters the box, which is t_min if it starts inside,
+    /// or None if it misses it
+    pub fn collide_t(&self, ray: &RayExpanded) -> Option<Real> {
         // This is a hot function, optimizations are welcome
         // https://tavianator.com/2011/ray_box.html
         let t0 = (self.min - ray.inner.origin).component_mul(&ray.inv_direction);
@@ -150,44 +368,163 @@ impl AABB {
             .min(t0.y.max(t1.y))
             .min(t0.z.max(t1.z));
 
-        t_max >= t_min
+        if t_max >= t_min {Some(t_min)} else {None}
+    }
+}
+
+/// Two bounding boxes laid out axis by axis, so that both can be tested against a ray in one go
+#[derive(Debug, Clone)]
+pub struct AabbPair {
+    min: [[Real; 2]; 3],
+    max: [[Real; 2]; 3],
+}
+
+impl AabbPair {
+    pub fn new(a: &AABB, b: &AABB) -> Self {
+        AabbPair {
+            min: [[a.min.x, b.min.x], [a.min.y, b.min.y], [a.min.z, b.min.z]],
+            max: [[a.max.x, b.max.x], [a.max.y, b.max.y], [a.max.z, b.max.z]],
+        }
+    }
+
+    /// Returns the distance at which the ray enters each box, or None if it misses it
+    pub fn collide(&self, ray: &RayExpanded) -> [Option<Real>; 2] {
+        // Same slab test as AABB::collide_t, but the inner loop over the two boxes is easy to vectorize
+        let mut t_min = [ray.inner.t_min; 2];
+        let mut t_max = [ray.inner.t_max; 2];
+        for axis in 0..3 {
+            let origin = ray.inner.origin[axis];
+            let inv_direction = ray.inv_direction[axis];
+            for k in 0..2 {
+                let t0 = (self.min[axis][k] - origin) * inv_direction;
+                let t1 = (self.max[axis][k] - origin) * inv_direction;
+                t_min[k] = t_min[k].max(t0.min(t1));
+                t_max[k] = t_max[k].min(t0.max(t1));
+            }
+        }
+        [
+            if t_max[0] >= t_min[0] {Some(t_min[0])} else {None},
+            if t_max[1] >= t_min[1] {Some(t_min[1])} else {None},
+        ]
     }
 }
 
 // ------------------------------------------- Transformation -------------------------------------------
 
+/// An affine transformation: a linear part, which may rotate, scale and shear, followed by a translation
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "TransformationParts", into = "TransformationParts"))]
 pub struct Transformation {
-    pub orientation: Rmat3,
-    pub position: Rvec3,
+    linear: Rmat3,
+    position: Rvec3,
+    /// Inverse transpose of the linear part, to transform the normals
+    normal_matrix: Rmat3,
 }
 
 impl Transformation {
+    /// The linear part must be invertible
+    pub fn new(linear: Rmat3, position: Rvec3) -> Self {
+        let inverse = linear.try_inverse().expect("A transformation with a zero scale has no inverse");
+        let normal_matrix = inverse.transpose();
+        Transformation {linear, position, normal_matrix}
+    }
+
     pub fn identity() -> Self {
-        let orientation = Rmat3::identity();
-        let position = Rvec3::zeros();
-        Transformation {orientation, position}
+        Transformation::new(Rmat3::identity(), Rvec3::zeros())
     }
 
     pub fn lookat(position: &Rvec3, target: &Rvec3, up: &Rvec3) -> Self {
         let z = (position - target).normalize();
-        let x = up.cross(&z);
+        let x = up.cross(&z).normalize();
         let y = z.cross(&x);
-        Transformation {orientation: Rmat3::from_columns(&[x, y, z]), position: *position}
+        Transformation::new(Rmat3::from_columns(&[x, y, z]), *position)
+    }
+
+    /// Scale along the local axes, then rotate, then translate
+    pub fn from_trs(translation: &Rvec3, rotation: &Rotation, scale: &Rvec3) -> Self {
+        Transformation::new(rotation.matrix() * Rmat3::from_diagonal(scale), *translation)
+    }
+
+    pub fn linear(&self) -> &Rmat3 {
+        &self.linear
+    }
+
+    pub fn position(&self) -> &Rvec3 {
+        &self.position
     }
 
     pub fn inverse(&self) -> Self {
-        let inv_orientation = self.orientation.transpose();
-        let inv_position = -inv_orientation * self.position;
-        Transformation {orientation: inv_orientation, position: inv_position}
+        // The inverse of the linear part is the transpose of the normal matrix, no need to invert it again
+        let inv_linear = self.normal_matrix.transpose();
+        let inv_position = -inv_linear * self.position;
+        Transformation {linear: inv_linear, position: inv_position, normal_matrix: self.linear.transpose()}
+    }
+
+    /// The transformation that applies other first, then self, like a child placed in the frame of its parent
+    pub fn compose(&self, other: &Transformation) -> Self {
+        Transformation {
+            linear: self.linear * other.linear,
+            position: self.linear * other.position + self.position,
+            // The inverse transpose of a product is the product of the inverse transposes, in the same order
+            normal_matrix: self.normal_matrix * other.normal_matrix,
+        }
     }
 
     pub fn transform_vector(&self, vector: &Rvec3) -> Rvec3 {
-        self.orientation * vector
+        self.linear * vector
+    }
+
+    /// Normals are transformed by the inverse transpose, so that they stay perpendicular to the transformed surface.
+    /// The result is normalized.
+    pub fn transform_normal(&self, normal: &Rvec3) -> Rvec3 {
+        (self.normal_matrix * normal).normalize()
     }
 
     pub fn transform_point(&self, point: &Rvec3) -> Rvec3 {
-        self.orientation * point + self.position
+        self.linear * point + self.position
+    }
+}
+
+/// What a Transformation is serialized as, so that the normal matrix is recomputed on loading
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TransformationParts {
+    linear: Rmat3,
+    position: Rvec3,
+}
+
+#[cfg(feature = "serde")]
+impl From<TransformationParts> for Transformation {
+    fn from(parts: TransformationParts) -> Self {
+        Transformation::new(parts.linear, parts.position)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Transformation> for TransformationParts {
+    fn from(transformation: Transformation) -> Self {
+        TransformationParts {linear: transformation.linear, position: transformation.position}
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rotation {
+    /// A rotation by angle radians around the axis, counterclockwise when the axis points toward the viewer
+    AxisAngle {axis: Rvec3, angle: Real},
+    /// Rotations in radians around the X axis, then around the Y axis, then around the Z axis
+    Euler {x: Real, y: Real, z: Real},
+}
+
+impl Rotation {
+    pub fn matrix(&self) -> Rmat3 {
+        match self {
+            Self::AxisAngle {axis, angle} => {
+                nalgebra::Rotation3::from_axis_angle(&nalgebra::Unit::new_normalize(*axis), *angle).into_inner()
+            }
+            Self::Euler {x, y, z} => nalgebra::Rotation3::from_euler_angles(*x, *y, *z).into_inner(),
+        }
     }
 }
 
@@ -195,10 +532,32 @@ impl Transformation {
 
 pub type Color = nalgebra::Vector3<Real>;
 
+pub const WHITE: Color = vector![1.0, 1.0, 1.0];
+
 pub fn rgb(r: Real, g: Real, b: Real) -> Color {
     vector![r, g, b]
 }
 
+/// Perceived brightness of a linear color (Rec. 709 weights)
+pub fn luminance(color: &Color) -> Real {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// Scale the color down so that no channel is above max, which keeps its hue, unlike clamping each channel
+pub fn clamp_color(color: &Color, max: Real) -> Color {
+    let largest = color.max();
+    if largest > max {
+        color * (max / largest)
+    } else {
+        *color
+    }
+}
+
+/// Replace the NaN and infinite channels by 0, so that a single degenerate sample does not spoil a whole pixel
+pub fn sanitize(color: &Color) -> Color {
+    color.map(|x| if x.is_finite() { x } else { 0.0 })
+}
+
 pub fn to_u8(color: &Color) -> [u8; 4] {
     let clamp_and_cast = |x: Real| (255.0 * x.clamp(0.0, 1.0)) as u8;
     [
@@ -218,3 +577,33 @@ pub fn to_srgb_u8(color: &Color) -> [u8; 4] {
         0xff,
     ]
 }
+
+/// How the coverage of the foreground is stored in the alpha channel of the output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    /// No transparency, alpha is always 0xff
+    Opaque,
+    /// The color is not affected by the alpha
+    Straight,
+    /// The color is multiplied by the alpha, which composites without dark fringes
+    Premultiplied,
+}
+
+/// Same as to_srgb_u8 with a transparent background.
+/// The color must only contain the contribution of the foreground, i.e. it is already premultiplied by the coverage.
+pub fn to_srgb_u8_alpha(color: &Color, coverage: Real, alpha_mode: AlphaMode) -> [u8; 4] {
+    let gamma_correct = |x: Real| x.clamp(0.0, 1.0).powf(1.0/2.2);
+    let coverage = coverage.clamp(0.0, 1.0);
+    let straight = if coverage > 0.0 { color / coverage } else { rgb(0.0, 0.0, 0.0) };
+    let (scale, alpha) = match alpha_mode {
+        AlphaMode::Opaque => return to_srgb_u8(color),
+        AlphaMode::Straight => (1.0, coverage),
+        AlphaMode::Premultiplied => (coverage, coverage),
+    };
+    [
+        (255.0 * scale * gamma_correct(straight.x)) as u8,
+        (255.0 * scale * gamma_correct(straight.y)) as u8,
+        (255.0 * scale * gamma_correct(straight.z)) as u8,
+        (255.0 * alpha) as u8,
+    ]
+}
@@ -0,0 +1,173 @@
+use raytracing2::bvh::{Bvh, TriangleBvh};
+use raytracing2::hittable::Hittable;
+use raytracing2::material::MaterialId;
+use raytracing2::mesh::{Mesh, MeshId, Vertex};
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+fn unit_cube() -> AABB {
+    AABB {min: vector![0.0, 0.0, 0.0], max: vector![1.0, 1.0, 1.0]}
+}
+
+#[test]
+fn unit_cube_measures() {
+    let cube = unit_cube();
+    assert_eq!(cube.surface_area(), 6.0);
+    assert_eq!(cube.extent(), vector![1.0, 1.0, 1.0]);
+    assert_eq!(cube.centroid(), vector![0.5, 0.5, 0.5]);
+
+    let slab = AABB {min: vector![-1.0, 2.0, 0.0], max: vector![3.0, 4.0, 0.5]};
+    assert_eq!(slab.surface_area(), 2.0 * (4.0 * 2.0 + 2.0 * 0.5 + 0.5 * 4.0));
+    assert_eq!(slab.centroid(), vector![1.0, 3.0, 0.25]);
+}
+
+#[test]
+fn empty_is_the_identity_of_union() {
+    let boxes = [unit_cube(), AABB {min: vector![-1.0, 2.0, -3.0], max: vector![-0.5, 2.5, 4.0]}];
+    for x in boxes.iter() {
+        assert_eq!(AABB::empty().union(x), *x);
+        assert_eq!(x.union(&AABB::empty()), *x);
+    }
+    assert_eq!(AABB::empty().union(&AABB::empty()), AABB::empty());
+    assert_eq!(AABB::empty().surface_area(), 0.0);
+}
+
+#[test]
+fn collide_t_is_the_entry_distance() {
+    let ray = |origin: Rvec3, direction: Rvec3| Ray {
+        origin,
+        direction,
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    }.expand();
+    let cube = unit_cube();
+
+    // Through the face x = 0, 2 units away along a direction of length 1
+    let entering = ray(vector![-2.0, 0.5, 0.5], vector![1.0, 0.0, 0.0]);
+    assert_eq!(cube.collide_t(&entering), Some(2.0));
+    assert!(cube.collide(&entering));
+
+    // Diagonally, through the corner at the origin
+    let diagonal = ray(vector![-1.0, -1.0, -1.0], vector![1.0, 1.0, 1.0]);
+    assert_eq!(cube.collide_t(&diagonal), Some(1.0));
+
+    // From inside, the box is entered right away
+    let inside = ray(vector![0.5, 0.5, 0.5], vector![0.0, 1.0, 0.0]);
+    assert_eq!(cube.collide_t(&inside), Some(RAY_EPSILON));
+
+    // Missed, or behind, or beyond the end of the ray
+    let missing = ray(vector![-2.0, 1.5, 0.5], vector![1.0, 0.0, 0.0]);
+    assert_eq!(cube.collide_t(&missing), None);
+    assert!(!cube.collide(&missing));
+    assert_eq!(cube.collide_t(&ray(vector![2.0, 0.5, 0.5], vector![1.0, 0.0, 0.0])), None);
+    let mut short = ray(vector![-2.0, 0.5, 0.5], vector![1.0, 0.0, 0.0]);
+    short.inner.t_max = 1.5;
+    assert_eq!(cube.collide_t(&short), None);
+}
+
+#[test]
+fn empty_list_has_an_empty_box() {
+    let scene_data = SceneData::empty();
+    assert_eq!(Hittable::List(Vec::new()).bounding_box(&scene_data), AABB::empty());
+
+    // An empty list next to a sphere does not stretch the box to the origin
+    let sphere = Hittable::Sphere {center: vector![5.0, 5.0, 5.0], radius: 1.0, material: MaterialId(0)};
+    let list = Hittable::List(vec![Hittable::List(Vec::new()), sphere]);
+    assert_eq!(list.bounding_box(&scene_data), AABB {min: vector![4.0, 4.0, 4.0], max: vector![6.0, 6.0, 6.0]});
+}
+
+#[test]
+fn empty_list_does_not_move_the_bvh_bounds() {
+    let scene_data = SceneData::empty();
+    let sphere = Hittable::Sphere {center: vector![5.0, 5.0, 5.0], radius: 1.0, material: MaterialId(0)};
+    let alone = Bvh::new(vec![sphere.clone()], &scene_data);
+    let with_empty = Bvh::new(vec![sphere, Hittable::List(Vec::new())], &scene_data);
+    assert_eq!(with_empty.bounding_box(), alone.bounding_box());
+
+    // And the sphere is still found through it
+    let ray = Ray {
+        origin: vector![5.0, 5.0, 0.0],
+        direction: vector![0.0, 0.0, 1.0],
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let (hit, _) = with_empty.hit(&ray, &scene_data).unwrap();
+    assert!((hit.t - 4.0).abs() < 1e-12);
+}
+
+#[test]
+fn refit_after_moving_the_leaves_matches_a_new_bvh() {
+    let scene_data = SceneData::empty();
+    let spheres = (0..7).map(|k| {
+        let k = k as Real;
+        Hittable::Sphere {center: vector![k, (2.0 * k).sin(), -k], radius: 0.1 * (k + 1.0), material: MaterialId(0)}
+    });
+    let mut bvh = Bvh::new(spheres.collect(), &scene_data);
+
+    let offset = vector![3.0, -2.0, 10.0];
+    for leaf in bvh.leaves_mut() {
+        if let Hittable::Sphere {center, ..} = leaf {
+            *center += offset;
+        }
+    }
+    bvh.refit(&scene_data);
+    let rebuilt = Bvh::new(bvh.leaves().to_vec(), &scene_data);
+    assert_eq!(bvh.bounding_box(), rebuilt.bounding_box());
+
+    // The last sphere is found where it moved to, through the refitted boxes of the branches
+    let ray = Ray {
+        origin: vector![9.0, 12.0_f64.sin() - 2.0, 0.0],
+        direction: vector![0.0, 0.0, 1.0],
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let (hit, _) = bvh.hit(&ray, &scene_data).unwrap();
+    let (expected, _) = rebuilt.hit(&ray, &scene_data).unwrap();
+    assert!((hit.t - 3.3).abs() < 1e-12);
+    assert_eq!(hit.t, expected.t);
+}
+
+#[test]
+fn refit_after_updating_the_vertices_of_a_mesh_finds_its_triangles() {
+    // A strip of 4 unit squares along x, facing +z
+    let (normal, uv) = (vector![0.0, 0.0, 1.0], vector![0.0, 0.0]);
+    let vertices = (0..10).map(|k| {
+        let position = vector![(k / 2) as Real, (k % 2) as Real, 0.0];
+        Vertex {position, normal, uv, color: WHITE}
+    }).collect();
+    let indices = (0..4).flat_map(|k| [2 * k, 2 * k + 2, 2 * k + 1, 2 * k + 1, 2 * k + 2, 2 * k + 3]).collect();
+    let mut scene_data = SceneData {
+        mesh_table: vec![Mesh::new(vertices, indices, MaterialId(0))],
+        ..SceneData::empty()
+    };
+    let mut bvh = TriangleBvh::from_mesh(MeshId(0), &scene_data);
+
+    let offset = vector![0.0, 5.0, -2.0];
+    scene_data.mesh_table[0].update_vertices(|vertices| {
+        for vertex in vertices {
+            vertex.position += offset;
+        }
+    });
+    let ray = Ray {
+        origin: vector![3.5, 5.5, 3.0],
+        direction: vector![0.0, 0.0, -1.0],
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    // The boxes are still around the old positions
+    assert!(bvh.hit(&ray, &scene_data).is_none());
+
+    bvh.refit(&scene_data);
+    assert_eq!(bvh.bounding_box(), TriangleBvh::from_mesh(MeshId(0), &scene_data).bounding_box());
+    let (hit, _) = bvh.hit(&ray, &scene_data).unwrap();
+    assert!((hit.t - 5.0).abs() < 1e-12);
+    assert!((hit.position - vector![3.5, 5.5, -2.0]).norm() < 1e-12);
+}
@@ -0,0 +1,113 @@
+use raytracing2::example_scenes::ExampleScene;
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+const WIDTH: u32 = 9;
+const HEIGHT: u32 = 3;
+const GLOW: Color = Color::new(0.9, 0.5, 0.1);
+const BACKGROUND: Color = Color::new(0.1, 0.3, 0.8);
+
+/// A flat glowing ball so big that its edge is nearly straight, across the middle column of the image
+fn edge_scene() -> ExampleScene {
+    let material_table = vec![Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(GLOW))];
+    let root = Hittable::Sphere {center: vector![-1000.0, 0.0, 0.0], radius: 1000.0, material: MaterialId(0)};
+    let camera = Camera {
+        aspect_ratio: WIDTH as Real / HEIGHT as Real,
+        fov: FRAC_PI_3,
+        focus_distance: 5.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 0.0, 5.0],
+            &vector![0.0, 0.0, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+    let scene_data = SceneData::new(material_table);
+    ExampleScene {camera, scene_data, root, background: Emit::Color(BACKGROUND), animation: None}
+}
+
+fn render(alpha_mode: AlphaMode) -> RenderOutput {
+    let params = RenderParams {
+        width: WIDTH,
+        height: HEIGHT,
+        num_samples: 64,
+        filter: Filter::Box,
+        max_bounce: 2,
+        tile_size: 4,
+        num_threads: 2,
+        seed: 1,
+        alpha_mode,
+        ..RenderParams::default()
+    };
+    render_image(&edge_scene(), &params).unwrap()
+}
+
+fn to_linear(x: u8) -> Real {
+    (x as Real / 255.0).powf(2.2)
+}
+
+#[test]
+fn transparent_render_composites_back_into_the_opaque_one() {
+    let opaque = render(AlphaMode::Opaque);
+    let transparent = render(AlphaMode::Premultiplied);
+    let mut edge_pixels = 0;
+    for j in 0..HEIGHT {
+        for i in 0..WIDTH {
+            // The same samples are taken in both renders, only the background ones are left out of the color
+            let coverage = *transparent.foreground.get(i, j);
+            let color = transparent.color.get(i, j);
+            let composited = color + (1.0 - coverage) * BACKGROUND;
+            assert!((composited - opaque.color.get(i, j)).norm() < 1e-9, "pixel ({}, {})", i, j);
+
+            if coverage > 0.25 && coverage < 0.75 {
+                edge_pixels += 1;
+                // The edge keeps the color of the foreground, it is not darkened by the coverage a second time
+                let [r, g, b, a] = to_srgb_u8_alpha(color, coverage, AlphaMode::Straight);
+                let straight = rgb(to_linear(r), to_linear(g), to_linear(b));
+                assert!((straight - GLOW).amax() < 0.01, "pixel ({}, {}): {:?}", i, j, straight);
+                assert_eq!(a, (255.0 * coverage) as u8);
+
+                // Over the background, the written pixel gives back the opaque render
+                let alpha = a as Real / 255.0;
+                let over = alpha * straight + (1.0 - alpha) * BACKGROUND;
+                assert!((over - opaque.color.get(i, j)).amax() < 0.01, "pixel ({}, {})", i, j);
+            }
+        }
+    }
+    assert!(edge_pixels > 0);
+}
+
+/// The pixel of a file composited over red, in the sRGB space of the file
+fn over_red(pixel: [u8; 4], alpha_mode: AlphaMode) -> [Real; 3] {
+    let alpha = pixel[3] as Real / 255.0;
+    let scale = if alpha_mode == AlphaMode::Straight {alpha} else {1.0};
+    let over = |c: usize, red: Real| scale * pixel[c] as Real / 255.0 + (1.0 - alpha) * red;
+    [over(0, 1.0), over(1, 0.0), over(2, 0.0)]
+}
+
+#[test]
+fn half_covered_pixel_composites_over_red_without_a_dark_halo() {
+    let foreground = rgb(0.2, 0.6, 0.4);
+    let coverage = 0.5;
+    // Halfway between the foreground and red
+    let foreground_srgb = to_srgb_u8(&foreground).map(|x| x as Real / 255.0);
+    let red = [1.0, 0.0, 0.0];
+    let expected: Vec<Real> = (0..3).map(|c| coverage * foreground_srgb[c] + (1.0 - coverage) * red[c]).collect();
+
+    // The render keeps only the contribution of the foreground, the color is premultiplied by the coverage
+    let color = coverage * foreground;
+    for alpha_mode in [AlphaMode::Premultiplied, AlphaMode::Straight] {
+        let over = over_red(to_srgb_u8_alpha(&color, coverage, alpha_mode), alpha_mode);
+        for c in 0..3 {
+            assert!((over[c] - expected[c]).abs() < 0.01, "{:?}: {:?} instead of {:?}", alpha_mode, over, expected);
+        }
+    }
+
+    // Writing the premultiplied color as if it were straight darkens the edge
+    let [r, g, b, _] = to_srgb_u8(&color);
+    let halo = over_red([r, g, b, (255.0 * coverage) as u8], AlphaMode::Straight);
+    assert!(halo[1] < expected[1] - 0.05);
+}
@@ -0,0 +1,76 @@
+use raytracing2::image::Array2d;
+
+/// A 3x2 array whose element (i, j) is 10 j + i
+fn numbered() -> Array2d<u32> {
+    Array2d::from_raw(3, 2, vec![0, 1, 2, 10, 11, 12]).unwrap()
+}
+
+#[test]
+fn row_is_the_contiguous_slice_of_its_elements() {
+    let mut array = numbered();
+    assert_eq!(array.row(0), &[0, 1, 2]);
+    assert_eq!(array.row(1), &[10, 11, 12]);
+
+    array.row_mut(1).copy_from_slice(&[20, 21, 22]);
+    assert_eq!(*array.get(2, 1), 22);
+    assert_eq!(array.row(0), &[0, 1, 2]);
+}
+
+#[test]
+fn transpose_turns_the_columns_into_rows() {
+    let array = numbered();
+    let transposed = array.transpose();
+    assert_eq!((transposed.width(), transposed.height()), (2, 3));
+    assert_eq!(transposed.row(2), &[2, 12]);
+    for j in 0..array.height() {
+        for i in 0..array.width() {
+            assert_eq!(transposed.get(j, i), array.get(i, j));
+        }
+    }
+
+    // Twice gives back the original
+    let back = transposed.transpose();
+    assert_eq!((back.width(), back.height()), (3, 2));
+    assert_eq!(back.as_slice(), array.as_slice());
+}
+
+#[test]
+fn map_applies_the_function_to_each_element() {
+    let doubled = numbered().map(|&x| 2 * x);
+    assert_eq!((doubled.width(), doubled.height()), (3, 2));
+    assert_eq!(doubled.as_slice(), &[0, 2, 4, 20, 22, 24]);
+
+    // The element type can change
+    let strings = numbered().map(|x| x.to_string());
+    assert_eq!(strings.get(1, 1), "11");
+}
+
+#[test]
+fn zip_map_pairs_the_elements_at_the_same_position() {
+    let array = numbered();
+    let sums = array.zip_map(&array.map(|&x| x as f64 * 0.5), |&a, &b| a as f64 + b);
+    assert_eq!((sums.width(), sums.height()), (3, 2));
+    assert_eq!(sums.as_slice(), &[0.0, 1.5, 3.0, 15.0, 16.5, 18.0]);
+}
+
+#[test]
+#[should_panic(expected = "Cannot zip a 3x2 array with a 2x3 array")]
+fn zip_map_of_different_sizes_panics() {
+    let array = numbered();
+    array.zip_map(&array.transpose(), |a, b| a + b);
+}
+
+#[test]
+fn from_raw_and_into_raw_round_trip() {
+    let raw = vec![0, 1, 2, 10, 11, 12];
+    let array = Array2d::from_raw(3, 2, raw.clone()).unwrap();
+    assert_eq!(*array.get(0, 1), 10);
+    assert_eq!(array.into_raw(), raw);
+}
+
+#[test]
+fn from_raw_with_the_wrong_length_is_an_error() {
+    assert!(Array2d::from_raw(3, 2, vec![0u32; 5]).is_err());
+    assert!(Array2d::from_raw(3, 2, vec![0u32; 7]).is_err());
+    assert!(Array2d::<u32>::from_raw(0, 0, Vec::new()).is_ok());
+}
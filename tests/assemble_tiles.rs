@@ -0,0 +1,70 @@
+use raytracing2::image::*;
+use raytracing2::randomness::*;
+
+/// A buffer for each tile, where each pixel tells which tile it comes from
+fn render_tiles(tiles: &[Tile]) -> Vec<(Tile, Array2d<u32>)> {
+    tiles.iter().enumerate().map(|(id, tile)| {
+        let mut buffer = Array2d::new(tile.width, tile.height);
+        buffer.as_mut_slice().fill(id as u32 + 1);
+        (tile.clone(), buffer)
+    }).collect()
+}
+
+fn assemble(width: u32, height: u32, completed: &[(Tile, Array2d<u32>)]) -> Vec<u32> {
+    assemble_tiles(width, height, completed.iter().map(|(tile, buffer)| (tile, buffer))).into_raw()
+}
+
+#[test]
+fn assembly_is_independent_of_completion_order() {
+    let (width, height) = (100, 70);
+    let mut completed = render_tiles(&Tile::split_spiral(width, height, 16, 16));
+    let expected = assemble(width, height, &completed);
+    // Every pixel is covered by exactly one tile
+    assert!(expected.iter().all(|&id| id != 0));
+
+    let mut rng = Randomizer::seed_from_u64(0);
+    for _ in 0..10 {
+        completed.shuffle(&mut rng);
+        assert_eq!(assemble(width, height, &completed), expected);
+    }
+}
+
+#[test]
+fn overlapping_tiles_are_assembled_in_a_stable_order() {
+    let (width, height) = (8, 8);
+    let tiles = [
+        Tile {offset_i: 0, offset_j: 0, width: 6, height: 6},
+        Tile {offset_i: 2, offset_j: 2, width: 6, height: 6},
+        Tile {offset_i: 4, offset_j: 0, width: 4, height: 4},
+    ];
+    let mut completed = render_tiles(&tiles);
+    let expected = assemble(width, height, &completed);
+    completed.reverse();
+    assert_eq!(assemble(width, height, &completed), expected);
+    completed.rotate_left(1);
+    assert_eq!(assemble(width, height, &completed), expected);
+
+    // The tile that starts on the highest row is pasted last
+    assert_eq!(expected[3 + 3 * width as usize], 2);
+    // On the same row, the tile that starts on the right is pasted last
+    assert_eq!(expected[5 + width as usize], 3);
+}
+
+#[test]
+fn spiral_starts_on_the_center_pixel() {
+    let contains = |tile: &Tile, i: u32, j: u32| {
+        (tile.offset_i..tile.offset_i + tile.width).contains(&i)
+            && (tile.offset_j..tile.offset_j + tile.height).contains(&j)
+    };
+    for (width, height, tile_size) in [(64, 64, 16), (100, 37, 16), (7, 5, 2), (800, 600, 64), (10, 10, 32)] {
+        let tiles = Tile::split_spiral(width, height, tile_size, tile_size);
+        assert!(contains(&tiles[0], width / 2, height / 2), "{}x{}: starts on {:?}", width, height, tiles[0]);
+
+        // The same tiles as in rows, only in another order
+        let key = |tile: &Tile| tile.assembly_key();
+        let mut sorted: Vec<_> = tiles.iter().map(key).collect();
+        sorted.sort_unstable();
+        let rows: Vec<_> = Tile::split_in_tiles(width, height, tile_size, tile_size).iter().map(key).collect();
+        assert_eq!(sorted, rows);
+    }
+}
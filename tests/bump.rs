@@ -0,0 +1,46 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::texture::*;
+use raytracing2::utility::*;
+
+/// The normal that a lambert material bumped by the texture shades a ball with where the ray from the origin hits
+/// it, and the normal of the ball there
+fn shading_normal(height: Texture, origin: Rvec3) -> (Rvec3, Rvec3) {
+    let lambert = || Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(WHITE), Emit::None);
+    let scene_data = SceneData {
+        material_table: vec![lambert().with_bump(Bump {texture: TextureId(0), strength: 2.0})],
+        texture_table: vec![height],
+        ..SceneData::empty()
+    };
+    let ray = Ray {
+        origin,
+        direction: -origin.normalize(),
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let ball = Hittable::Sphere {center: Rvec3::zeros(), radius: 1.0, material: MaterialId(0)};
+    let (hit, material) = ball.hit(&ray, &scene_data).unwrap();
+    let mut rng = Randomizer::seed_from_u64(2);
+    let output = scene_data.material(material).evaluate(&ray, &hit, &scene_data, &mut rng, 0.0);
+    match output.lobe {
+        Some(Lobe::Lambert {normal}) => (normal, hit.normal),
+        lobe => panic!("Unexpected lobe {:?}", lobe),
+    }
+}
+
+#[test]
+fn constant_height_keeps_the_normal() {
+    for origin in [vector![0.0, 0.0, 3.0], vector![1.0, 2.0, -2.0], vector![-2.5, 0.3, 0.1]] {
+        let (bumped, normal) = shading_normal(Texture::Solid(rgb(0.3, 0.5, 0.7)), origin);
+        assert_eq!(bumped, normal);
+
+        // A varying height does tilt it
+        let (bumped, normal) = shading_normal(Texture::Perlin {seed: 4, space: TextureSpace::Position}, origin);
+        assert!((bumped - normal).norm() > 1e-3);
+        assert!((bumped.norm() - 1.0).abs() < 1e-12);
+    }
+}
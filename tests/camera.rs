@@ -0,0 +1,144 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::material::MaterialId;
+use raytracing2::randomness::*;
+use raytracing2::render::{Camera, SceneData};
+use raytracing2::utility::*;
+
+const TOLERANCE: Real = 1e-12;
+
+fn camera(eye: Rvec3, target: Rvec3, lens_radius: Real) -> Camera {
+    Camera {
+        aspect_ratio: 16.0 / 9.0,
+        fov: FRAC_PI_3,
+        focus_distance: (target - eye).norm(),
+        lens_radius,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(&eye, &target, &vector![0.0, 1.0, 0.0]),
+    }
+}
+
+#[test]
+fn center_ray_points_along_the_forward_axis() {
+    let eye = vector![1.0, 2.0, 3.0];
+    let target = vector![-2.0, 0.5, -4.0];
+    // With and without a lens, the center ray goes through the center of the lens
+    for lens_radius in [0.0, 0.3] {
+        let camera = camera(eye, target, lens_radius);
+        let ray = camera.shoot_center(vector![0.5, 0.5]);
+        let forward = -camera.transformation.linear().column(2).normalize();
+        assert!((ray.origin - eye).norm() < TOLERANCE);
+        assert!((ray.direction - forward).norm() < TOLERANCE);
+        assert!((ray.direction - (target - eye).normalize()).norm() < TOLERANCE);
+    }
+}
+
+#[test]
+fn center_ray_is_the_same_every_time() {
+    let camera = camera(vector![0.0, 1.0, 5.0], vector![0.0, 1.0, 0.0], 0.5);
+    let uv = vector![0.2, 0.9];
+    let first = camera.shoot_center(uv);
+    let second = camera.shoot_center(uv);
+    assert_eq!(first.origin, second.origin);
+    assert_eq!(first.direction, second.direction);
+}
+
+#[test]
+fn center_ray_goes_through_the_projected_point() {
+    let camera = camera(vector![0.0, 1.0, 5.0], vector![0.5, 0.0, 0.0], 0.0);
+    for point in [vector![0.0, 0.0, 0.0], vector![1.0, 1.5, -2.0], vector![-2.0, 0.2, 1.0]] {
+        let uv = camera.project(&point).unwrap();
+        let ray = camera.shoot_center(uv);
+        let to_point = (point - ray.origin).normalize();
+        assert!((ray.direction - to_point).norm() < 1e-9, "point {:?} seen at {:?}", point, uv);
+    }
+}
+
+#[test]
+fn only_the_plane_at_the_focus_distance_is_sharp() {
+    let eye = vector![1.0, 2.0, 3.0];
+    let target = vector![-2.0, 0.5, -4.0];
+    let focus_distance = (target - eye).norm();
+    let forward = (target - eye) / focus_distance;
+    let uv = vector![0.3, 0.8];
+    let lens_points = [vector![0.0, 0.0], vector![1.0, 0.0], vector![0.0, -1.0], vector![-0.6, 0.6]];
+    // Spread of the points where the rays through the lens cross the plane at some depth in front of the camera
+    let spread = |lens_radius: Real, depth: Real| {
+        let camera = camera(eye, target, lens_radius);
+        let points: Vec<Rvec3> = lens_points.iter().map(|&lens| {
+            let ray = camera.shoot(uv, lens, &mut Randomizer::seed_from_u64(0));
+            let t = (depth - (ray.origin - eye).dot(&forward)) / ray.direction.dot(&forward);
+            ray.at(t)
+        }).collect();
+        points.iter().map(|p| (p - points[0]).norm()).fold(0.0, Real::max)
+    };
+
+    for lens_radius in [0.0, 0.1, 0.5] {
+        assert!(spread(lens_radius, focus_distance) < 1e-9, "blurry in focus with a lens of {}", lens_radius);
+    }
+    // Out of focus, the blur grows with the lens, and a pinhole keeps everything sharp
+    for depth in [0.5 * focus_distance, 2.0 * focus_distance] {
+        assert!(spread(0.0, depth) < 1e-9);
+        assert!(spread(0.1, depth) > 0.01);
+        assert!(spread(0.5, depth) > 4.0 * spread(0.1, depth));
+    }
+}
+
+#[test]
+fn prepared_camera_shoots_the_same_rays_as_the_local_frame() {
+    let camera = camera(vector![1.0, 2.0, 3.0], vector![-2.0, 0.5, -4.0], 0.3);
+    let cache = camera.prepare();
+    let mut rng = Randomizer::seed_from_u64(3);
+    for _ in 0..1000 {
+        let uv = vector![rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0)];
+        let lens = vector![rng.gen_range(-0.7..0.7), rng.gen_range(-0.7..0.7)];
+
+        // The ray built in the local frame of the camera, then moved to the world
+        let tan_fov = (0.5 * camera.fov).tan();
+        let origin = camera.lens_radius * vector![lens.x, lens.y, 0.0];
+        let target = camera.focus_distance * vector![
+            (2.0 * uv.x - 1.0) * tan_fov * camera.aspect_ratio,
+            (2.0 * uv.y - 1.0) * tan_fov,
+            -1.0
+        ];
+        let expected_origin = camera.transformation.transform_point(&origin);
+        let expected_direction = camera.transformation.transform_vector(&(target - origin)).normalize();
+
+        let ray = cache.shoot(uv, lens, &mut rng);
+        assert!((ray.origin - expected_origin).norm() < TOLERANCE);
+        assert!((ray.direction - expected_direction).norm() < TOLERANCE);
+    }
+}
+
+#[test]
+fn look_at_scene_fits_the_bounding_box_in_the_frustum() {
+    let scene_data = SceneData::empty();
+    let root = Hittable::List(vec![
+        Hittable::Sphere {center: vector![10.0, 0.0, -3.0], radius: 1.0, material: MaterialId(0)},
+        Hittable::Sphere {center: vector![14.0, 5.0, -1.0], radius: 2.0, material: MaterialId(0)},
+        Hittable::Capsule {
+            a: vector![9.0, 1.0, 2.0], b: vector![12.0, -2.0, 0.0], radius: 0.5, material: MaterialId(0)
+        },
+    ]);
+    let aabb = root.bounding_box(&scene_data);
+    let center = 0.5 * (aabb.min + aabb.max);
+
+    // The camera starts far away and looking elsewhere, only its view direction is kept
+    let views = [(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, -1.0]), (vector![5.0, 9.0, 1.0], vector![-1.0, 2.0, 3.0])];
+    for (eye, target) in views {
+        let mut camera = camera(eye, target, 0.0);
+        camera.look_at_scene(&root, &scene_data, &vector![0.0, 1.0, 0.0]);
+        let forward = -camera.transformation.linear().column(2).normalize();
+        let focus = camera.transformation.position() + camera.focus_distance * forward;
+        assert!((focus - center).norm() < 1e-9);
+        assert!((camera.project(&center).unwrap() - vector![0.5, 0.5]).norm() < 1e-9);
+        for i in 0..8 {
+            let corner = vector![
+                if i & 1 == 0 {aabb.min.x} else {aabb.max.x},
+                if i & 2 == 0 {aabb.min.y} else {aabb.max.y},
+                if i & 4 == 0 {aabb.min.z} else {aabb.max.z}
+            ];
+            let uv = camera.project(&corner).expect("corner behind the camera");
+            assert!(uv.iter().all(|&x| (0.0..=1.0).contains(&x)), "corner {:?} seen at {:?}", corner, uv);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use raytracing2::example_scenes;
+use raytracing2::render::*;
+
+fn params() -> RenderParams {
+    RenderParams {
+        width: 20,
+        height: 12,
+        num_samples: 2,
+        filter: Filter::Tent {radius: 1.0},
+        max_bounce: 4,
+        tile_size: 8,
+        num_threads: 2,
+        seed: 9,
+        ..RenderParams::default()
+    }
+}
+
+#[test]
+fn resumed_render_matches_an_uninterrupted_one() {
+    let mut scene = example_scenes::three_balls();
+    scene.root.build_acceleration(&scene.scene_data);
+    let params = params();
+
+    let mut uninterrupted = Accumulator::new(params.width, params.height);
+    let mut saved_passes = Vec::new();
+    let image = render_progressive(&scene, &params, &mut uninterrupted, 3, |a| saved_passes.push(a.passes()))
+        .unwrap().color;
+    assert_eq!(saved_passes, vec![1, 2, 3]);
+    assert!(uninterrupted.samples().as_slice().iter().all(|&samples| samples == 6));
+
+    // Stop after the first pass, then load the checkpoint and render the two others
+    let path = std::env::temp_dir().join(format!("raytracing2_test_{}.checkpoint", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut first = Accumulator::new(params.width, params.height);
+    let first_image = render_progressive(&scene, &params, &mut first, 1, |a| a.save(path).unwrap()).unwrap().color;
+    let mut resumed = Accumulator::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(resumed.passes(), 1);
+    assert_eq!(resumed.image().as_slice(), first_image.as_slice());
+    let resumed_image = render_progressive(&scene, &params, &mut resumed, 2, |_| ()).unwrap().color;
+    assert_eq!(resumed_image.as_slice(), image.as_slice());
+
+    // The first pass is a plain render, and the next ones add other samples
+    assert_eq!(first_image.as_slice(), render_image(&scene, &params).unwrap().color.as_slice());
+    assert_ne!(first_image.as_slice(), image.as_slice());
+}
+
+#[test]
+fn other_files_are_not_checkpoints() {
+    assert!(Accumulator::load("tests/golden/three_balls.tga").is_err());
+    assert!(Accumulator::load("tests/golden/missing.checkpoint").is_err());
+}
+
+#[test]
+fn saving_replaces_the_checkpoint_without_leaving_a_temporary_file() {
+    let path = std::env::temp_dir().join(format!("raytracing2_replaced_{}.checkpoint", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut accumulator = Accumulator::new(3, 2);
+    accumulator.save(path).unwrap();
+    accumulator.add(&raytracing2::image::Array2d::new(3, 2), 4);
+    accumulator.save(path).unwrap();
+    let loaded = Accumulator::load(path);
+    let temporary_exists = std::path::Path::new(&format!("{}.tmp", path)).exists();
+    std::fs::remove_file(path).unwrap();
+    assert!(!temporary_exists);
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.passes(), 1);
+    assert_eq!(loaded.samples().as_slice(), &[4; 6]);
+}
+
+#[test]
+fn header_must_match_the_length_of_the_file() {
+    let path = std::env::temp_dir().join(format!("raytracing2_damaged_{}.checkpoint", std::process::id()));
+    let path = path.to_str().unwrap();
+    Accumulator::new(3, 2).save(path).unwrap();
+    let bytes = std::fs::read(path).unwrap();
+
+    // A huge size in the header, which must not be allocated
+    let mut huge = bytes.clone();
+    huge[4..12].copy_from_slice(&[0xff; 8]);
+    std::fs::write(path, &huge).unwrap();
+    let huge = Accumulator::load(path);
+    // A missing pixel
+    std::fs::write(path, &bytes[..bytes.len() - 28]).unwrap();
+    let truncated = Accumulator::load(path);
+    // Trailing bytes
+    std::fs::write(path, [&bytes[..], &[0]].concat()).unwrap();
+    let trailing = Accumulator::load(path);
+    std::fs::remove_file(path).unwrap();
+    assert!(huge.is_err());
+    assert!(truncated.is_err());
+    assert!(trailing.is_err());
+}
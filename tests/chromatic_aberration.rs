@@ -0,0 +1,60 @@
+use raytracing2::example_scenes::ExampleScene;
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 32;
+
+/// A white glowing ball near the right border of the frame, over a black background
+fn edge_scene(chromatic_aberration: Real) -> ExampleScene {
+    let material_table = vec![Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(1.0, 1.0, 1.0)))];
+    let root = Hittable::Sphere {center: vector![4.0, 0.0, 0.0], radius: 1.0, material: MaterialId(0)};
+    let camera = Camera {
+        aspect_ratio: WIDTH as Real / HEIGHT as Real,
+        fov: FRAC_PI_3,
+        focus_distance: 5.0,
+        lens_radius: 0.0,
+        chromatic_aberration,
+        transformation: Transformation::lookat(
+            &vector![0.0, 0.0, 5.0],
+            &vector![0.0, 0.0, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+    let scene_data = SceneData::new(material_table);
+    ExampleScene {camera, scene_data, root, background: Emit::None, animation: None}
+}
+
+fn render(chromatic_aberration: Real) -> Vec<Color> {
+    let params = RenderParams {
+        width: WIDTH,
+        height: HEIGHT,
+        num_samples: 16,
+        filter: Filter::Box,
+        max_bounce: 2,
+        tile_size: 16,
+        num_threads: 2,
+        seed: 1,
+        ..RenderParams::default()
+    };
+    render_image(&edge_scene(chromatic_aberration), &params).unwrap().color.as_slice().to_vec()
+}
+
+#[test]
+fn zero_aberration_gives_identical_channels() {
+    let image = render(0.0);
+    assert!(image.iter().any(|c| c.x > 0.5));
+    for c in image {
+        assert!(c.x == c.y && c.y == c.z, "{:?}", c);
+    }
+}
+
+#[test]
+fn aberration_makes_color_fringes_at_the_edges() {
+    let image = render(0.2);
+    // The red image is larger than the blue one, so each side of the outline has a fringe of one color
+    assert!(image.iter().any(|c| c.x > c.z + 0.5));
+    assert!(image.iter().any(|c| c.z > c.x + 0.5));
+}
@@ -0,0 +1,46 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+#[test]
+fn no_clearcoat_leaves_the_base_material_unchanged() {
+    let base = Scatter::Lambert {two_sided: false};
+    let albedo = rgb(0.7, 0.3, 0.2);
+    let coat = Clearcoat {refraction_index: 1.5, roughness: 0.0};
+    let scene_data = SceneData::new(vec![
+        Material::new(base.clone(), Absorb::Albedo(albedo), Emit::None),
+        Material::new(base.clone(), Absorb::Albedo(albedo), Emit::None).with_clearcoat(coat),
+    ]);
+    // A grazing ray, where the coat reflects the most
+    let ray = Ray {
+        origin: vector![-3.0, 1.0, 0.0],
+        direction: vector![3.0, -1.0, 0.0].normalize(),
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let ground = Hittable::Sphere {center: vector![0.0, -1000.0, 0.0], radius: 1000.0, material: MaterialId(0)};
+    let (hit, _) = ground.hit(&ray, &scene_data).unwrap();
+
+    // Without a coat, the output is the one of the base scattering, drawn with the same random numbers
+    let evaluate = |material: usize, seed: u64| {
+        let mut rng = Randomizer::seed_from_u64(seed);
+        scene_data.material_table[material].evaluate(&ray, &hit, &scene_data, &mut rng, 0.0)
+    };
+    let mut num_coat_bounces = 0;
+    for seed in 0..1000 {
+        let output = evaluate(0, seed);
+        let scatter = base.evaluate(&ray, &hit, &scene_data, &mut Randomizer::seed_from_u64(seed));
+        assert_eq!(output.scatter.map(|ray| ray.direction), scatter.map(|ray| ray.direction));
+        assert_eq!(output.absorb, albedo);
+
+        // While the coat reflects some rays untinted
+        if evaluate(1, seed).absorb == WHITE {
+            num_coat_bounces += 1;
+        }
+    }
+    assert!(num_coat_bounces > 50, "{} bounces on the coat", num_coat_bounces);
+}
@@ -0,0 +1,70 @@
+use raytracing2::utility::*;
+
+#[test]
+fn luminance_uses_rec709_weights() {
+    assert!((luminance(&rgb(1.0, 1.0, 1.0)) - 1.0).abs() < 1e-12);
+    assert_eq!(luminance(&rgb(1.0, 0.0, 0.0)), 0.2126);
+    assert_eq!(luminance(&rgb(0.0, 1.0, 0.0)), 0.7152);
+    assert_eq!(luminance(&rgb(0.0, 0.0, 1.0)), 0.0722);
+    // Green looks much brighter than blue of the same intensity
+    assert!(luminance(&rgb(0.0, 0.5, 0.0)) > luminance(&rgb(0.0, 0.0, 1.0)));
+}
+
+#[test]
+fn clamped_color_keeps_its_hue() {
+    let clamped = clamp_color(&rgb(40.0, 20.0, 10.0), 4.0);
+    assert_eq!(clamped, rgb(4.0, 2.0, 1.0));
+    // Below the limit, nothing changes
+    assert_eq!(clamp_color(&rgb(0.5, 3.0, 0.0), 4.0), rgb(0.5, 3.0, 0.0));
+}
+
+#[test]
+fn sanitized_color_has_only_finite_channels() {
+    assert_eq!(sanitize(&rgb(Real::NAN, 0.5, Real::INFINITY)), rgb(0.0, 0.5, 0.0));
+    assert_eq!(sanitize(&rgb(Real::NEG_INFINITY, -0.25, 2.0)), rgb(0.0, -0.25, 2.0));
+    assert_eq!(sanitize(&rgb(0.1, 0.2, 0.3)), rgb(0.1, 0.2, 0.3));
+}
+
+#[test]
+fn bad_samples_do_not_spoil_the_render() {
+    use raytracing2::example_scenes::ExampleScene;
+    use raytracing2::hittable::Hittable;
+    use raytracing2::material::*;
+    use raytracing2::render::*;
+
+    // A ball that emits NaN in front of the sky, across the middle of the image
+    let material_table = vec![Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(Real::NAN, 1.0, 1.0)))];
+    let root = Hittable::Sphere {center: vector![0.0, 0.0, 0.0], radius: 1.0, material: MaterialId(0)};
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_3,
+        focus_distance: 5.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 0.0, 5.0],
+            &vector![0.0, 0.0, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+    let scene_data = SceneData::new(material_table);
+    let background = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
+    let scene = ExampleScene {camera, scene_data, root, background, animation: None};
+    let params = RenderParams {
+        width: 8,
+        height: 8,
+        num_samples: 4,
+        filter: Filter::Box,
+        max_bounce: 2,
+        tile_size: 8,
+        num_threads: 1,
+        seed: 1,
+        ..RenderParams::default()
+    };
+    let image = render_image(&scene, &params).unwrap().color;
+    assert!(image.as_slice().iter().all(|color| color.iter().all(|x| x.is_finite())));
+    // Only the bad channel of the ball is dropped, its other channels and the sky around are kept
+    let (center, corner) = (image.get(4, 4), image.get(0, 0));
+    assert_eq!((center.x, center.y), (0.0, 1.0));
+    assert!(corner.x > 0.0);
+}
@@ -0,0 +1,15 @@
+//! Helpers shared by the integration tests, each test file includes this module with `mod common;`
+
+use raytracing2::utility::*;
+
+/// A ray from the origin along the direction, normalized, that does not stop before infinity
+pub fn ray(origin: Rvec3, direction: Rvec3) -> Ray {
+    Ray {
+        origin,
+        direction: direction.normalize(),
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    }
+}
@@ -0,0 +1,81 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+#[test]
+fn schlick_goes_from_r0_to_1() {
+    let r0 = fresnel_r0(1.5);
+    assert!((r0 - 0.04).abs() < 1e-12);
+    // The same interface seen from inside the glass
+    assert!((fresnel_r0(1.0 / 1.5) - r0).abs() < 1e-12);
+
+    assert_eq!(fresnel_schlick(1.0, r0), r0);
+    assert!((fresnel_schlick(1e-6, r0) - 1.0).abs() < 1e-5);
+    assert_eq!(fresnel_schlick(0.0, r0), 1.0);
+    // Increasing toward the grazing angles
+    let cosines = [1.0, 0.8, 0.5, 0.2, 0.0];
+    assert!(cosines.windows(2).all(|w| fresnel_schlick(w[0], r0) < fresnel_schlick(w[1], r0)));
+}
+
+#[test]
+fn colored_schlick_works_on_each_channel() {
+    let f0 = rgb(0.95, 0.64, 0.54);
+    assert_eq!(fresnel_schlick_rgb(1.0, f0), f0);
+    assert_eq!(fresnel_schlick_rgb(0.0, f0), rgb(1.0, 1.0, 1.0));
+    let color = fresnel_schlick_rgb(0.3, f0);
+    assert_eq!(color, rgb(fresnel_schlick(0.3, 0.95), fresnel_schlick(0.3, 0.64), fresnel_schlick(0.3, 0.54)));
+}
+
+#[test]
+fn gold_gets_whiter_at_grazing_angles() {
+    let (eta, k) = GOLD;
+    // Reference values from the Fresnel equations with complex numbers
+    let normal = fresnel_conductor(1.0, &eta, &k);
+    assert!((normal - rgb(0.96666, 0.80219, 0.32405)).amax() < 1e-4, "{:?}", normal);
+    let grazing = fresnel_conductor((85.0 as Real).to_radians().cos(), &eta, &k);
+    assert!((grazing - rgb(0.97607, 0.92865, 0.74680)).amax() < 1e-4, "{:?}", grazing);
+    assert!((fresnel_conductor(0.0, &eta, &k) - rgb(1.0, 1.0, 1.0)).amax() < 1e-12);
+
+    // A metal with no absorption is a dielectric
+    let glass = Color::repeat(1.5);
+    let reflectance = fresnel_conductor(1.0, &glass, &Color::zeros());
+    assert!((reflectance - Color::repeat(fresnel_r0(1.5))).amax() < 1e-12);
+}
+
+#[test]
+fn thin_glass_reflects_r0_at_normal_incidence_and_lets_the_rest_through() {
+    let scene_data = SceneData::new(vec![
+        Material::new(Scatter::ThinDielectric {refraction_index: 1.5}, Absorb::BlackBody, Emit::None)
+    ]);
+    // A sphere so big that it is a flat pane where the ray hits it
+    let pane = Hittable::Sphere {center: vector![0.0, 0.0, -1000.0], radius: 999.0, material: MaterialId(0)};
+    let direction = vector![0.0, 0.0, -1.0];
+    let ray = Ray {
+        origin: Rvec3::zeros(),
+        direction,
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let (hit, material) = pane.hit(&ray, &scene_data).unwrap();
+
+    let mut rng = Randomizer::seed_from_u64(5);
+    let num_samples = 100_000;
+    let mut num_reflected = 0;
+    for _ in 0..num_samples {
+        let output = scene_data.material(material).evaluate(&ray, &hit, &scene_data, &mut rng, 0.0);
+        let scattered = output.scatter.unwrap().direction;
+        // The transmitted ray is not bent
+        if scattered == direction {
+            assert!(scattered.dot(&hit.normal) < 0.0);
+        } else {
+            assert!((scattered + direction).norm() < 1e-12);
+            num_reflected += 1;
+        }
+    }
+    let reflectance = num_reflected as Real / num_samples as Real;
+    assert!((reflectance - fresnel_r0(1.5)).abs() < 0.003, "reflectance {}", reflectance);
+}
@@ -0,0 +1,100 @@
+use raytracing2::image::hdr;
+use raytracing2::material::Emit;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::texture::*;
+use raytracing2::utility::*;
+
+/// Write the bytes to a file of the temporary directory and load it
+fn load(name: &str, bytes: &[u8]) -> Result<raytracing2::image::Array2d<Color>, Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("raytracing2_{}_{}.hdr", name, std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    let image = hdr::load(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    image
+}
+
+/// The extra lines of the header must each end with a new line
+fn header(extra: &str, resolution: &str) -> Vec<u8> {
+    format!("#?RADIANCE\n# Made by hand\n{}FORMAT=32-bit_rle_rgbe\n\n{}\n", extra, resolution).into_bytes()
+}
+
+#[test]
+fn flat_pixels_decode_to_their_radiance() {
+    let mut bytes = header("", "-Y 2 +X 2");
+    bytes.extend([
+        128, 64, 32, 129, // 2^(129 - 136) = 1/128
+        200, 100, 50, 140, // 2^4, much brighter than white like the sun
+        0, 0, 0, 0,
+        255, 255, 255, 0, // A zero exponent is black, whatever the mantissas
+    ]);
+    let image = load("flat", &bytes).unwrap();
+    assert_eq!((image.width(), image.height()), (2, 2));
+    assert_eq!(*image.get(0, 0), rgb(1.0, 0.5, 0.25));
+    assert_eq!(*image.get(1, 0), rgb(3200.0, 1600.0, 800.0));
+    assert_eq!(*image.get(0, 1), rgb(0.0, 0.0, 0.0));
+    assert_eq!(*image.get(1, 1), rgb(0.0, 0.0, 0.0));
+}
+
+#[test]
+fn run_length_encoded_scanlines_decode_like_flat_ones() {
+    // Two scanlines of 8 pixels, encoded with runs and literals
+    let mut encoded = header("", "-Y 2 +X 8");
+    let mut flat = header("", "-Y 2 +X 8");
+    for row in 0..2u8 {
+        let pixels = (0..8u8).map(|x| [10 * x + row, 128, if x < 5 {7} else {9}, 130]).collect::<Vec<_>>();
+        pixels.iter().for_each(|pixel| flat.extend(pixel));
+        encoded.extend([2, 2, 0, 8]);
+        encoded.push(8); // 8 literal red values
+        encoded.extend(pixels.iter().map(|pixel| pixel[0]));
+        encoded.extend([128 + 8, 128]); // A run of 8 greens
+        encoded.extend([128 + 5, 7, 3, 9, 9, 9]); // A run of 5 blues, then 3 literals
+        encoded.extend([128 + 8, 130]);
+    }
+    let encoded = load("rle", &encoded).unwrap();
+    let flat = load("rle_flat", &flat).unwrap();
+    assert_eq!(encoded.as_slice(), flat.as_slice());
+    // 2^(130 - 136) = 1/64
+    assert_eq!(*encoded.get(3, 1), rgb(31.0, 128.0, 7.0) / 64.0);
+    assert_eq!(*encoded.get(7, 0), rgb(70.0, 128.0, 9.0) / 64.0);
+}
+
+#[test]
+fn exposure_is_undone_and_rows_can_go_up() {
+    let mut bytes = header("EXPOSURE=2\nEXPOSURE= 4\n", "+Y 2 +X 1");
+    bytes.extend([128, 128, 128, 137, 64, 64, 64, 137]);
+    let image = load("exposure", &bytes).unwrap();
+    // The first row of the file is the bottom of the image. 2^(137 - 136) = 2, divided by the exposure of 8.
+    assert_eq!(*image.get(0, 1), rgb(32.0, 32.0, 32.0));
+    assert_eq!(*image.get(0, 0), rgb(16.0, 16.0, 16.0));
+}
+
+#[test]
+fn other_files_are_rejected() {
+    assert!(load("not_hdr", b"P6\n1 1\n255\n\0\0\0").is_err());
+    assert!(load("bad_format", &header("FORMAT=32-bit_rle_xyze\n", "-Y 1 +X 1")).is_err());
+    assert!(load("truncated", &header("", "-Y 2 +X 2")).is_err());
+}
+
+#[test]
+fn hdr_sky_keeps_its_bright_texels() {
+    // The sky in front of the default view is bright, and the one behind is dim
+    let mut bytes = header("", "-Y 1 +X 2");
+    bytes.extend([200, 150, 100, 144, 128, 128, 128, 128]);
+    let image = load("sky", &bytes).unwrap();
+    let scene_data = SceneData {
+        texture_table: vec![Texture::HdrImage {image, wrap_u: WrapMode::Repeat, wrap_v: WrapMode::Clamp}],
+        ..SceneData::empty()
+    };
+    let sky = Emit::SkySphere {texture: TextureId(0), yaw: 0.0, pitch: 0.0};
+    let mut rng = Randomizer::seed_from_u64(0);
+    let look = |direction: Rvec3, rng: &mut Randomizer| {
+        let ray = Ray {
+            origin: Rvec3::zeros(), direction, t_min: 0.0, t_max: INFINITY, wavelength: None, differentials: None
+        };
+        sky.evaluate(&ray, &Hit::at_infinity(&direction), &scene_data, rng)
+    };
+    // 2^(144 - 136) = 256
+    assert_eq!(look(vector![0.0, 0.0, 1.0], &mut rng), rgb(200.0, 150.0, 100.0) * 256.0);
+    assert_eq!(look(vector![0.0, 0.0, -1.0], &mut rng), rgb(0.5, 0.5, 0.5));
+}
@@ -0,0 +1,516 @@
+use raytracing2::bvh::{Bvh, TriangleBvh};
+use raytracing2::hittable::*;
+use raytracing2::material::MaterialId;
+use raytracing2::mesh::*;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+mod common;
+use common::ray;
+
+fn unit_sphere() -> Hittable {
+    Hittable::Sphere {center: vector![0.0, 1.0, 0.0], radius: 1.0, material: MaterialId(3)}
+}
+
+#[test]
+fn flipped_sphere_returns_inverted_normals() {
+    let scene_data = SceneData::empty();
+    let sphere = unit_sphere();
+    let flipped = Hittable::FlipNormals(Box::new(unit_sphere()));
+    // From outside, then from the center
+    let rays = [
+        ray(vector![0.3, 1.2, 5.0], vector![0.0, 0.0, -1.0]),
+        ray(vector![0.0, 1.0, 0.0], vector![1.0, 2.0, 3.0]),
+    ];
+    for ray in rays {
+        let (hit, material) = sphere.hit(&ray, &scene_data).unwrap();
+        let (flipped_hit, flipped_material) = flipped.hit(&ray, &scene_data).unwrap();
+        assert_eq!(flipped_hit.normal, -hit.normal);
+        assert_eq!(flipped_hit.t, hit.t);
+        assert_eq!(flipped_hit.position, hit.position);
+        assert_eq!(flipped_material.to_index(), material.to_index());
+    }
+}
+
+#[test]
+fn flipped_sphere_faces_inward() {
+    let scene_data = SceneData::empty();
+    let flipped = Hittable::FlipNormals(Box::new(unit_sphere()));
+    let (hit, _) = flipped.hit(&ray(vector![0.0, 1.0, 0.0], vector![0.0, -1.0, 0.0]), &scene_data).unwrap();
+    assert!((hit.normal - vector![0.0, 1.0, 0.0]).norm() < 1e-12);
+}
+
+#[test]
+fn flipped_sphere_keeps_its_bounding_box() {
+    let scene_data = SceneData::empty();
+    let aabb = unit_sphere().bounding_box(&scene_data);
+    let flipped_aabb = Hittable::FlipNormals(Box::new(unit_sphere())).bounding_box(&scene_data);
+    assert_eq!(flipped_aabb.min, aabb.min);
+    assert_eq!(flipped_aabb.max, aabb.max);
+}
+
+#[test]
+fn flip_normals_misses_when_the_child_misses() {
+    let scene_data = SceneData::empty();
+    let flipped = Hittable::FlipNormals(Box::new(unit_sphere()));
+    assert!(flipped.hit(&ray(vector![5.0, 1.0, 5.0], vector![0.0, 0.0, -1.0]), &scene_data).is_none());
+}
+
+#[test]
+fn translated_sphere_is_hit_at_the_moved_position() {
+    let scene_data = SceneData::empty();
+    let sphere = Hittable::Sphere {center: vector![0.0, 0.0, 0.0], radius: 1.0, material: MaterialId(0)};
+    let translated = Hittable::Translate {offset: vector![0.0, 5.0, 0.0], child: Box::new(sphere)};
+
+    // Straight down onto the top of the moved sphere
+    let (hit, _) = translated.hit(&ray(vector![0.0, 10.0, 0.0], vector![0.0, -1.0, 0.0]), &scene_data).unwrap();
+    assert!((hit.position - vector![0.0, 6.0, 0.0]).norm() < 1e-12);
+    assert!((hit.normal - vector![0.0, 1.0, 0.0]).norm() < 1e-12);
+    assert!((hit.t - 4.0).abs() < 1e-12);
+
+    // From the side, at an angle
+    let origin = vector![-4.0, 5.5, 0.0];
+    let (hit, _) = translated.hit(&ray(origin, vector![1.0, 0.0, 0.0]), &scene_data).unwrap();
+    let expected = vector![-(0.75 as Real).sqrt(), 5.5, 0.0];
+    assert!((hit.position - expected).norm() < 1e-12);
+    assert!((hit.normal - (expected - vector![0.0, 5.0, 0.0])).norm() < 1e-12);
+
+    // Where the sphere used to be, there is nothing
+    assert!(translated.hit(&ray(vector![0.0, 0.0, 5.0], vector![0.0, 0.0, -1.0]), &scene_data).is_none());
+}
+
+#[test]
+fn translated_sphere_moves_its_bounding_box() {
+    let scene_data = SceneData::empty();
+    let translated = Hittable::Translate {offset: vector![0.0, 5.0, 0.0], child: Box::new(unit_sphere())};
+    let aabb = translated.bounding_box(&scene_data);
+    assert_eq!(aabb.min, vector![-1.0, 5.0, -1.0]);
+    assert_eq!(aabb.max, vector![1.0, 7.0, 1.0]);
+}
+
+/// The faces of an axis-aligned box, each made of two triangles, in the order +X, -X, +Y, -Y, +Z, -Z.
+/// The u coordinate of a face is its index, to tell which one was hit.
+fn box_mesh(half_size: Rvec3) -> Mesh {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for face in 0..6 {
+        let axis = face / 2;
+        let sign = if face % 2 == 0 {1.0} else {-1.0};
+        let mut normal = Rvec3::zeros();
+        normal[axis] = sign;
+        // Two axes along the face, ordered so that the triangles wind counterclockwise seen from outside
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let first = vertices.len() as u32;
+        for (sa, sb) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let mut position = Rvec3::zeros();
+            position[axis] = sign * half_size[axis];
+            position[a] = sa * sign * half_size[a];
+            position[b] = sb * half_size[b];
+            vertices.push(Vertex {position, normal, uv: vector![face as Real, 0.0], color: WHITE});
+        }
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+    Mesh::new(vertices, indices, MaterialId(0))
+}
+
+#[test]
+fn rotating_a_box_by_90_degrees_swaps_its_faces() {
+    // Long along X, short along Z
+    let scene_data = SceneData {mesh_table: vec![box_mesh(vector![2.0, 0.5, 0.5])], ..SceneData::empty()};
+    let the_box = || Box::new(Hittable::TriangleBvh(TriangleBvh::from_mesh(MeshId(0), &scene_data)));
+    let plain = the_box();
+    let rotated = Hittable::RotateY {angle: FRAC_PI_2, child: the_box()};
+    let from_front = ray(vector![0.1, 0.2, 10.0], vector![0.0, 0.0, -1.0]);
+    let from_right = ray(vector![10.0, 0.2, 0.1], vector![-1.0, 0.0, 0.0]);
+    let face = |hit: &Hit| hit.uv.x.round() as usize;
+
+    // Unrotated, the +Z face is in front and the +X face on the right, at the end of the long side
+    let (hit, _) = plain.hit(&from_front, &scene_data).unwrap();
+    assert_eq!(face(&hit), 4);
+    assert!((hit.position.z - 0.5).abs() < 1e-9);
+    let (hit, _) = plain.hit(&from_right, &scene_data).unwrap();
+    assert_eq!(face(&hit), 0);
+    assert!((hit.position.x - 2.0).abs() < 1e-9);
+
+    // Turned counterclockwise seen from above, +X goes to -Z and +Z goes to +X:
+    // the end of the long side is now in front, and the former front face is on the right
+    let (hit, _) = rotated.hit(&from_front, &scene_data).unwrap();
+    assert_eq!(face(&hit), 1);
+    assert!((hit.position.z - 2.0).abs() < 1e-9);
+    assert!((hit.normal - vector![0.0, 0.0, 1.0]).norm() < 1e-9);
+    let (hit, _) = rotated.hit(&from_right, &scene_data).unwrap();
+    assert_eq!(face(&hit), 4);
+    assert!((hit.position.x - 0.5).abs() < 1e-9);
+    assert!((hit.normal - vector![1.0, 0.0, 0.0]).norm() < 1e-9);
+}
+
+#[test]
+fn rotated_box_has_a_rotated_bounding_box() {
+    let scene_data = SceneData {mesh_table: vec![box_mesh(vector![2.0, 0.5, 0.5])], ..SceneData::empty()};
+    let child = Box::new(Hittable::TriangleBvh(TriangleBvh::from_mesh(MeshId(0), &scene_data)));
+    let aabb = Hittable::RotateY {angle: FRAC_PI_2, child}.bounding_box(&scene_data);
+    assert!((aabb.min - vector![-0.5, -0.5, -2.0]).norm() < 1e-9);
+    assert!((aabb.max - vector![0.5, 0.5, 2.0]).norm() < 1e-9);
+}
+
+#[test]
+fn primitives_are_counted_through_lists_wrappers_and_bvhs() {
+    let scene_data = SceneData {mesh_table: vec![box_mesh(vector![1.0, 1.0, 1.0])], ..SceneData::empty()};
+    let the_box = TriangleBvh::from_mesh(MeshId(0), &scene_data);
+    let capsule = Hittable::Capsule {
+        a: vector![0.0, 0.0, 0.0], b: vector![0.0, 1.0, 0.0], radius: 0.5, material: MaterialId(0)
+    };
+    let mut root = Hittable::List(vec![
+        unit_sphere(),
+        Hittable::FlipNormals(Box::new(unit_sphere())),
+        Hittable::Difference {a: Box::new(unit_sphere()), b: Box::new(capsule)},
+        Hittable::Translate {offset: vector![5.0, 0.0, 0.0], child: Box::new(Hittable::TriangleBvh(the_box))},
+        Hittable::List(Vec::new()),
+    ]);
+    assert_eq!(root.count_primitives(), 1 + 1 + 2 + 12);
+
+    // Building the Bvhs moves the primitives around but does not lose any
+    let mut spheres = vec![root.clone(); 9];
+    spheres.push(unit_sphere());
+    root = Hittable::List(spheres);
+    root.build_acceleration(&scene_data);
+    assert!(matches!(root, Hittable::Bvh(_)));
+    assert_eq!(root.count_primitives(), 9 * 16 + 1);
+}
+
+#[test]
+fn bvh_depth_and_node_count() {
+    let scene_data = SceneData {mesh_table: vec![box_mesh(vector![1.0, 1.0, 1.0])], ..SceneData::empty()};
+    // The 12 triangles are split at the median: 12, 6, 3, then 2 and 1, then the leaves
+    let the_box = TriangleBvh::from_mesh(MeshId(0), &scene_data);
+    assert_eq!(the_box.count_primitives(), 12);
+    assert_eq!(the_box.node_count(), 2 * 12 - 1);
+    assert_eq!(the_box.max_depth(), 5);
+
+    // A balanced tree over 8 leaves, and a lone leaf
+    let spheres = (0..8)
+        .map(|i| Hittable::Sphere {center: vector![i as Real, 0.0, 0.0], radius: 0.4, material: MaterialId(0)})
+        .collect::<Vec<_>>();
+    let bvh = Bvh::new(spheres, &scene_data);
+    assert_eq!(bvh.node_count(), 15);
+    assert_eq!(bvh.max_depth(), 4);
+    let bvh = Bvh::new(vec![unit_sphere()], &scene_data);
+    assert_eq!((bvh.node_count(), bvh.max_depth(), bvh.count_primitives()), (1, 1, 1));
+}
+
+#[test]
+fn hit_any_sees_only_the_blockers_inside_the_segment() {
+    let scene_data = SceneData::empty();
+    // The unit sphere is between 4 and 6 along the ray
+    let mut shadow_ray = ray(vector![0.0, 1.0, 5.0], vector![0.0, 0.0, -1.0]);
+    shadow_ray.t_max = 10.0;
+    assert!(unit_sphere().hit_any(&shadow_ray, &scene_data));
+    shadow_ray.t_max = 3.5;
+    assert!(!unit_sphere().hit_any(&shadow_ray, &scene_data));
+
+    // The same through a Bvh and the wrappers
+    let spheres = (0..20).map(|k| Hittable::Sphere {
+        center: vector![3.0 * k as Real, 1.0, 0.0], radius: 1.0, material: MaterialId(0)
+    });
+    let moved = Hittable::RotateY {
+        angle: 0.0,
+        child: Box::new(Hittable::Translate {offset: vector![-30.0, 0.0, 0.0], child: Box::new(unit_sphere())}),
+    };
+    let bvh = Hittable::List(spheres.chain([moved]).collect()).into_bvh(&scene_data);
+    for (x, blocked) in [(0.0, true), (-30.0, true), (1.5, false), (-15.0, false)] {
+        let mut shadow_ray = ray(vector![x, 1.0, 5.0], vector![0.0, 0.0, -1.0]);
+        shadow_ray.t_max = 10.0;
+        assert_eq!(bvh.hit_any(&shadow_ray, &scene_data), blocked, "x = {}", x);
+        assert_eq!(bvh.hit(&shadow_ray, &scene_data).is_some(), blocked, "x = {}", x);
+        shadow_ray.t_max = 3.5;
+        assert!(!bvh.hit_any(&shadow_ray, &scene_data), "x = {}", x);
+    }
+}
+
+#[test]
+fn visitor_reaches_every_node_of_a_nested_scene() {
+    let scene_data = SceneData::empty();
+    let bvh = Bvh::new(vec![unit_sphere(), Hittable::FlipNormals(Box::new(unit_sphere()))], &scene_data);
+    let mut root = Hittable::List(vec![
+        Hittable::Translate {offset: vector![5.0, 0.0, 0.0], child: Box::new(Hittable::RotateY {
+            angle: 1.0, child: Box::new(Hittable::List(vec![unit_sphere(), unit_sphere()])),
+        })},
+        Hittable::Difference {a: Box::new(unit_sphere()), b: Box::new(unit_sphere())},
+        Hittable::Bvh(bvh),
+    ]);
+    let spheres = |root: &Hittable, material| {
+        let (mut nodes, mut spheres) = (0, 0);
+        root.visit(&mut |x| {
+            nodes += 1;
+            if let Hittable::Sphere {material: MaterialId(m), ..} = x {
+                spheres += (*m == material) as usize;
+            }
+        });
+        (nodes, spheres)
+    };
+    // The root, 2 wrappers, a list and its 2 spheres, a difference and its 2 spheres, a Bvh with a sphere and a
+    // flipped one
+    assert_eq!(spheres(&root, 3), (13, 6));
+    assert_eq!(root.count_primitives(), 6);
+
+    // The mutable visit changes everything but the leaves of the Bvh
+    root.visit_mut(&mut |x| if let Hittable::Sphere {material, ..} = x {
+        *material = MaterialId(5);
+    });
+    assert_eq!(spheres(&root, 5), (13, 4));
+    assert_eq!(spheres(&root, 3), (13, 2));
+}
+
+#[test]
+fn context_visitor_gives_the_frame_and_the_differences_above_each_node() {
+    let root = Hittable::List(vec![
+        Hittable::Translate {offset: vector![5.0, 0.0, 0.0], child: Box::new(Hittable::RotateY {
+            angle: FRAC_PI_2, child: Box::new(Hittable::Sphere {
+                center: vector![1.0, 0.0, 0.0], radius: 1.0, material: MaterialId(1),
+            }),
+        })},
+        Hittable::Difference {a: Box::new(unit_sphere()), b: Box::new(unit_sphere())},
+    ]);
+    let mut centers = Vec::new();
+    root.visit_with_context(&mut |x, context| if let Hittable::Sphere {center, material, ..} = x {
+        centers.push((material.to_index(), context.to_world.transform_point(center), context.in_difference));
+    });
+    assert_eq!(centers.len(), 3);
+    // Turned a quarter around the vertical, then moved
+    let (material, center, in_difference) = centers[0];
+    assert_eq!((material, in_difference), (1, false));
+    assert!((center - vector![5.0, 0.0, -1.0]).norm() < 1e-12);
+    for &(material, center, in_difference) in &centers[1..] {
+        assert_eq!((material, in_difference), (3, true));
+        assert!((center - vector![0.0, 1.0, 0.0]).norm() < 1e-12);
+    }
+}
+
+fn displaced_sphere_scene_data(height: Color) -> SceneData {
+    use raytracing2::texture::Texture;
+    SceneData {texture_table: vec![Texture::Solid(height)], ..SceneData::empty()}
+}
+
+#[test]
+fn displaced_sphere_without_amplitude_is_a_sphere() {
+    use raytracing2::texture::TextureId;
+    let scene_data = displaced_sphere_scene_data(rgb(0.3, 0.9, 0.6));
+    let center = vector![0.5, 1.0, -0.2];
+    let sphere = Hittable::Sphere {center, radius: 1.0, material: MaterialId(3)};
+    let displaced = Hittable::DisplacedSphere {
+        center, radius: 1.0, material: MaterialId(3), height: TextureId(0), amplitude: 0.0
+    };
+    let rays = [
+        ray(vector![0.0, 1.0, -5.0], vector![0.1, 0.0, 1.0]),
+        ray(vector![3.0, 2.0, 2.0], center - vector![3.0, 2.0, 2.0] + vector![0.3, 0.2, 0.0]),
+        ray(vector![0.0, 0.0, 4.0], vector![0.1, 0.3, -1.0]),
+        ray(center, vector![1.0, -2.0, 0.5]), // From the inside
+    ];
+    for ray in rays.iter() {
+        let (expected, _) = sphere.hit(ray, &scene_data).unwrap();
+        let (hit, material) = displaced.hit(ray, &scene_data).unwrap();
+        assert_eq!(material.to_index(), 3);
+        assert!((hit.t - expected.t).abs() < 1e-5, "t {} instead of {}", hit.t, expected.t);
+        assert!((hit.position - expected.position).norm() < 1e-5);
+        assert!((hit.normal - expected.normal).norm() < 1e-5);
+        assert!((hit.uv - expected.uv).norm() < 1e-5);
+    }
+    assert!(displaced.hit(&ray(vector![0.0, 1.0, -5.0], vector![0.0, 1.0, 0.0]), &scene_data).is_none());
+}
+
+#[test]
+fn displaced_sphere_grows_with_its_height() {
+    use raytracing2::texture::TextureId;
+    // A uniform height of 1 lifts the whole surface by the amplitude
+    let scene_data = displaced_sphere_scene_data(rgb(1.0, 1.0, 1.0));
+    let displaced = Hittable::DisplacedSphere {
+        center: Rvec3::zeros(), radius: 1.0, material: MaterialId(0), height: TextureId(0), amplitude: 0.5
+    };
+    let (hit, _) = displaced.hit(&ray(vector![0.0, 0.0, -5.0], vector![0.0, 0.0, 1.0]), &scene_data).unwrap();
+    assert!((hit.t - 3.5).abs() < 1e-5);
+    assert!((hit.normal - vector![0.0, 0.0, -1.0]).norm() < 1e-5);
+    // Grazing the plain sphere, but well inside the displaced one
+    assert!(displaced.hit(&ray(vector![1.2, 0.0, -5.0], vector![0.0, 0.0, 1.0]), &scene_data).is_some());
+    assert!(displaced.hit(&ray(vector![1.6, 0.0, -5.0], vector![0.0, 0.0, 1.0]), &scene_data).is_none());
+    let bounds = displaced.bounding_box(&scene_data);
+    assert!((bounds.max - vector![1.5, 1.5, 1.5]).norm() < 1e-9);
+}
+
+#[test]
+fn half_sphere_has_no_upper_cap() {
+    let scene_data = SceneData::empty();
+    let bowl = Hittable::SphereSection {
+        center: vector![0.0, 1.0, 0.0],
+        radius: 1.0,
+        phi_range: (0.0, TAU),
+        theta_range: (FRAC_PI_2, PI),
+        material: MaterialId(3),
+    };
+    // Both crossings are on the removed cap
+    assert!(bowl.hit(&ray(vector![-5.0, 1.5, 0.0], vector![1.0, 0.0, 0.0]), &scene_data).is_none());
+    assert!(!bowl.hit_any(&ray(vector![-5.0, 1.5, 0.0], vector![1.0, 0.0, 0.0]), &scene_data));
+    // Through the opening, onto the inside of the bowl
+    let (hit, _) = bowl.hit(&ray(vector![0.0, 5.0, 0.0], vector![0.0, -1.0, 0.0]), &scene_data).unwrap();
+    assert!((hit.t - 5.0).abs() < 1e-9);
+    assert!((hit.normal - vector![0.0, -1.0, 0.0]).norm() < 1e-9);
+    // The lower half is kept
+    let (hit, _) = bowl.hit(&ray(vector![-5.0, 0.5, 0.0], vector![1.0, 0.0, 0.0]), &scene_data).unwrap();
+    assert!(hit.position.y < 1.0);
+    assert!(bowl.bounding_box(&scene_data).max.y >= 2.0);
+}
+
+#[test]
+fn sphere_section_is_cut_around_the_y_axis() {
+    let scene_data = SceneData::empty();
+    // Only the quarter with phi from 0 to π/2 is left
+    let quarter = Hittable::SphereSection {
+        center: Rvec3::zeros(),
+        radius: 1.0,
+        phi_range: (0.0, FRAC_PI_2),
+        theta_range: (0.0, PI),
+        material: MaterialId(3),
+    };
+    // That is the quarter of the sphere with x < 0 and z > 0
+    let hit_t = |x: Real, z: Real| {
+        let direction = vector![x, 0.0, z].normalize();
+        quarter.hit(&ray(5.0 * direction, -direction), &scene_data).map(|(hit, _)| hit.t)
+    };
+    assert!((hit_t(-1.0, 0.1).unwrap() - 4.0).abs() < 1e-9);
+    // Through the center, onto the inside of the quarter
+    assert!((hit_t(1.0, -0.1).unwrap() - 6.0).abs() < 1e-9);
+    assert!(hit_t(1.0, 0.1).is_none());
+    assert!(hit_t(-1.0, -0.1).is_none());
+}
+
+#[test]
+fn triangle_hit_interpolates_and_follows_the_updated_vertices() {
+    let normal = vector![0.0, 0.0, 1.0];
+    let vertices = vec![
+        Vertex {position: vector![0.0, 0.0, 0.0], normal, uv: vector![0.0, 0.0], color: WHITE},
+        Vertex {position: vector![4.0, 0.0, 0.0], normal, uv: vector![1.0, 0.0], color: WHITE},
+        Vertex {position: vector![0.0, 4.0, 0.0], normal, uv: vector![0.0, 1.0], color: WHITE},
+    ];
+    let mut scene_data = SceneData {
+        mesh_table: vec![Mesh::new(vertices, vec![0, 1, 2], MaterialId(0))],
+        ..SceneData::empty()
+    };
+    let triangle = Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(0)};
+
+    // The uvs are interpolated with the barycentric coordinates of the hit
+    let (hit, _) = triangle.hit(&ray(vector![1.0, 2.0, 3.0], vector![0.0, 0.0, -1.0]), &scene_data).unwrap();
+    assert!((hit.t - 3.0).abs() < 1e-12);
+    assert!((hit.uv - vector![0.25, 0.5]).norm() < 1e-12);
+    assert!(triangle.hit(&ray(vector![3.0, 3.0, 3.0], vector![0.0, 0.0, -1.0]), &scene_data).is_none());
+
+    // The edges follow the vertices
+    scene_data.mesh_table[0].update_vertices(|vertices| {
+        for vertex in vertices {
+            vertex.position += vector![0.0, 0.0, -1.0];
+        }
+    });
+    let (hit, _) = triangle.hit(&ray(vector![1.0, 2.0, 3.0], vector![0.0, 0.0, -1.0]), &scene_data).unwrap();
+    assert!((hit.t - 4.0).abs() < 1e-12);
+    assert!((hit.position - vector![1.0, 2.0, -1.0]).norm() < 1e-12);
+}
+
+#[test]
+fn capsule_body_and_caps_have_outward_normals() {
+    let scene_data = SceneData::empty();
+    let capsule = Hittable::Capsule {
+        a: vector![0.0, 0.0, 0.0], b: vector![0.0, 2.0, 0.0], radius: 0.5, material: MaterialId(0)
+    };
+
+    // Across the body, the normal is perpendicular to the axis
+    let (hit, _) = capsule.hit(&ray(vector![3.0, 1.2, 0.0], vector![-1.0, 0.0, 0.0]), &scene_data).unwrap();
+    assert!((hit.t - 2.5).abs() < 1e-12);
+    assert!((hit.normal - vector![1.0, 0.0, 0.0]).norm() < 1e-12);
+
+    // Along the axis, the ray enters through the top cap
+    let (hit, _) = capsule.hit(&ray(vector![0.0, 5.0, 0.0], vector![0.0, -1.0, 0.0]), &scene_data).unwrap();
+    assert!((hit.t - 2.5).abs() < 1e-12);
+    assert!((hit.normal - vector![0.0, 1.0, 0.0]).norm() < 1e-12);
+
+    // Slanted on the bottom cap, the normal points away from the end point
+    let origin = vector![0.0, -0.3, 3.0];
+    let (hit, _) = capsule.hit(&ray(origin, vector![0.0, 0.0, -1.0]), &scene_data).unwrap();
+    assert!((hit.position - vector![0.0, -0.3, 0.4]).norm() < 1e-12);
+    assert!((hit.normal - vector![0.0, -0.6, 0.8]).norm() < 1e-12);
+
+    // Just past the end of the cap
+    assert!(capsule.hit(&ray(vector![0.0, 2.6, 3.0], vector![0.0, 0.0, -1.0]), &scene_data).is_none());
+}
+
+#[test]
+fn list_and_its_bvh_give_the_same_hits() {
+    let scene_data = SceneData::empty();
+    let mut rng = Randomizer::seed_from_u64(7);
+    let spheres = (0..100).map(|k| Hittable::Sphere {
+        center: vector![rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0)],
+        radius: rng.gen_range(0.1..1.5),
+        material: MaterialId(k),
+    }).collect::<Vec<_>>();
+    let list = Hittable::List(spheres);
+    let bvh = list.clone().into_bvh(&scene_data);
+    assert!(matches!(bvh, Hittable::Bvh(_)));
+
+    // Rays from a box around the spheres towards random points inside it
+    let mut num_hits = 0;
+    for _ in 0..1000 {
+        let origin = vector![rng.gen_range(-15.0..15.0), rng.gen_range(-15.0..15.0), rng.gen_range(-15.0..15.0)];
+        let target = vector![rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0)];
+        let ray = ray(origin, target - origin);
+        match (list.hit(&ray, &scene_data), bvh.hit(&ray, &scene_data)) {
+            (None, None) => (),
+            (Some((list_hit, list_material)), Some((bvh_hit, bvh_material))) => {
+                assert_eq!(list_hit.t, bvh_hit.t);
+                assert_eq!(list_hit.normal, bvh_hit.normal);
+                assert_eq!(list_material.to_index(), bvh_material.to_index());
+                num_hits += 1;
+            }
+            _ => panic!("The list and the bvh disagree on {:?}", ray),
+        }
+    }
+    assert!(num_hits > 100);
+}
+
+#[test]
+fn difference_lets_the_rays_through_the_carved_region() {
+    let scene_data = SceneData::empty();
+    // A unit ball with a bite taken out of its +X side
+    let bitten = Hittable::Difference {
+        a: Box::new(Hittable::Sphere {center: vector![0.0, 0.0, 0.0], radius: 1.0, material: MaterialId(0)}),
+        b: Box::new(Hittable::Sphere {center: vector![1.0, 0.0, 0.0], radius: 0.8, material: MaterialId(1)}),
+    };
+
+    // Through the bite, the ray would only cross the ball where it is carved away
+    assert!(bitten.hit(&ray(vector![0.9, 0.0, 5.0], vector![0.0, 0.0, -1.0]), &scene_data).is_none());
+
+    // Into the bite, the ray hits the inside of the carving sphere, facing the ray
+    let (hit, material) = bitten.hit(&ray(vector![5.0, 0.0, 0.0], vector![-1.0, 0.0, 0.0]), &scene_data).unwrap();
+    assert!((hit.position - vector![0.2, 0.0, 0.0]).norm() < 1e-12);
+    assert!((hit.normal - vector![1.0, 0.0, 0.0]).norm() < 1e-12);
+    assert_eq!(material.to_index(), 1);
+
+    // Away from the bite, the ball is intact
+    let (hit, _) = bitten.hit(&ray(vector![-5.0, 0.0, 0.0], vector![1.0, 0.0, 0.0]), &scene_data).unwrap();
+    assert!((hit.position - vector![-1.0, 0.0, 0.0]).norm() < 1e-12);
+}
+
+#[test]
+fn sphere_tangent_is_perpendicular_to_the_normal() {
+    let scene_data = SceneData::empty();
+    let mut rng = Randomizer::seed_from_u64(13);
+    for _ in 0..1000 {
+        let target = vector![rng.gen_range(-0.9..0.9), rng.gen_range(0.1..1.9), rng.gen_range(-0.9..0.9)];
+        let origin = vector![rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0)];
+        let Some((hit, _)) = unit_sphere().hit(&ray(origin, target - origin), &scene_data) else { continue };
+        assert!((hit.tangent.norm() - 1.0).abs() < 1e-9);
+        assert!(hit.tangent.dot(&hit.normal).abs() < 1e-9, "{:?} against {:?}", hit.tangent, hit.normal);
+
+        // Horizontal, always turning the same way around the axis of the sphere
+        let around_y = vector![0.0, 1.0, 0.0].cross(&(hit.position - vector![0.0, 1.0, 0.0]));
+        assert!(hit.tangent.y.abs() < 1e-9);
+        assert!(hit.tangent.dot(&around_y) >= -1e-9);
+    }
+}
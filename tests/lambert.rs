@@ -0,0 +1,59 @@
+use raytracing2::bvh::TriangleBvh;
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::mesh::*;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+/// A unit square of the plane z = 0 facing +Z, with a one-sided and a two-sided white Lambert material
+fn quad_scene() -> (Hittable, SceneData) {
+    let normal = vector![0.0, 0.0, 1.0];
+    let vertices = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].iter()
+        .map(|&(x, y)| Vertex {position: vector![x, y, 0.0], normal, uv: vector![x, y], color: WHITE})
+        .collect();
+    let white = || Absorb::Albedo(rgb(1.0, 1.0, 1.0));
+    let scene_data = SceneData {
+        material_table: vec![
+            Material::new(Scatter::Lambert {two_sided: false}, white(), Emit::None),
+            Material::new(Scatter::Lambert {two_sided: true}, white(), Emit::None),
+        ],
+        mesh_table: vec![Mesh::new(vertices, vec![0, 1, 2, 0, 2, 3], MaterialId(0))],
+        ..SceneData::empty()
+    };
+    let root = Hittable::TriangleBvh(TriangleBvh::from_mesh(MeshId(0), &scene_data));
+    (root, scene_data)
+}
+
+#[test]
+fn two_sided_lambert_scatters_from_both_sides() {
+    let (root, scene_data) = quad_scene();
+    let mut rng = Randomizer::seed_from_u64(3);
+    for side in [1.0, -1.0] {
+        let ray = Ray {
+            origin: vector![0.4, 0.6, 2.0 * side],
+            direction: vector![0.1, -0.2, -side].normalize(),
+            t_min: RAY_EPSILON,
+            t_max: Real::INFINITY,
+            wavelength: None,
+            differentials: None,
+        };
+        let (hit, _) = root.hit(&ray, &scene_data).unwrap();
+        for _ in 0..100 {
+            let two_sided = scene_data.material(MaterialId(1)).evaluate(&ray, &hit, &scene_data, &mut rng, 0.0);
+            // Back to the side the ray came from, starting off the surface on that side
+            let scattered = two_sided.scatter.unwrap();
+            assert!(scattered.direction.z * side > 0.0);
+            assert!(scattered.origin.z * side > 0.0);
+            assert_eq!(two_sided.absorb, rgb(1.0, 1.0, 1.0));
+            let lobe = two_sided.lobe.unwrap();
+            assert!(lobe.pdf(&ray.direction, &vector![0.0, 0.0, side]) > 0.0);
+            assert_eq!(lobe.pdf(&ray.direction, &vector![0.0, 0.0, -side]), 0.0);
+
+            // The one-sided material is black from behind
+            let one_sided = scene_data.material(MaterialId(0)).evaluate(&ray, &hit, &scene_data, &mut rng, 0.0);
+            assert_eq!(one_sided.scatter.is_some(), side > 0.0);
+            assert_eq!(one_sided.lobe.is_some(), side > 0.0);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::light::LightList;
+use raytracing2::material::*;
+use raytracing2::mesh::{Mesh, MeshId, TriangleId, Vertex};
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+#[test]
+fn emissive_quad_is_found_as_the_only_light() {
+    let (normal, uv) = (vector![0.0, -1.0, 0.0], vector![0.0, 0.0]);
+    let scene_data = SceneData {
+        material_table: vec![
+            Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(4.0, 4.0, 4.0))),
+            Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.5, 0.5, 0.5)), Emit::None),
+        ],
+        mesh_table: vec![Mesh::new(
+            vec![
+                Vertex {position: vector![-1.0, 2.0, -1.0], normal, uv, color: WHITE},
+                Vertex {position: vector![1.0, 2.0, -1.0], normal, uv, color: WHITE},
+                Vertex {position: vector![1.0, 2.0, 1.0], normal, uv, color: WHITE},
+                Vertex {position: vector![-1.0, 2.0, 1.0], normal, uv, color: WHITE},
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+            MaterialId(0),
+        )],
+        ..SceneData::empty()
+    };
+    assert!(scene_data.material_table[0].is_emissive());
+    assert!(!scene_data.material_table[1].is_emissive());
+
+    // Nothing is registered by hand, the quad is the only primitive that glows
+    let root = Hittable::List(vec![
+        Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(0)},
+        Hittable::Triangle {triangle: TriangleId(3), mesh: MeshId(0)},
+        Hittable::Sphere {center: vector![0.0, -1000.0, 0.0], radius: 1000.0, material: MaterialId(1)},
+    ]);
+    let lights = LightList::new(&root, &scene_data);
+
+    // The quad is the single light, of area 4, sampled through one entry for each of its two triangles
+    assert_eq!(lights.len(), 2);
+    assert_eq!(lights.pdf_area(MaterialId(0)), 0.25);
+    assert_eq!(lights.pdf_area(MaterialId(1)), 0.0);
+}
+
+#[test]
+fn black_emission_is_not_a_light() {
+    let black = Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(0.0, 0.0, 0.0)));
+    assert!(!black.is_emissive());
+    assert!(Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(0.0, 0.1, 0.0))).is_emissive());
+
+    let scene_data = SceneData::new(vec![black]);
+    let root = Hittable::Sphere {center: vector![0.0, 0.0, 0.0], radius: 1.0, material: MaterialId(0)};
+    let lights = LightList::new(&root, &scene_data);
+    assert_eq!(lights.len(), 0);
+    assert_eq!(lights.pdf_area(MaterialId(0)), 0.0);
+}
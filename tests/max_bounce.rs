@@ -0,0 +1,35 @@
+use raytracing2::example_scenes::{self, ExampleScene};
+use raytracing2::image::Array2d;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+fn render(scene: &ExampleScene, max_bounce: usize) -> Array2d<Color> {
+    let params = RenderParams {
+        width: 24,
+        height: 16,
+        num_samples: 4,
+        filter: Filter::Box,
+        max_bounce,
+        tile_size: 8,
+        num_threads: 2,
+        seed: 3,
+        ..RenderParams::default()
+    };
+    render_image(scene, &params).unwrap().color
+}
+
+#[test]
+fn material_limit_stops_the_paths_like_the_global_one() {
+    let mut scene = example_scenes::three_balls();
+    scene.root.build_acceleration(&scene.scene_data);
+    let unlimited = render(&scene, 8);
+    let two_bounces = render(&scene, 2);
+
+    // A single bounce after any hit, so the third hit of a path is never reached
+    scene.scene_data.material_table = scene.scene_data.material_table.drain(..)
+        .map(|material| material.with_max_bounce(1))
+        .collect();
+    let limited = render(&scene, 8);
+    assert_eq!(limited.as_slice(), two_bounces.as_slice());
+    assert_ne!(limited.as_slice(), unlimited.as_slice());
+}
@@ -0,0 +1,69 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::randomness::*;
+use raytracing2::example_scenes;
+use raytracing2::render::{render_image, RenderParams, SceneData};
+use raytracing2::utility::*;
+
+fn lambert() -> Material {
+    Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.8, 0.2, 0.1)), Emit::None)
+}
+
+fn metal() -> Material {
+    Material::new(Scatter::Metal {fuzziness: 0.3}, Absorb::Albedo(rgb(0.9, 0.9, 0.9)), Emit::Color(rgb(0.1, 0.0, 0.0)))
+}
+
+fn mix(a: u32, b: u32, factor: Real) -> Material {
+    Material::new(Scatter::Mix {a: MaterialId(a), b: MaterialId(b), factor}, Absorb::BlackBody, Emit::None)
+}
+
+#[test]
+fn mix_factors_0_and_1_give_the_pure_materials() {
+    let scene_data = SceneData::new(vec![lambert(), metal(), mix(0, 1, 1.0), mix(0, 1, 0.0)]);
+    let ray = Ray {
+        origin: vector![0.3, 0.2, 5.0],
+        direction: vector![0.0, 0.0, -1.0],
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let sphere = Hittable::Sphere {center: Rvec3::zeros(), radius: 1.0, material: MaterialId(0)};
+    let (hit, _) = sphere.hit(&ray, &scene_data).unwrap();
+
+    // The same random numbers are drawn, so the outputs are the same down to the bits
+    for (mixed, pure) in [(2, 0), (3, 1)] {
+        for seed in 0..20 {
+            let evaluate = |material: u32| {
+                let mut rng = Randomizer::seed_from_u64(seed);
+                scene_data.material_table[material as usize].evaluate(&ray, &hit, &scene_data, &mut rng, 0.0)
+            };
+            let (mixed, pure) = (evaluate(mixed), evaluate(pure));
+            assert_eq!(mixed.scatter.map(|ray| ray.direction), pure.scatter.map(|ray| ray.direction));
+            assert_eq!(mixed.absorb, pure.absorb);
+            assert_eq!(mixed.emit, pure.emit);
+        }
+    }
+}
+
+#[test]
+fn mixes_that_come_back_to_themselves_are_rejected() {
+    // Mixes of mixes are fine as long as they end on plain materials
+    let nested = SceneData::new(vec![lambert(), metal(), mix(0, 1, 0.5), mix(2, 0, 0.5), mix(3, 2, 0.1)]);
+    assert!(nested.check_materials().is_ok());
+
+    // Directly, through other mixes, or into a missing material
+    assert!(SceneData::new(vec![lambert(), mix(1, 0, 0.5)]).check_materials().is_err());
+    assert!(SceneData::new(vec![lambert(), mix(0, 2, 0.5), mix(3, 0, 0.5), mix(0, 1, 0.5)]).check_materials().is_err());
+    assert!(SceneData::new(vec![lambert(), mix(0, 5, 0.5)]).check_materials().is_err());
+}
+
+#[test]
+fn render_fails_on_a_cycle_of_mixes() {
+    let mut scene = example_scenes::three_balls();
+    let cycle = scene.scene_data.material_table.len() as u32;
+    scene.scene_data.material_table.push(mix(cycle, 0, 0.5));
+    let params = RenderParams {width: 4, height: 4, ..RenderParams::default()};
+    let error = render_image(&scene, &params).map(|_| ()).unwrap_err();
+    assert!(error.to_string().starts_with("Invalid materials"), "{}", error);
+}
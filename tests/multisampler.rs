@@ -0,0 +1,137 @@
+use raytracing2::example_scenes::ExampleScene;
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::randomness::*;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+fn sampler() -> Multisampler {
+    Multisampler {width: 40, height: 25, num_samples: 16, filter: Filter::Box}
+}
+
+#[test]
+fn pixel_center_is_half_a_pixel_from_the_corner() {
+    let sampler = sampler();
+    assert_eq!(sampler.make_uv(0, 0), vector![0.5 / 40.0, 0.5 / 25.0]);
+    assert_eq!(sampler.make_uv(39, 24), vector![39.5 / 40.0, 24.5 / 25.0]);
+}
+
+#[test]
+fn jittered_samples_are_centered_on_the_pixel_center() {
+    let sampler = sampler();
+    let mut rng = Randomizer::seed_from_u64(3);
+    for (i, j) in [(0, 0), (7, 3), (39, 24)] {
+        let center = sampler.make_uv(i, j);
+        for (uv, offset, _) in sampler.make_uv_jitter(i, j, &mut rng) {
+            // The offset to the center is in pixels
+            let back_to_center = uv - vector![offset.x / 40.0, offset.y / 25.0];
+            assert!((back_to_center - center).norm() < 1e-12);
+        }
+    }
+}
+
+#[test]
+fn pixel_and_lens_samples_are_stratified() {
+    let sampler = sampler();
+    let mut rng = Randomizer::seed_from_u64(3);
+    let samples = sampler.make_uv_jitter(7, 3, &mut rng).collect::<Vec<_>>();
+    let cell = |x: Real, n: usize| ((x + 0.5) * n as Real) as usize;
+    let mut rows = [0; 16];
+    let mut columns = [0; 16];
+    let mut grid = [[0; 4]; 4];
+    let mut lens_quadrants = [[0; 2]; 2];
+    for (_, offset, lens) in samples {
+        rows[cell(offset.y, 16)] += 1;
+        columns[cell(offset.x, 16)] += 1;
+        grid[cell(offset.x, 4)][cell(offset.y, 4)] += 1;
+        assert!(lens.norm() <= 1.0);
+        lens_quadrants[(lens.x > 0.0) as usize][(lens.y > 0.0) as usize] += 1;
+    }
+    // One sample in each of the 16 rows, of the 16 columns and of the 4 by 4 cells, and as many on each quarter of
+    // the lens
+    assert_eq!((rows, columns), ([1; 16], [1; 16]));
+    assert_eq!(grid, [[1; 4]; 4]);
+    assert_eq!(lens_quadrants, [[4; 2]; 2]);
+}
+
+#[test]
+fn few_samples_still_cover_the_rows_and_columns() {
+    // With fewer than 4 samples, the grid is a single column
+    for num_samples in 1..4 {
+        let pattern = CorrelatedMultiJitter {num_samples, pattern: 12345};
+        let samples: Vec<Rvec2> = (0..num_samples).map(|s| pattern.sample(s)).collect();
+        for axis in 0..2 {
+            let mut cells: Vec<u32> = samples.iter().map(|x| (x[axis] * num_samples as Real) as u32).collect();
+            cells.sort_unstable();
+            assert_eq!(cells, (0..num_samples).collect::<Vec<_>>(), "{} samples", num_samples);
+        }
+    }
+}
+
+#[test]
+fn filter_weights_fall_off_from_the_pixel_center() {
+    let center = vector![0.0, 0.0];
+    assert_eq!(Filter::Box.weight(&vector![0.4, -0.3]), 1.0);
+
+    // The tent is the product of a linear falloff along each axis
+    let tent = Filter::Tent {radius: 1.0};
+    assert_eq!(tent.weight(&center), 1.0);
+    assert!((tent.weight(&vector![0.5, 0.0]) - 0.5).abs() < 1e-12);
+    assert!((tent.weight(&vector![0.5, -0.5]) - 0.25).abs() < 1e-12);
+    assert_eq!(tent.weight(&vector![1.0, 0.2]), 0.0);
+    assert_eq!(tent.weight(&vector![0.0, -1.5]), 0.0);
+
+    // The gaussian depends only on the distance, down to exp(-1/2) at one standard deviation
+    let gaussian = Filter::Gaussian {sigma: 0.5};
+    assert_eq!(gaussian.weight(&center), 1.0);
+    assert!((gaussian.weight(&vector![0.3, 0.4]) - (-0.5 as Real).exp()).abs() < 1e-12);
+    assert!((gaussian.weight(&vector![0.0, 0.5]) - gaussian.weight(&vector![-0.3, -0.4])).abs() < 1e-12);
+    assert!(gaussian.weight(&vector![1.0, 0.0]) < gaussian.weight(&vector![0.5, 0.0]));
+}
+
+/// A glowing ball out of focus, so that its outline is blurred by the lens
+fn defocused_ball() -> ExampleScene {
+    let material_table = vec![Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(1.0, 1.0, 1.0)))];
+    let root = Hittable::Sphere {center: vector![0.0, 0.0, 0.0], radius: 2.0, material: MaterialId(0)};
+    let camera = Camera {
+        aspect_ratio: 1.0,
+        fov: FRAC_PI_3,
+        focus_distance: 2.0,
+        lens_radius: 0.2,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 0.0, 5.0],
+            &vector![0.0, 0.0, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+    let scene_data = SceneData::new(material_table);
+    ExampleScene {camera, scene_data, root, background: Emit::None, animation: None}
+}
+
+fn render_defocused_ball(num_samples: u32, seed: u64) -> Vec<Real> {
+    let params = RenderParams {
+        width: 16,
+        height: 16,
+        num_samples,
+        filter: Filter::Box,
+        max_bounce: 1,
+        tile_size: 16,
+        num_threads: 1,
+        seed,
+        ..RenderParams::default()
+    };
+    render_image(&defocused_ball(), &params).unwrap().color.as_slice().iter().map(|c| c.x).collect()
+}
+
+#[test]
+fn defocused_edge_is_clean_with_few_samples() {
+    let reference = render_defocused_ball(256, 9);
+    for seed in 1..4 {
+        let image = render_defocused_ball(16, seed);
+        let squared_error = image.iter().zip(&reference).map(|(x, y)| (x - y).powi(2)).sum::<Real>();
+        // Independent random points of the pixel and of the lens give an error of about 0.05
+        let rmse = (squared_error / image.len() as Real).sqrt();
+        assert!(rmse < 0.04, "seed {}: {}", seed, rmse);
+    }
+}
@@ -0,0 +1,56 @@
+use raytracing2::randomness::noise;
+use raytracing2::utility::*;
+
+#[test]
+fn gradients_are_uniform_unit_vectors() {
+    const SIDE: isize = 40;
+    const BINS: usize = 8;
+    let mut mean = Rvec3::zeros();
+    let mut second_moment = Rmat3::zeros();
+    // Slices of equal height hold equal areas of the sphere, and so do the sectors around the pole
+    let mut height_bins = [0; BINS];
+    let mut angle_bins = [0; BINS];
+    let mut count = 0;
+    for x in -SIDE / 2..SIDE / 2 {
+        for y in -SIDE / 2..SIDE / 2 {
+            for z in -SIDE / 2..SIDE / 2 {
+                let gradient = noise::vec3(x, y, z, 7);
+                assert!((gradient.norm() - 1.0).abs() < 1e-9);
+                mean += gradient;
+                second_moment += gradient * gradient.transpose();
+                height_bins[((0.5 * gradient.z + 0.5) * BINS as Real) as usize % BINS] += 1;
+                angle_bins[((gradient.y.atan2(gradient.x) / TAU + 0.5) * BINS as Real) as usize % BINS] += 1;
+                count += 1;
+            }
+        }
+    }
+    let count_real = count as Real;
+    assert!((mean / count_real).norm() < 0.02, "mean {:?}", mean / count_real);
+    // No favored axis: the covariance of uniform directions is a third of the identity
+    assert!((second_moment / count_real - Rmat3::identity() / 3.0).abs().max() < 0.01);
+    for bins in [height_bins, angle_bins].iter() {
+        let expected = count_real / BINS as Real;
+        assert!(bins.iter().all(|&n| (n as Real - expected).abs() < 0.05 * expected), "{:?}", bins);
+    }
+}
+
+#[test]
+fn neighbors_and_seeds_are_not_correlated() {
+    const N: isize = 20000;
+    let correlation = |a: &dyn Fn(isize) -> Real, b: &dyn Fn(isize) -> Real| {
+        (0..N).map(|i| a(i) * b(i)).sum::<Real>() / N as Real
+    };
+    // Uniform in [-1, 1], so the product of independent values averages to 0, and a value with itself to 1/3
+    assert!((correlation(&|i| noise::real1(i, 0), &|i| noise::real1(i, 0)) - 1.0 / 3.0).abs() < 0.01);
+    assert!(correlation(&|i| noise::real1(i, 0), &|i| noise::real1(i + 1, 0)).abs() < 0.01);
+    assert!(correlation(&|i| noise::real1(i, 0), &|i| noise::real1(i, 1)).abs() < 0.01);
+    assert!(correlation(&|i| noise::real2(i, 3, 0), &|i| noise::real2(3, i, 0)).abs() < 0.01);
+    assert!(correlation(&|i| noise::real(i, 0, 0, 0), &|i| noise::real(i, 0, 0, 1)).abs() < 0.01);
+    // Swapped coordinates give other values
+    assert!(correlation(&|i| noise::real(i, 1, 2, 0), &|i| noise::real(1, i, 2, 0)).abs() < 0.01);
+    for i in 0..N {
+        for value in [noise::real1(i, 5), noise::real2(i, -i, 5), noise::real(i, -i, 2 * i, 5)].iter() {
+            assert!((-1.0..=1.0).contains(value));
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use raytracing2::bvh::TriangleBvh;
+use raytracing2::hittable::Hittable;
+use raytracing2::mesh::*;
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+/// Load an obj written to a temporary file
+fn load_obj(name: &str, text: &str) -> Result<Mesh, String> {
+    load_obj_with_options(name, text, &LoadOptions::default())
+}
+
+fn load_obj_with_options(name: &str, text: &str, options: &LoadOptions) -> Result<Mesh, String> {
+    let path = std::env::temp_dir().join(format!("raytracing2_{}_{}.obj", name, std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    let mesh = obj::load_with_options(path.to_str().unwrap(), options).map_err(|e| e.to_string());
+    std::fs::remove_file(&path).unwrap();
+    mesh
+}
+
+fn positions(mesh: &Mesh, triangle: TriangleId) -> [Rvec3; 3] {
+    let (a, b, c) = mesh.get_triangle(triangle);
+    [a.position, b.position, c.position]
+}
+
+#[test]
+fn degenerate_faces_are_dropped() {
+    // The second face repeats a vertex and the third one is flat along a line
+    let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 2 0 0\nf 1 2 3\nf 1 2 2\nf 1 2 4\nf 3 1 2\n";
+    let mesh = load_obj("degenerate", text).unwrap();
+    assert_eq!(mesh.iter_triangles().count(), 2);
+    let (o, x, y) = (vector![0.0, 0.0, 0.0], vector![1.0, 0.0, 0.0], vector![0.0, 1.0, 0.0]);
+    assert_eq!(positions(&mesh, TriangleId(0)), [o, x, y]);
+    assert_eq!(positions(&mesh, TriangleId(3)), [y, o, x]);
+}
+
+#[test]
+fn negative_indices_count_back_from_the_last_element() {
+    let text = "v 0 0 0\nv 1 0 0\nvt 0.5 0.5\nvn 0 0 -1\nv 0 1 0\nvt 0.25 0.75\nvn 0 0 1\nf 1/1/1 2/1/1 -1/-1/-1\n";
+    let mesh = load_obj("negative", text).unwrap();
+    let (_, _, last) = mesh.get_triangle(TriangleId(0));
+    assert_eq!(last.position, vector![0.0, 1.0, 0.0]);
+    assert_eq!(last.uv, vector![0.25, 0.75]);
+    assert_eq!(last.normal, vector![0.0, 0.0, 1.0]);
+
+    // Counting back past the first element is an error
+    assert!(load_obj("negative_out_of_range", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 -4\n").is_err());
+}
+
+#[test]
+fn comments_and_groups_are_skipped() {
+    let text = "# A single triangle\nmtllib triangle.mtl\no triangle\ng front\ns off\nusemtl red\n\
+        v 0 0 0 # first\nv 1 0 0\n\n  # indented comment\nv 0 1 0\nf 1 2 3\n";
+    let mesh = load_obj("comments", text);
+    let mesh = mesh.unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(mesh.iter_triangles().count(), 1);
+    assert_eq!(mesh.vertices().len(), 3);
+}
+
+#[test]
+fn trailing_garbage_on_a_geometry_line_is_an_error() {
+    let error = load_obj("garbage", "v 0 0 0\nv 1 0 0 junk\nv 0 1 0\nf 1 2 3\n").map(|_| ()).unwrap_err();
+    assert!(error.contains("Line 2"), "{}", error);
+    assert!(load_obj("garbage_face", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3 x\n").is_err());
+
+    // The optional color after a position is still accepted
+    assert!(load_obj("color", "v 0 0 0 1 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").is_ok());
+}
+
+#[test]
+fn optional_w_coordinates_are_accepted() {
+    let text = "v 0 0 0 1\nv 1 0 0 1\nv 0 1 0 0.5\nvt 0 0 0\nvt 1 0 0\nvt 0 1 0\nf 1/1 2/2 3/3\n";
+    let mesh = load_obj("w", text).unwrap_or_else(|e| panic!("{}", e));
+    let (o, x, y) = (vector![0.0, 0.0, 0.0], vector![1.0, 0.0, 0.0], vector![0.0, 1.0, 0.0]);
+    assert_eq!(positions(&mesh, TriangleId(0)), [o, x, y]);
+    assert_eq!(mesh.get_triangle(TriangleId(0)).2.uv, vector![0.0, 1.0]);
+
+    // But not more values than the w, nor text after it
+    assert!(load_obj("v_five", "v 0 0 0 1 2\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").is_err());
+    assert!(load_obj("v_w_junk", "v 0 0 0 1 junk\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").is_err());
+    assert!(load_obj("vt_four", "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0 0 0\nf 1/1 2/1 3/1\n").is_err());
+    assert!(load_obj("vt_junk", "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0 junk\nf 1/1 2/1 3/1\n").is_err());
+}
+
+#[test]
+fn nearly_coincident_vertices_are_welded() {
+    // Two triangles sharing the edge from (1, 0, 0) to (0, 1, 0), written twice with a tiny difference
+    let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1.000000001 0 0\nv 0 1.000000001 0\nv 1 1 0\n\
+        vn 0 0 1\nvn 0 0.6 0.8\nf 1//1 2//1 3//1\nf 4//2 6//2 5//2\n";
+    let mesh = load_obj("unwelded", text).unwrap();
+    assert_eq!(mesh.vertices().len(), 6);
+
+    let options = LoadOptions {weld_tolerance: Some(1e-6)};
+    let mesh = load_obj_with_options("welded", text, &options).unwrap();
+    assert_eq!(mesh.vertices().len(), 4);
+    assert_eq!(mesh.indices(), &[0, 1, 2, 1, 3, 2]);
+
+    // The shared vertices take the average normal of the two triangles
+    let shared_normal = (vector![0.0, 0.0, 1.0] + vector![0.0, 0.6, 0.8]).normalize();
+    assert!((mesh.vertices()[1].normal - shared_normal).norm() < 1e-12);
+    assert_eq!(mesh.vertices()[0].normal, vector![0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn weld_tolerance_must_be_a_positive_distance() {
+    let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+    for tolerance in [0.0, -1e-6, Real::NAN, Real::INFINITY] {
+        let options = LoadOptions {weld_tolerance: Some(tolerance)};
+        let error = load_obj_with_options("bad_tolerance", text, &options).map(|_| ()).unwrap_err();
+        assert!(error.contains("weld tolerance"), "{}", error);
+    }
+}
+
+#[test]
+fn mesh_of_degenerate_faces_makes_an_empty_bvh() {
+    let mesh = load_obj("all_degenerate", "v 0 0 0\nv 1 0 0\nv 2 0 0\nf 1 2 3\nf 1 1 2\n").unwrap();
+    assert_eq!(mesh.iter_triangles().count(), 0);
+    let scene_data = SceneData {mesh_table: vec![mesh], ..SceneData::empty()};
+    let mut bvh = TriangleBvh::from_mesh(MeshId(0), &scene_data);
+    assert_eq!((bvh.count_primitives(), bvh.node_count(), bvh.max_depth()), (0, 0, 0));
+    assert_eq!(bvh.bounding_box(), AABB::empty());
+    bvh.refit(&scene_data);
+
+    // Nothing is hit, even along the line of the vertices
+    let ray = Ray {
+        origin: vector![-1.0, 0.0, 0.0],
+        direction: vector![1.0, 0.0, 0.0],
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let hittable = Hittable::TriangleBvh(bvh);
+    assert!(hittable.hit(&ray, &scene_data).is_none());
+    assert!(!hittable.hit_any(&ray, &scene_data));
+}
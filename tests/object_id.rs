@@ -0,0 +1,88 @@
+use raytracing2::example_scenes::ExampleScene;
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 16;
+
+/// Two spheres side by side in front of the camera, with nothing behind them
+fn two_spheres() -> ExampleScene {
+    let material_table = vec![
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.8, 0.2, 0.2)), Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(0.2, 0.2, 0.8)), Emit::None),
+    ];
+    let root = Hittable::List(vec![
+        Hittable::Sphere {center: vector![-1.0, 0.0, 0.0], radius: 0.8, material: MaterialId(0)},
+        Hittable::Sphere {center: vector![1.0, 0.0, 0.0], radius: 0.8, material: MaterialId(1)},
+    ]);
+    let camera = Camera {
+        aspect_ratio: WIDTH as Real / HEIGHT as Real,
+        fov: FRAC_PI_3,
+        focus_distance: 5.0,
+        lens_radius: 0.0,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::lookat(
+            &vector![0.0, 0.0, 5.0],
+            &vector![0.0, 0.0, 0.0],
+            &vector![0.0, 1.0, 0.0]
+        ),
+    };
+    let scene_data = SceneData::new(material_table);
+    let background = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
+    ExampleScene {camera, scene_data, root, background, animation: None}
+}
+
+/// The pixel where the camera sees a point
+fn pixel_of(point: &Rvec3) -> (u32, u32) {
+    let uv = two_spheres().camera.project(point).unwrap();
+    ((uv.x * WIDTH as Real) as u32, (uv.y * HEIGHT as Real) as u32)
+}
+
+fn render_object_ids() -> Vec<Option<usize>> {
+    let params = RenderParams {
+        width: WIDTH,
+        height: HEIGHT,
+        num_samples: 4,
+        filter: Filter::Tent {radius: 1.0},
+        max_bounce: 2,
+        tile_size: 8,
+        num_threads: 2,
+        seed: 1,
+        ..RenderParams::default()
+    };
+    let object_id = render_image(&two_spheres(), &params).unwrap().object_id;
+    object_id.as_slice().iter().map(|id| id.map(MaterialId::to_index)).collect()
+}
+
+fn at(ids: &[Option<usize>], i: u32, j: u32) -> Option<usize> {
+    ids[(i + j * WIDTH) as usize]
+}
+
+#[test]
+fn two_spheres_have_distinct_ids() {
+    let ids = render_object_ids();
+    let (i, j) = pixel_of(&vector![-1.0, 0.0, 0.0]);
+    let left = at(&ids, i, j);
+    let (i, j) = pixel_of(&vector![1.0, 0.0, 0.0]);
+    let right = at(&ids, i, j);
+    assert_eq!(left, Some(0));
+    assert_eq!(right, Some(1));
+    assert_ne!(left, right);
+}
+
+#[test]
+fn background_has_no_id() {
+    let ids = render_object_ids();
+    for (i, j) in [(0, 0), (WIDTH - 1, 0), (0, HEIGHT - 1), (WIDTH - 1, HEIGHT - 1), (WIDTH / 2, 0)] {
+        assert_eq!(at(&ids, i, j), None, "pixel ({}, {})", i, j);
+    }
+}
+
+#[test]
+fn every_id_is_a_material_of_the_scene() {
+    let ids = render_object_ids();
+    assert!(ids.iter().flatten().all(|&id| id < 2));
+    assert!(ids.iter().any(|id| id.is_none()));
+}
@@ -0,0 +1,108 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::light::{Light, LightList};
+use raytracing2::material::*;
+use raytracing2::mesh::*;
+use raytracing2::randomness::*;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+fn trace(scatter: Scatter) -> Color {
+    let emissive = Material::new(scatter, Absorb::BlackBody, Emit::Color(rgb(0.5, 0.25, 0.125)));
+    let scene_data = SceneData::new(vec![emissive]);
+    let root = Hittable::Sphere {center: vector![0.0, 0.0, 5.0], radius: 1.0, material: MaterialId(0)};
+    let lights = LightList::new(&root, &scene_data);
+    let background = Emit::Color(rgb(0.0, 1.0, 2.0));
+    let ray = Ray {
+        origin: Rvec3::zeros(),
+        direction: vector![0.0, 0.0, 1.0],
+        t_min: RAY_EPSILON,
+        t_max: INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let mut rng = Randomizer::seed_from_u64(1);
+    trace_path(&root, &ray, 4, &scene_data, &mut rng, &background, &lights, None, 0.0).final_color
+}
+
+#[test]
+fn passthrough_surface_shows_the_background_behind_its_emission() {
+    // The ray crosses both sides of the sphere, each adds its emission, and the black body absorption is ignored
+    assert_eq!(trace(Scatter::Passthrough), rgb(0.0, 1.0, 2.0) + 2.0 * rgb(0.5, 0.25, 0.125));
+    // While a surface that does not scatter hides the background
+    assert_eq!(trace(Scatter::None), rgb(0.5, 0.25, 0.125));
+}
+
+/// A horizontal square facing up, centered on the y axis
+fn quad(y: Real, half_size: Real, material: MaterialId) -> Mesh {
+    let (normal, uv) = (vector![0.0, 1.0, 0.0], vector![0.0, 0.0]);
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    let vertices = corners.iter()
+        .map(|(x, z)| Vertex {position: vector![half_size * x, y, half_size * z], normal, uv, color: WHITE})
+        .collect();
+    Mesh::new(vertices, vec![0, 2, 1, 0, 3, 2], material)
+}
+
+/// A white diffuse floor at y = 0, with a panel of the given scattering at y = 1 between it and the lights above, and
+/// an emissive sphere at y = 3 if asked
+fn panel_scene(panel: Option<Scatter>, point_lights: Vec<Light>, sphere_light: bool) -> (Hittable, SceneData) {
+    let white = Absorb::Albedo(rgb(1.0, 1.0, 1.0));
+    let scene_data = SceneData {
+        material_table: vec![
+            Material::new(Scatter::Lambert {two_sided: false}, white.clone(), Emit::None),
+            Material::new(panel.clone().unwrap_or(Scatter::None), white, Emit::None),
+            Material::new(Scatter::None, Absorb::BlackBody, Emit::Color(rgb(5.0, 5.0, 5.0))),
+        ],
+        mesh_table: vec![quad(0.0, 100.0, MaterialId(0)), quad(1.0, 0.5, MaterialId(1))],
+        light_table: point_lights,
+        ..SceneData::empty()
+    };
+    let mut root = vec![
+        Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(0)},
+        Hittable::Triangle {triangle: TriangleId(3), mesh: MeshId(0)},
+    ];
+    if panel.is_some() {
+        root.push(Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(1)});
+        root.push(Hittable::Triangle {triangle: TriangleId(3), mesh: MeshId(1)});
+    }
+    if sphere_light {
+        root.push(Hittable::Sphere {center: vector![0.0, 3.0, 0.0], radius: 0.5, material: MaterialId(2)});
+    }
+    (Hittable::List(root), scene_data)
+}
+
+/// The light coming back from the floor under the panel, averaged over a few paths
+fn floor_light((root, scene_data): &(Hittable, SceneData), depth: usize) -> Color {
+    let ray = Ray {
+        origin: vector![0.0, 0.1, 0.0],
+        direction: vector![0.0, -1.0, 0.0],
+        t_min: RAY_EPSILON,
+        t_max: INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let lights = LightList::new(root, scene_data);
+    let num_paths = 64;
+    (0..num_paths).map(|seed| {
+        let mut rng = Randomizer::seed_from_u64(seed);
+        trace_path(root, &ray, depth, scene_data, &mut rng, &Emit::None, &lights, None, 0.0).final_color
+    }).sum::<Color>() / num_paths as Real
+}
+
+#[test]
+fn point_light_shines_through_a_passthrough_panel() {
+    let light = || vec![Light::Point {position: vector![0.0, 2.0, 0.0], color: WHITE, intensity: 10.0}];
+    let open = floor_light(&panel_scene(None, light(), false), 1);
+    assert!((open - rgb(2.5, 2.5, 2.5) / PI).norm() < 1e-9, "{:?}", open);
+    assert_eq!(floor_light(&panel_scene(Some(Scatter::Passthrough), light(), false), 1), open);
+    // While an opaque panel casts its shadow
+    assert_eq!(floor_light(&panel_scene(Some(Scatter::None), light(), false), 1), rgb(0.0, 0.0, 0.0));
+}
+
+#[test]
+fn area_light_behind_a_passthrough_panel_is_weighted_as_if_the_panel_was_not_there() {
+    // The light is found both by the light samples and by the scattered rays, which must still add up to it
+    let open = floor_light(&panel_scene(None, Vec::new(), true), 3);
+    let through = floor_light(&panel_scene(Some(Scatter::Passthrough), Vec::new(), true), 3);
+    assert!(open.max() > 0.0);
+    assert!((through - open).norm() < 1e-12 * open.norm(), "{:?} instead of {:?}", through, open);
+}
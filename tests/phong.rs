@@ -0,0 +1,46 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+/// The cosines between the mirror direction and the rays scattered by a Phong lobe, on a ray hitting the ground at 45°
+fn cosines_to_mirror(shininess: Real) -> Vec<Real> {
+    let scene_data = SceneData::empty();
+    let root = Hittable::Sphere {center: vector![0.0, -1000.0, 0.0], radius: 1000.0, material: MaterialId(0)};
+    let ray = Ray {
+        origin: vector![-1.0, 1.0, 0.0],
+        direction: vector![1.0, -1.0, 0.0].normalize(),
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let (hit, _) = root.hit(&ray, &scene_data).unwrap();
+    let mirror = reflect(&ray.direction, &hit.normal);
+
+    let scatter = Scatter::Phong {shininess, specular: rgb(1.0, 1.0, 1.0)};
+    let mut rng = Randomizer::seed_from_u64(5);
+    (0..1000)
+        .filter_map(|_| scatter.evaluate(&ray, &hit, &scene_data, &mut rng))
+        .map(|scattered| {
+            assert!(scattered.direction.dot(&hit.normal) > 0.0);
+            scattered.direction.dot(&mirror)
+        })
+        .collect()
+}
+
+#[test]
+fn high_shininess_approaches_the_mirror_reflection() {
+    let cosines = cosines_to_mirror(1e5);
+    assert!(cosines.len() > 500);
+    assert!(cosines.iter().all(|&cos| cos > 0.999));
+}
+
+#[test]
+fn low_shininess_spreads_widely() {
+    let cosines = cosines_to_mirror(1.0);
+    assert!(cosines.iter().any(|&cos| cos < 0.2));
+    let mean = cosines.iter().sum::<Real>() / cosines.len() as Real;
+    assert!(mean < 0.8, "mean cosine {}", mean);
+}
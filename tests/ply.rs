@@ -0,0 +1,67 @@
+use raytracing2::mesh::*;
+use raytracing2::utility::*;
+
+/// Load a ply written to a temporary file
+fn load_ply(name: &str, text: &str) -> Result<Mesh, String> {
+    let path = std::env::temp_dir().join(format!("raytracing2_{}_{}.ply", name, std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    let mesh = ply::load(path.to_str().unwrap()).map_err(|e| e.to_string());
+    std::fs::remove_file(&path).unwrap();
+    mesh
+}
+
+/// A unit cube made of quads, with a normal on each corner if asked
+fn cube_ply(with_normals: bool) -> String {
+    let mut text = String::from("ply\nformat ascii 1.0\ncomment a unit cube\nelement vertex 8\n");
+    text += "property float x\nproperty float y\nproperty float z\n";
+    if with_normals {
+        text += "property float nx\nproperty float ny\nproperty float nz\n";
+    }
+    text += "element face 6\nproperty list uchar int vertex_indices\nend_header\n";
+    for i in 0..8 {
+        let corner = [i & 1, (i >> 1) & 1, (i >> 2) & 1].map(|x| x as Real);
+        text += &format!("{} {} {}", corner[0], corner[1], corner[2]);
+        if with_normals {
+            let normal = vector![corner[0] - 0.5, corner[1] - 0.5, corner[2] - 0.5].normalize();
+            text += &format!(" {} {} {}", normal.x, normal.y, normal.z);
+        }
+        text += "\n";
+    }
+    text += "4 0 2 3 1\n4 4 5 7 6\n4 0 1 5 4\n4 2 6 7 3\n4 0 4 6 2\n4 1 3 7 5\n";
+    text
+}
+
+#[test]
+fn cube_without_normals() {
+    let mesh = load_ply("cube", &cube_ply(false)).unwrap();
+    assert_eq!(mesh.vertices().len(), 8);
+    assert_eq!(mesh.iter_triangles().count(), 12);
+    assert_eq!(mesh.vertices()[6].position, vector![0.0, 1.0, 1.0]);
+    assert!(mesh.vertices().iter().all(|v| v.normal == Rvec3::zeros()));
+
+    // Each quad is split around its first corner
+    assert_eq!(&mesh.indices()[..6], &[0, 2, 3, 0, 3, 1]);
+}
+
+#[test]
+fn cube_with_normals() {
+    let mesh = load_ply("cube_normals", &cube_ply(true)).unwrap();
+    assert_eq!(mesh.iter_triangles().count(), 12);
+    for v in mesh.vertices() {
+        let expected = (v.position - vector![0.5, 0.5, 0.5]).normalize();
+        assert!((v.normal - expected).norm() < 1e-6);
+    }
+}
+
+#[test]
+fn end_header_in_a_comment_does_not_end_the_header() {
+    let text = cube_ply(false).replace("comment a unit cube", "comment the end_header line comes last");
+    assert_eq!(load_ply("comment", &text).unwrap().iter_triangles().count(), 12);
+}
+
+#[test]
+fn negative_face_indices_are_rejected() {
+    let text = cube_ply(false).replace("4 0 2 3 1\n", "4 0 2 3 -1\n");
+    let error = load_ply("negative", &text).map(|_| ()).unwrap_err();
+    assert!(error.contains("Negative"), "{}", error);
+}
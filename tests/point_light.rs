@@ -0,0 +1,127 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::light::{Light, LightList};
+use raytracing2::material::*;
+use raytracing2::mesh::*;
+use raytracing2::randomness::*;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+const HALF_SIZE: Real = 100.0;
+
+/// A white diffuse floor at y = 0, facing up, with an optional sphere above it to cast a shadow
+fn floor_scene(lights: Vec<Light>, occluder: Option<(Rvec3, Real)>) -> (Hittable, SceneData) {
+    let normal = vector![0.0, 1.0, 0.0];
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    let vertices = corners.iter()
+        .map(|(x, z)| Vertex {position: HALF_SIZE * vector![*x, 0.0, *z], normal, uv: vector![0.0, 0.0], color: WHITE})
+        .collect();
+    let floor = Mesh::new(vertices, vec![0, 2, 1, 0, 3, 2], MaterialId(0));
+    let material_table = vec![
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::Albedo(rgb(1.0, 1.0, 1.0)), Emit::None)
+    ];
+
+    let mut root = vec![
+        Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(0)},
+        Hittable::Triangle {triangle: TriangleId(3), mesh: MeshId(0)},
+    ];
+    if let Some((center, radius)) = occluder {
+        root.push(Hittable::Sphere {center, radius, material: MaterialId(0)});
+    }
+    let scene_data = SceneData {material_table, mesh_table: vec![floor], light_table: lights, ..SceneData::empty()};
+    (Hittable::List(root), scene_data)
+}
+
+/// The light reflected straight up by the floor at (x, 0, z), without the bounces.
+/// The ray starts just above the floor, so that it does not meet the occluder on its way.
+fn direct_light(root: &Hittable, scene_data: &SceneData, x: Real, z: Real) -> Color {
+    let ray = Ray {
+        origin: vector![x, 0.1, z],
+        direction: vector![0.0, -1.0, 0.0],
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let mut rng = Randomizer::seed_from_u64(1);
+    let lights = LightList::new(root, scene_data);
+    trace_path(root, &ray, 1, scene_data, &mut rng, &Emit::None, &lights, None, 0.0).final_color
+}
+
+fn assert_close(actual: Color, expected: Color) {
+    assert!((actual - expected).norm() < 1e-9 * expected.norm().max(1.0), "{:?} instead of {:?}", actual, expected);
+}
+
+#[test]
+fn point_light_follows_the_inverse_square_law() {
+    let position = vector![0.5, 2.0, -1.0];
+    let color = rgb(1.0, 0.8, 0.5);
+    let intensity = 30.0;
+    let (root, scene_data) = floor_scene(vec![Light::Point {position, color, intensity}], None);
+
+    for (x, z) in [(0.5, -1.0), (1.5, -1.0), (-2.0, 3.0), (10.0, 4.0)] {
+        let to_light: Rvec3 = position - vector![x, 0.0, z];
+        let r = to_light.norm();
+        let cos = to_light.y / r;
+        // Irradiance intensity / r² * cos, reflected by a white Lambert surface
+        let expected = intensity / (r * r) * cos * color / PI;
+        assert_close(direct_light(&root, &scene_data, x, z), expected);
+    }
+}
+
+#[test]
+fn point_lights_add_up() {
+    let a = Light::Point {position: vector![1.0, 1.0, 0.0], color: rgb(1.0, 0.0, 0.0), intensity: 4.0};
+    let b = Light::Point {position: vector![-1.0, 3.0, 2.0], color: rgb(0.0, 0.5, 1.0), intensity: 9.0};
+    let (root_a, data_a) = floor_scene(vec![a.clone()], None);
+    let (root_b, data_b) = floor_scene(vec![b.clone()], None);
+    let (root_ab, data_ab) = floor_scene(vec![a, b], None);
+    let sum = direct_light(&root_a, &data_a, 0.3, 0.7) + direct_light(&root_b, &data_b, 0.3, 0.7);
+    assert_close(direct_light(&root_ab, &data_ab, 0.3, 0.7), sum);
+}
+
+#[test]
+fn point_light_casts_hard_shadows() {
+    let light = Light::Point {position: vector![0.0, 4.0, 0.0], color: rgb(1.0, 1.0, 1.0), intensity: 10.0};
+    let (root, scene_data) = floor_scene(vec![light], Some((vector![0.0, 2.0, 0.0], 0.5)));
+    // Right under the sphere, and just outside of its shadow
+    assert_eq!(direct_light(&root, &scene_data, 0.0, 0.0), rgb(0.0, 0.0, 0.0));
+    assert!(direct_light(&root, &scene_data, 3.0, 0.0).x > 0.0);
+}
+
+#[test]
+fn spot_light_only_lights_its_cone() {
+    let position = vector![0.0, 2.0, 0.0];
+    let color = rgb(1.0, 1.0, 1.0);
+    let intensity = 10.0;
+    // Pointing straight down with a 45 degrees half angle: the lit disk has a radius of 2
+    let spot = Light::Spot {
+        position, direction: vector![0.0, -1.0, 0.0], cone_angle: FRAC_PI_4, edge_angle: 0.0, color, intensity
+    };
+    let (root, scene_data) = floor_scene(vec![spot], None);
+    let (point_root, point_data) = floor_scene(vec![Light::Point {position, color, intensity}], None);
+
+    // Inside, it is the same as a point light
+    for x in [0.0, 1.0, 1.9] {
+        assert_close(direct_light(&root, &scene_data, x, 0.0), direct_light(&point_root, &point_data, x, 0.0));
+    }
+    // Outside, nothing
+    for x in [2.1, 5.0] {
+        assert_eq!(direct_light(&root, &scene_data, x, 0.0), rgb(0.0, 0.0, 0.0));
+    }
+}
+
+#[test]
+fn spot_light_fades_out_at_its_edge() {
+    let spot = Light::Spot {
+        position: vector![0.0, 2.0, 0.0], direction: vector![0.0, -1.0, 0.0], cone_angle: FRAC_PI_4,
+        edge_angle: 0.2, color: rgb(1.0, 1.0, 1.0), intensity: 10.0
+    };
+    // Full intensity inside the inner cone, then less and less toward the edge
+    let at = |angle: Real| spot.intensity(&vector![angle.sin(), -angle.cos(), 0.0]).x;
+    assert_eq!(at(0.0), 10.0);
+    assert_eq!(at(FRAC_PI_4 - 0.21), 10.0);
+    assert!(at(FRAC_PI_4 - 0.15) > at(FRAC_PI_4 - 0.1));
+    assert!(at(FRAC_PI_4 - 0.1) > at(FRAC_PI_4 - 0.05));
+    assert!(at(FRAC_PI_4 - 0.05) > 0.0);
+    assert_eq!(at(FRAC_PI_4 + 0.01), 0.0);
+}
@@ -0,0 +1,56 @@
+use raytracing2::image::Array2d;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+fn gray_image(width: u32, height: u32, value: Real) -> Array2d<Color> {
+    Array2d::from_raw(width, height, vec![Color::repeat(value); (width * height) as usize]).unwrap()
+}
+
+#[test]
+fn vignette_darkens_the_corners_only_with_some_strength() {
+    let original = gray_image(31, 21, 0.8);
+    let mut image = original.clone();
+    apply_vignette(&mut image, 0.0);
+    assert_eq!(image.as_slice(), original.as_slice());
+
+    apply_vignette(&mut image, 1.0);
+    // The center pixel is untouched, the corners are almost black, and the edges are in between
+    assert_eq!(*image.get(15, 10), *original.get(15, 10));
+    for (i, j) in [(0, 0), (30, 0), (0, 20), (30, 20)] {
+        assert!(image.get(i, j).x < 0.1 * original.get(i, j).x, "corner ({}, {}): {:?}", i, j, image.get(i, j));
+    }
+    assert!(image.get(0, 10).x < original.get(0, 10).x);
+    assert!(image.get(0, 10).x > image.get(0, 0).x);
+}
+
+#[test]
+fn bloom_spreads_a_bright_pixel_to_its_neighbors() {
+    let mut image = gray_image(41, 41, 0.1);
+    *image.get_mut(20, 20) = Color::repeat(50.0);
+    let original = image.clone();
+    // The widest blur has a sigma of 4 pixels and reaches 12 pixels away
+    apply_bloom(&mut image, 1.0, 0.5, 1.0);
+
+    // The glow fades away from the bright pixel, and the dim pixels far from it are left alone
+    let glow = |i: u32, j: u32| image.get(i, j).x - original.get(i, j).x;
+    assert!(glow(21, 20) > 0.1);
+    assert!((glow(20, 21) - glow(21, 20)).abs() < 1e-12);
+    assert!(glow(22, 20) < glow(21, 20));
+    assert!(glow(21, 21) < glow(21, 20));
+    assert_eq!(glow(0, 0), 0.0);
+}
+
+#[test]
+fn bloom_of_radius_0_does_not_blur() {
+    let mut image = gray_image(5, 5, 0.1);
+    *image.get_mut(2, 2) = Color::repeat(3.0);
+    let original = image.clone();
+    apply_bloom(&mut image, 1.0, 0.5, 0.0);
+
+    // Only the bright pixel gets its glow, added in place
+    assert!(image.as_slice().iter().all(|color| color.iter().all(|x| x.is_finite())));
+    assert!((image.get(2, 2).x - (3.0 + 0.5 * 2.0)).abs() < 1e-12);
+    for (pixel, original) in image.as_slice().iter().zip(original.as_slice()).filter(|(_, o)| o.x < 1.0) {
+        assert_eq!(pixel, original);
+    }
+}
@@ -0,0 +1,85 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::material::MaterialId;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+mod common;
+
+/// Without the epsilon at the start, so that only the nudge of the scattered rays keeps them off their surface
+fn ray(origin: Rvec3, direction: Rvec3) -> Ray {
+    Ray {t_min: 0.0, ..common::ray(origin, direction)}
+}
+
+#[test]
+fn small_scene_does_not_leak_through_thin_gaps() {
+    // A ball of 1 mm, modeled in meters, with a smaller one hovering 0.2 mm above its top
+    let scene_data = SceneData::empty();
+    let ball = Hittable::Sphere {center: vector![0.0, 0.0, 0.0], radius: 1e-3, material: MaterialId(0)};
+    let blocker = Hittable::Sphere {center: vector![0.0, 1.3e-3, 0.0], radius: 1e-4, material: MaterialId(1)};
+    let scene = Hittable::List(vec![ball, blocker]);
+
+    // Looking down from inside the gap, then bouncing straight up
+    let view = ray(vector![0.0, 1.1e-3, 0.0], vector![0.0, -1.0, 0.0]);
+    let (hit, material) = scene.hit(&view, &scene_data).unwrap();
+    assert_eq!(material.to_index(), 0);
+    let up = view.scatter(&hit, vector![0.0, 1.0, 0.0]);
+    assert!((up.origin - hit.position).norm() < 1e-6);
+    let (blocked, material) = scene.hit(&up, &scene_data).unwrap();
+    assert_eq!(material.to_index(), 1);
+    assert!((blocked.t - 2e-4).abs() < 1e-6);
+
+    // A fixed nudge of RAY_EPSILON would start past the blocker, and the light would leak under it
+    assert!(scene.hit(&Ray {origin: hit.position, t_min: RAY_EPSILON, ..up}, &scene_data).is_none());
+}
+
+#[test]
+fn huge_scene_has_no_acne() {
+    // A ball the size of a planet, measured in millimeters
+    let scene_data = SceneData::empty();
+    let center = vector![3e11, 2e11, 3e12];
+    let ball = Hittable::Sphere {center, radius: 1e12, material: MaterialId(0)};
+    let mut rng = Randomizer::seed_from_u64(3);
+    let mut fixed_acne = 0;
+    for _ in 0..1000 {
+        let view = ray(Rvec3::zeros(), center + 0.9e12 * rng.sample(UnitSphere));
+        let (hit, _) = ball.hit(&view, &scene_data).unwrap();
+        // Any ray leaving the outside of a sphere can only hit it again by mistake
+        let mut direction = rng.sample(UnitSphere).normalize();
+        if direction.dot(&hit.normal) < 0.0 {
+            direction = -direction;
+        }
+        let bounce = view.scatter(&hit, direction);
+        assert!(ball.hit(&bounce, &scene_data).is_none());
+        if ball.hit(&Ray {origin: hit.position, t_min: RAY_EPSILON, ..bounce}, &scene_data).is_some() {
+            fixed_acne += 1;
+        }
+    }
+    // The fixed nudge is lost in the rounding errors of such big coordinates
+    assert!(fixed_acne > 0);
+}
+
+#[test]
+fn grazing_rays_leave_a_sphere_cleanly() {
+    let scene_data = SceneData::empty();
+    let center = vector![0.3, 0.2, 3.0];
+    let ball = Hittable::Sphere {center, radius: 1.0, material: MaterialId(0)};
+    let mut rng = Randomizer::seed_from_u64(3);
+    let mut acne_along_the_ray = 0;
+    for _ in 0..1000 {
+        let view = ray(Rvec3::zeros(), center + 0.9 * rng.sample(UnitSphere));
+        let (hit, _) = ball.hit(&view, &scene_data).unwrap();
+        // Light that barely rises above the surface
+        let tangent = tangent_along(&hit.normal, &rng.sample(UnitSphere));
+        let direction = (tangent + 1e-10 * hit.normal).normalize();
+        let bounce = view.scatter(&hit, direction);
+        assert!(ball.hit(&bounce, &scene_data).is_none());
+
+        // The same nudge along the ray barely moves it away from the surface, which it may still be under
+        let along_the_ray = Ray {origin: hit.position, t_min: hit.ray_epsilon(), ..bounce};
+        if ball.hit(&along_the_ray, &scene_data).is_some() {
+            acne_along_the_ray += 1;
+        }
+    }
+    assert!(acne_along_the_ray > 0);
+}
@@ -0,0 +1,107 @@
+use raytracing2::utility::*;
+use raytracing2::randomness::*;
+
+const TOLERANCE: Real = 1e-12;
+const NUM_CASES: usize = 1000;
+
+/// Random unit incident directions and the unit normals they arrive against (incident . normal < 0)
+fn incidence_cases(seed: u64) -> Vec<(Rvec3, Rvec3)> {
+    let mut rng = Randomizer::seed_from_u64(seed);
+    (0..NUM_CASES).map(|_| {
+        let normal: Rvec3 = rng.sample(UnitSphere);
+        let mut incident: Rvec3 = rng.sample(UnitSphere);
+        if incident.dot(&normal) > 0.0 {
+            incident = -incident;
+        }
+        (incident, normal)
+    }).collect()
+}
+
+/// The same normal and an incident direction in the plane (normal, tangent) at theta from the inward normal
+fn incident_at(theta: Real, normal: &Rvec3) -> Rvec3 {
+    let (tangent, _) = orthonormal_basis(normal);
+    theta.sin() * tangent - theta.cos() * normal
+}
+
+fn sin_to_normal(direction: &Rvec3, normal: &Rvec3) -> Real {
+    direction.cross(normal).norm()
+}
+
+#[test]
+fn reflect_keeps_tangential_and_flips_normal_component() {
+    for (incident, normal) in incidence_cases(1) {
+        let reflected = reflect(&incident, &normal);
+        let tangential = |v: &Rvec3| v - v.dot(&normal) * normal;
+        assert!((tangential(&reflected) - tangential(&incident)).norm() < TOLERANCE);
+        assert!((reflected.dot(&normal) + incident.dot(&normal)).abs() < TOLERANCE);
+    }
+}
+
+#[test]
+fn reflect_returns_unit_vectors() {
+    for (incident, normal) in incidence_cases(2) {
+        assert!((reflect(&incident, &normal).norm() - 1.0).abs() < TOLERANCE);
+    }
+}
+
+#[test]
+fn refract_follows_snell_law() {
+    let normal = vector![0.0, 0.0, 1.0];
+    for &eta in &[1.0 / 1.5, 1.0 / 1.33, 1.0, 1.33, 1.5, 2.4] {
+        for k in 0..90 {
+            let theta = (k as Real).to_radians();
+            let incident = incident_at(theta, &normal);
+            // Entering a denser medium never fails, leaving it fails past the critical angle
+            let refracted = match refract(&incident, &normal, eta) {
+                Some(refracted) => refracted,
+                None => {
+                    assert!(eta * theta.sin() > 1.0, "no refraction at {} degrees with eta = {}", k, eta);
+                    continue
+                }
+            };
+            // sin(theta_t) = eta * sin(theta_i), the ray goes through and stays in the plane of incidence
+            let sin_t = sin_to_normal(&refracted, &normal);
+            assert!((sin_t - eta * theta.sin()).abs() < 1e-9, "eta = {}, theta = {} degrees", eta, k);
+            assert!(refracted.dot(&normal) < 0.0);
+            assert!(refracted.dot(&incident.cross(&normal)).abs() < TOLERANCE);
+        }
+    }
+}
+
+#[test]
+fn refract_returns_none_past_the_critical_angle() {
+    let normal = vector![0.0, 1.0, 0.0];
+    for &eta in &[1.33, 1.5, 2.4 as Real] {
+        let critical = (1.0 / eta).asin();
+        assert!(refract(&incident_at(critical - 1e-6, &normal), &normal, eta).is_some());
+        assert!(refract(&incident_at(critical + 1e-6, &normal), &normal, eta).is_none());
+        assert!(refract(&incident_at(0.5 * (critical + FRAC_PI_2), &normal), &normal, eta).is_none());
+    }
+}
+
+#[test]
+fn refract_returns_none_at_the_critical_angle() {
+    // With eta = 2, the critical angle is 30 degrees and all the terms are exact
+    let normal = vector![0.0, 1.0, 0.0];
+    let incident = vector![0.5, -(0.75 as Real).sqrt(), 0.0];
+    assert!(refract(&incident, &normal, 2.0).is_none());
+}
+
+#[test]
+fn refract_returns_unit_vectors() {
+    for &eta in &[1.0 / 1.5, 1.0, 1.5] {
+        for (incident, normal) in incidence_cases(3) {
+            if let Some(refracted) = refract(&incident, &normal, eta) {
+                assert!((refracted.norm() - 1.0).abs() < 1e-9);
+            }
+        }
+    }
+}
+
+#[test]
+fn refract_with_eta_one_goes_straight_through() {
+    for (incident, normal) in incidence_cases(4) {
+        let refracted = refract(&incident, &normal, 1.0).unwrap();
+        assert!((refracted - incident).norm() < 1e-9);
+    }
+}
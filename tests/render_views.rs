@@ -0,0 +1,34 @@
+use raytracing2::example_scenes;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+#[test]
+fn each_camera_gets_its_own_image() {
+    let mut scene = example_scenes::three_balls();
+    scene.camera.aspect_ratio = 1.5;
+    let params = RenderParams {
+        width: 24,
+        height: 16,
+        num_samples: 4,
+        filter: Filter::Tent {radius: 1.0},
+        max_bounce: 4,
+        tile_size: 8,
+        num_threads: 2,
+        seed: 3,
+        ..RenderParams::default()
+    };
+    let mut turned = scene.camera.clone();
+    turned.transformation = Transformation::lookat(
+        &vector![3.0, 2.0, 4.0],
+        &vector![0.0, 0.0, 0.0],
+        &vector![0.0, 1.0, 0.0]
+    );
+    let cameras = [scene.camera.clone(), scene.camera.clone(), turned];
+    let views = render_views(&mut scene, &cameras, &params).unwrap();
+
+    assert_eq!(views.len(), 3);
+    assert_eq!(views[0].as_slice(), views[1].as_slice());
+    assert_ne!(views[0].as_slice(), views[2].as_slice());
+    // The same as rendering the scene alone, now that its Bvhs are built
+    assert_eq!(views[0].as_slice(), render_image(&scene, &params).unwrap().color.as_slice());
+}
@@ -0,0 +1,90 @@
+use raytracing2::material::*;
+use raytracing2::randomness::*;
+use raytracing2::utility::*;
+
+/// A hit on the plane z = 0 facing up, seen from above along the direction
+fn incident_and_hit(direction: Rvec3) -> (Ray, Hit) {
+    let ray = Ray {
+        origin: vector![0.0, 0.0, 1.0],
+        direction: direction.normalize(),
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let hit = Hit {
+        t: 1.0,
+        position: Rvec3::zeros(),
+        normal: vector![0.0, 0.0, 1.0],
+        geometric_normal: vector![0.0, 0.0, 1.0],
+        uv: Rvec2::zeros(),
+        tangent: vector![1.0, 0.0, 0.0],
+        color: WHITE,
+        differentials: None,
+    };
+    (ray, hit)
+}
+
+/// Monte Carlo integral of the pdf over the whole sphere of directions, sampled uniformly
+fn integrate(scatter: &Scatter, incident: &Ray, hit: &Hit) -> Real {
+    const N: usize = 200_000;
+    let mut rng = Randomizer::seed_from_u64(5);
+    let sum: Real = (0..N).map(|_| scatter.pdf(incident, hit, &rng.sample(UnitSphere))).sum();
+    4.0 * PI * sum / N as Real
+}
+
+#[test]
+fn lambert_pdf_integrates_to_one() {
+    for direction in [vector![0.0, 0.0, -1.0], vector![1.0, 0.5, -0.3]] {
+        let (incident, hit) = incident_and_hit(direction);
+        let integral = integrate(&Scatter::Lambert {two_sided: false}, &incident, &hit);
+        assert!((integral - 1.0).abs() < 0.01, "{}", integral);
+        // Nothing below the surface
+        assert_eq!(Scatter::Lambert {two_sided: false}.pdf(&incident, &hit, &vector![0.0, 0.6, -0.8]), 0.0);
+        assert_eq!(Scatter::Lambert {two_sided: false}.pdf(&incident, &hit, &vector![0.0, 0.0, 1.0]), 1.0 / PI);
+    }
+}
+
+#[test]
+fn ggx_pdf_integrates_to_the_reflected_fraction() {
+    // Some of the rays reflected by the visible microfacets go below the surface and are lost
+    let (incident, hit) = incident_and_hit(vector![0.0, 0.0, -1.0]);
+    let integral = integrate(&Scatter::Ggx {roughness: 0.5}, &incident, &hit);
+    assert!(integral > 0.9 && integral < 1.0, "{}", integral);
+    // Sharper around the mirror direction when smoother
+    let mirror = vector![0.0, 0.0, 1.0];
+    let smooth = Scatter::Ggx {roughness: 0.2}.pdf(&incident, &hit, &mirror);
+    assert!(smooth > Scatter::Ggx {roughness: 0.5}.pdf(&incident, &hit, &mirror));
+}
+
+#[test]
+fn specular_pdf_is_zero() {
+    let (incident, hit) = incident_and_hit(vector![1.0, 0.0, -1.0]);
+    let mirror = vector![1.0, 0.0, 1.0].normalize();
+    for scatter in [
+        Scatter::Metal {fuzziness: 0.0},
+        Scatter::Ggx {roughness: 0.0},
+        Scatter::Dielectric {refraction_index: 1.5, abbe_number: None},
+        Scatter::ThinDielectric {refraction_index: 1.5},
+        Scatter::None,
+    ] {
+        assert_eq!(scatter.pdf(&incident, &hit, &mirror), 0.0, "{:?}", scatter);
+    }
+}
+
+#[test]
+fn vndf_samples_are_visible_microfacets() {
+    let mut rng = Randomizer::seed_from_u64(11);
+    for alpha in [0.05, 0.3, 1.0] {
+        // From straight above down to a grazing view
+        for theta in [0.0, 0.5, 1.2, 1.55] {
+            let view = vector![(theta as Real).sin(), 0.0, (theta as Real).cos()];
+            for _ in 0..10_000 {
+                let normal = sample_ggx_vndf(&view, alpha, &mut rng);
+                assert!((normal.norm() - 1.0).abs() < 1e-9);
+                assert!(normal.z >= 0.0, "{:?} below the surface", normal);
+                assert!(normal.dot(&view) >= -1e-9, "{:?} facing away from {:?}", normal, view);
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::render::Camera;
+use raytracing2::texture::TextureId;
+use raytracing2::utility::*;
+
+fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(value: &T) -> T {
+    let json = serde_json::to_string(value).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn material_survives_a_json_round_trip() {
+    let material = Material::new(
+        Scatter::Dielectric {refraction_index: 1.5, abbe_number: Some(40.0)},
+        Absorb::Albedo(rgb(0.9, 0.8, 0.7)),
+        Emit::SkySphere {texture: TextureId(2), yaw: 0.5, pitch: -0.25},
+    )
+        .with_clearcoat(Clearcoat {refraction_index: 1.4, roughness: 0.1})
+        .with_bump(Bump {texture: TextureId(3), strength: 0.02});
+    // Debug prints every field, including the private ones
+    assert_eq!(format!("{:?}", round_trip(&material)), format!("{:?}", material));
+}
+
+#[test]
+fn camera_recomputes_its_normal_matrix() {
+    let camera = Camera {
+        aspect_ratio: 16.0 / 9.0,
+        fov: FRAC_PI_3,
+        focus_distance: 4.0,
+        lens_radius: 0.1,
+        chromatic_aberration: 0.0,
+        transformation: Transformation::from_trs(
+            &vector![1.0, 2.0, 3.0],
+            &Rotation::Euler {x: 0.1, y: 0.2, z: 0.3},
+            &vector![1.0, 2.0, 0.5]
+        ),
+    };
+    let json = serde_json::to_string(&camera).unwrap();
+    assert!(!json.contains("normal_matrix"));
+    let loaded: Camera = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{:?}", loaded), format!("{:?}", camera));
+
+    // The scenes saved before the rename still load
+    let old_json = json.replace("focus_distance", "focal_dist");
+    let loaded: Camera = serde_json::from_str(&old_json).unwrap();
+    assert_eq!(loaded.focus_distance, 4.0);
+}
+
+#[test]
+fn hittable_descriptors_round_trip_but_not_the_bvhs() {
+    let root = Hittable::List(vec![
+        Hittable::Sphere {center: vector![0.0, 1.0, 0.0], radius: 1.0, material: MaterialId(0)},
+        Hittable::RotateY {
+            angle: FRAC_PI_4,
+            child: Box::new(Hittable::Translate {
+                offset: vector![2.0, 0.0, 0.0],
+                child: Box::new(Hittable::Capsule {
+                    a: vector![0.0, 0.0, 0.0], b: vector![0.0, 1.0, 0.0], radius: 0.2, material: MaterialId(1)
+                }),
+            }),
+        },
+    ]);
+    let json = serde_json::to_string(&root).unwrap();
+    let loaded: Hittable = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+
+    let scene_data = raytracing2::render::SceneData::empty();
+    let list = Hittable::List(vec![root.clone(); raytracing2::hittable::BVH_THRESHOLD + 1]);
+    assert!(serde_json::to_string(&list.into_bvh(&scene_data)).is_err());
+}
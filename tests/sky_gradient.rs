@@ -0,0 +1,39 @@
+use raytracing2::material::Emit;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+fn sky_color(sky: &Emit, direction: Rvec3) -> Color {
+    let scene_data = SceneData::empty();
+    let ray = Ray {
+        origin: Rvec3::zeros(),
+        direction,
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let mut rng = Randomizer::seed_from_u64(1);
+    sky.evaluate(&ray, &Hit::at_infinity(&direction.normalize()), &scene_data, &mut rng)
+}
+
+#[test]
+fn sky_gradient_goes_through_the_horizon_to_the_ground() {
+    let (top, horizon, bottom) = (rgb(0.2, 0.4, 1.0), rgb(0.9, 0.9, 0.9), rgb(0.3, 0.2, 0.1));
+    let sky = Emit::SkyGradient {top, horizon, bottom};
+    assert_eq!(sky_color(&sky, vector![0.0, 2.0, 0.0]), top);
+    assert_eq!(sky_color(&sky, vector![1.0, 0.0, 1.0]), horizon);
+    assert_eq!(sky_color(&sky, vector![0.0, -0.5, 0.0]), bottom);
+
+    // Halfway down, halfway between the horizon and the ground
+    let halfway = sky_color(&sky, vector![3.0_f64.sqrt(), -1.0, 0.0]);
+    assert!((halfway - 0.5 * (horizon + bottom)).norm() < 1e-12);
+}
+
+#[test]
+fn two_color_sky_is_linear_from_bottom_to_top() {
+    let sky = Emit::sky_gradient(WHITE, rgb(0.5, 0.7, 1.0));
+    assert_eq!(sky_color(&sky, vector![0.0, 1.0, 0.0]), rgb(0.5, 0.7, 1.0));
+    assert_eq!(sky_color(&sky, vector![0.0, 0.0, -1.0]), rgb(0.75, 0.85, 1.0));
+    assert_eq!(sky_color(&sky, vector![0.0, -1.0, 0.0]), WHITE);
+}
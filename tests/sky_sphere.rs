@@ -0,0 +1,46 @@
+use raytracing2::image::Array2d;
+use raytracing2::material::Emit;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::texture::*;
+use raytracing2::utility::*;
+
+const SUN: Color = Color::new(50.0, 40.0, 30.0);
+
+/// A dark 8x4 panorama with one bright texel
+fn scene_data() -> SceneData {
+    let mut image = Array2d::new(8, 4);
+    *image.get_mut(5, 2) = SUN;
+    let texture = Texture::HdrImage {image, wrap_u: WrapMode::Repeat, wrap_v: WrapMode::Clamp};
+    SceneData {texture_table: vec![texture], ..SceneData::empty()}
+}
+
+fn sky_color(sky: &Emit, scene_data: &SceneData, direction: Rvec3) -> Color {
+    let ray = Ray {
+        origin: Rvec3::zeros(),
+        direction,
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let mut rng = Randomizer::seed_from_u64(1);
+    sky.evaluate(&ray, &Hit::at_infinity(&direction), scene_data, &mut rng)
+}
+
+#[test]
+fn yaw_of_90_degrees_moves_the_sun_a_quarter_turn() {
+    let scene_data = scene_data();
+    // The direction looking at the center of the bright texel, whose uv is (5.5 / 8, 2.5 / 4)
+    let longitude = TAU * (0.5 - 5.5 / 8.0);
+    let latitude = PI * (2.5 / 4.0 - 0.5);
+    let sun = vector![latitude.cos() * longitude.cos(), latitude.sin(), latitude.cos() * longitude.sin()];
+    let still = Emit::SkySphere {texture: TextureId(0), yaw: 0.0, pitch: 0.0};
+    assert_eq!(sky_color(&still, &scene_data, sun), SUN);
+
+    // Turning around the up axis by a quarter turn takes +X to -Z
+    let turned = Emit::SkySphere {texture: TextureId(0), yaw: FRAC_PI_2, pitch: 0.0};
+    let turned_sun = vector![sun.z, sun.y, -sun.x];
+    assert!((sky_color(&turned, &scene_data, turned_sun) - SUN).norm() < 1e-9);
+    assert_eq!(sky_color(&turned, &scene_data, sun), Color::zeros());
+}
@@ -0,0 +1,197 @@
+use raytracing2::image::Array2d;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::texture::*;
+use raytracing2::utility::*;
+
+fn sample(texture: Texture, uv: Rvec2) -> Color {
+    sample_at(texture, uv, Rvec3::zeros())
+}
+
+fn sample_at(texture: Texture, uv: Rvec2, position: Rvec3) -> Color {
+    let scene_data = SceneData::empty();
+    let ray = Ray {
+        origin: vector![0.0, 0.0, -1.0],
+        direction: vector![0.0, 0.0, 1.0],
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let hit = Hit {
+        t: 1.0,
+        position,
+        normal: vector![0.0, 0.0, -1.0],
+        geometric_normal: vector![0.0, 0.0, -1.0],
+        uv,
+        tangent: vector![1.0, 0.0, 0.0],
+        color: WHITE,
+        differentials: None,
+    };
+    texture.sample(&ray, &hit, &scene_data, &mut Randomizer::seed_from_u64(0))
+}
+
+#[test]
+fn float_texture_returns_its_values_unchanged() {
+    let texels = vec![rgb(3.5, 1.0, 0.123456789), rgb(-0.5, 0.0, 12.0)];
+    let image = Array2d::from_raw(2, 1, texels.clone()).unwrap();
+    let texture = || Texture::HdrImage {image: image.clone(), wrap_u: WrapMode::Clamp, wrap_v: WrapMode::Clamp};
+    // Above 1, below 0, and finer than 8 bits: no tone curve, no clamping, no quantization
+    assert_eq!(sample(texture(), vector![0.25, 0.5]), texels[0]);
+    assert_eq!(sample(texture(), vector![0.75, 0.5]), texels[1]);
+}
+
+#[test]
+fn wrap_modes_outside_of_the_unit_square() {
+    // The texel (i, j) of a 4x4 image holds (i, j, 0)
+    let texels = (0..16).map(|k| rgb((k % 4) as Real, (k / 4) as Real, 0.0)).collect();
+    let image = Array2d::from_raw(4, 4, texels).unwrap();
+    let texel_at = |wrap_u, wrap_v, uv| {
+        let color = sample(Texture::HdrImage {image: image.clone(), wrap_u, wrap_v}, uv);
+        (color.x as u32, color.y as u32)
+    };
+
+    // The texel index seen at -0.25 and at 1.25 under each mode
+    for (mode, below, above) in [(WrapMode::Repeat, 3, 1), (WrapMode::Clamp, 0, 3), (WrapMode::Mirror, 1, 3)] {
+        assert_eq!(texel_at(mode, WrapMode::Clamp, vector![-0.25, 0.6]), (below, 2), "{:?}", mode);
+        assert_eq!(texel_at(mode, WrapMode::Clamp, vector![1.25, 0.6]), (above, 2), "{:?}", mode);
+        // The other axis is wrapped on its own
+        assert_eq!(texel_at(WrapMode::Clamp, mode, vector![0.6, -0.25]), (2, below), "{:?}", mode);
+        assert_eq!(texel_at(WrapMode::Clamp, mode, vector![0.6, 1.25]), (2, above), "{:?}", mode);
+    }
+}
+
+#[test]
+fn byte_texture_is_linear_too() {
+    let image = Array2d::from_raw(1, 1, vec![[51, 128, 255, 255]]).unwrap();
+    let texture = Texture::Image {image, wrap_u: WrapMode::Clamp, wrap_v: WrapMode::Clamp, mips: None};
+    assert_eq!(sample(texture, vector![0.5, 0.5]), rgb(51.0, 128.0, 255.0) / 255.0);
+}
+
+#[test]
+fn uv_checker_follows_the_parameterization_of_a_quad() {
+    use raytracing2::bvh::TriangleBvh;
+    use raytracing2::hittable::Hittable;
+    use raytracing2::material::MaterialId;
+    use raytracing2::mesh::*;
+
+    // A tilted rectangle, 3 units long for u and 1.5 for v
+    let corner = vector![0.3, 0.2, 0.0];
+    let (edge_u, edge_v) = (vector![3.0, 0.0, 1.0], vector![0.0, 1.5, 0.0]);
+    let normal = edge_u.cross(&edge_v).normalize();
+    let vertices = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].iter()
+        .map(|&(u, v)| Vertex {position: corner + u * edge_u + v * edge_v, normal, uv: vector![u, v], color: WHITE})
+        .collect();
+    let quad = Mesh::new(vertices, vec![0, 1, 2, 0, 2, 3], MaterialId(0));
+    let checker = |space| Texture::Checker {odd: TextureId(0), even: TextureId(1), space};
+    let scene_data = SceneData {
+        texture_table: vec![
+            Texture::Solid(rgb(0.0, 0.0, 0.0)),
+            Texture::Solid(rgb(1.0, 1.0, 1.0)),
+            checker(TextureSpace::Uv {scale: 4.0}),
+            checker(TextureSpace::Position),
+        ],
+        mesh_table: vec![quad],
+        ..SceneData::empty()
+    };
+    let root = Hittable::TriangleBvh(TriangleBvh::from_mesh(MeshId(0), &scene_data));
+
+    let mut differs_from_position = false;
+    for j in 0..8 {
+        for i in 0..8 {
+            // Inside the cells of the checker, away from their edges
+            let (u, v) = ((i as Real + 0.3) / 8.0, (j as Real + 0.6) / 8.0);
+            let target = corner + u * edge_u + v * edge_v;
+            let ray = Ray {
+                origin: target + 2.0 * normal,
+                direction: -normal,
+                t_min: RAY_EPSILON,
+                t_max: Real::INFINITY,
+                wavelength: None,
+                differentials: None,
+            };
+            let (hit, _) = root.hit(&ray, &scene_data).unwrap();
+            let mut rng = Randomizer::seed_from_u64(0);
+            let color = scene_data.texture(TextureId(2)).sample(&ray, &hit, &scene_data, &mut rng);
+            // 4 cells along each side of the quad, however long it is in the world
+            let even = (i / 2 + j / 2) % 2 == 0;
+            assert_eq!(color, if even {rgb(1.0, 1.0, 1.0)} else {rgb(0.0, 0.0, 0.0)}, "u {} v {}", u, v);
+
+            let in_space = scene_data.texture(TextureId(3)).sample(&ray, &hit, &scene_data, &mut rng);
+            differs_from_position |= in_space != color;
+        }
+    }
+    assert!(differs_from_position);
+}
+
+#[test]
+fn worley_distance_vanishes_at_the_feature_points() {
+    let worley = |feature, p: Rvec3| {
+        sample_at(Texture::Worley {seed: 4, scale: 2.0, feature, space: TextureSpace::Position}, Rvec2::zeros(), p).x
+    };
+    for &(x, y, z) in [(0, 0, 0), (3, -2, 7), (-5, -5, 1)].iter() {
+        // The point is in cells, and the texture has 2 cells per unit
+        let point = worley_feature_point(x, y, z, 4);
+        assert!((point - vector![x as Real, y as Real, z as Real]).iter().all(|&d| (0.0..1.0).contains(&d)));
+        assert!(worley(WorleyFeature::F1, point / 2.0) < 1e-12);
+        let f2 = worley(WorleyFeature::F2, point / 2.0);
+        assert!(f2 > 0.0 && f2 < 3.0);
+        assert_eq!(worley(WorleyFeature::Edge, point / 2.0), f2);
+    }
+    // Halfway between two feature points, the edge is at least as close as them
+    let a = worley_feature_point(0, 0, 0, 4);
+    let b = worley_feature_point(1, 0, 0, 4);
+    let middle = 0.5 * (a + b) / 2.0;
+    assert!(worley(WorleyFeature::F1, middle) <= 0.5 * (a - b).norm() + 1e-12);
+    assert!(worley(WorleyFeature::Edge, middle) < worley(WorleyFeature::Edge, a / 2.0));
+}
+
+#[test]
+fn uv_grid_draws_lines_on_the_cell_boundaries() {
+    let (line_color, bg_color) = (rgb(0.05, 0.05, 0.05), rgb(0.9, 0.8, 1.0));
+    let grid = || Texture::UvGrid {cells: 4, line_color, bg_color};
+    // On the boundaries between the cells, along u and along v
+    assert_eq!(sample(grid(), vector![0.25, 0.6]), line_color);
+    assert_eq!(sample(grid(), vector![0.4, 0.751]), line_color);
+
+    // Inside the cell (1, 2), shaded by the gradient across the cells
+    let inside = sample(grid(), vector![0.375, 0.625]);
+    assert!((inside - bg_color.component_mul(&rgb(0.775, 0.85, 0.7))).norm() < 1e-12);
+    assert_ne!(inside, sample(grid(), vector![0.625, 0.625]));
+}
+
+#[test]
+fn blend_goes_from_a_to_b_with_the_factor() {
+    let (a, b) = (rgb(0.9, 0.1, 0.2), rgb(0.0, 0.5, 1.0));
+    let blend = |factor: Color| {
+        let scene_data = SceneData {
+            texture_table: vec![Texture::Solid(a), Texture::Solid(b), Texture::Solid(factor)],
+            ..SceneData::empty()
+        };
+        let texture = Texture::Blend {a: TextureId(0), b: TextureId(1), factor: TextureId(2)};
+        let ray = Ray {
+            origin: vector![0.0, 0.0, -1.0],
+            direction: vector![0.0, 0.0, 1.0],
+            t_min: RAY_EPSILON,
+            t_max: Real::INFINITY,
+            wavelength: None,
+            differentials: None,
+        };
+        texture.sample(&ray, &Hit::at_infinity(&ray.direction), &scene_data, &mut Randomizer::seed_from_u64(0))
+    };
+    assert_eq!(blend(Color::zeros()), a);
+    assert_eq!(blend(WHITE), b);
+    // Halfway for a mid gray factor
+    assert!((blend(rgb(0.5, 0.5, 0.5)) - 0.5 * (a + b)).norm() < 1e-12);
+}
+
+#[test]
+fn mip_chain_goes_down_to_a_single_texel_and_stops_on_empty_images() {
+    assert_eq!(MipChain::new(&Array2d::new(8, 2), 1.0).num_levels(), 3);
+    assert_eq!(MipChain::new(&Array2d::new(5, 5), 1.0).num_levels(), 3);
+    assert_eq!(MipChain::new(&Array2d::new(1, 1), 1.0).num_levels(), 0);
+    // An empty image is halved until its other side is 1
+    assert_eq!(MipChain::new(&Array2d::new(0, 4), 1.0).num_levels(), 2);
+    assert_eq!(MipChain::new(&Array2d::new(3, 0), 1.0).num_levels(), 2);
+    assert_eq!(MipChain::new(&Array2d::new(0, 0), 1.0).num_levels(), 0);
+}
@@ -0,0 +1,114 @@
+use raytracing2::image::tga;
+
+/// Write the bytes to a file of the temporary directory and load it
+fn load(name: &str, bytes: &[u8]) -> Result<raytracing2::image::Array2d<[u8; 4]>, Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("raytracing2_{}_{}.tga", name, std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    let image = tga::load(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    image
+}
+
+/// An 18 bytes header for an uncompressed image. The first row of pixels is at the bottom, which is the default of
+/// the format and also row 0 of the loaded array.
+fn header(datatype_code: u8, width: u16, height: u16, bits_per_pixel: u8, alpha_bits: u8) -> Vec<u8> {
+    let mut header = vec![0, 0, datatype_code, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    header.extend(width.to_le_bytes());
+    header.extend(height.to_le_bytes());
+    header.extend([bits_per_pixel, alpha_bits]);
+    header
+}
+
+#[test]
+fn grayscale_is_copied_into_the_three_channels() {
+    let mut bytes = header(3, 2, 2, 8, 0);
+    bytes.extend([0, 64, 200, 255]);
+    let image = load("gray", &bytes).unwrap();
+    assert_eq!(*image.get(0, 0), [0, 0, 0, 255]);
+    assert_eq!(*image.get(1, 0), [64, 64, 64, 255]);
+    assert_eq!(*image.get(0, 1), [200, 200, 200, 255]);
+    assert_eq!(*image.get(1, 1), [255, 255, 255, 255]);
+
+    // With an alpha byte after each gray one
+    let mut bytes = header(3, 1, 1, 16, 8);
+    bytes.extend([100, 30]);
+    assert_eq!(*load("gray_alpha", &bytes).unwrap().get(0, 0), [100, 100, 100, 30]);
+}
+
+#[test]
+fn sixteen_bits_pixels_are_stretched_to_eight_bits_per_channel() {
+    let mut bytes = header(2, 4, 1, 16, 0);
+    for pixel in [0x7c00u16, 0x03e0, 0x001f, 0x4210] {
+        bytes.extend(pixel.to_le_bytes());
+    }
+    let image = load("rgb555", &bytes).unwrap();
+    assert_eq!(*image.get(0, 0), [255, 0, 0, 255]);
+    assert_eq!(*image.get(1, 0), [0, 255, 0, 255]);
+    assert_eq!(*image.get(2, 0), [0, 0, 255, 255]);
+    // 16 out of 31 is a bit more than half
+    assert_eq!(*image.get(3, 0), [132, 132, 132, 255]);
+
+    // The top bit is the alpha when the descriptor says so
+    let mut bytes = header(2, 2, 1, 16, 1);
+    for pixel in [0x7fffu16, 0xffff] {
+        bytes.extend(pixel.to_le_bytes());
+    }
+    let image = load("argb1555", &bytes).unwrap();
+    assert_eq!(*image.get(0, 0), [255, 255, 255, 0]);
+    assert_eq!(*image.get(1, 0), [255, 255, 255, 255]);
+}
+
+#[test]
+fn bgr_and_bgra_pixels_load_the_same_with_either_origin() {
+    // A 3x2 image, with the first row at the bottom
+    let rgb = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [10, 20, 30], [40, 50, 60], [70, 80, 90]];
+    let mut bgr = header(2, 3, 2, 24, 0);
+    let mut bgra = header(2, 3, 2, 32, 8);
+    for [r, g, b] in rgb {
+        bgr.extend([b, g, r]);
+        bgra.extend([b, g, r, 255]);
+    }
+    let from_bgr = load("bgr", &bgr).unwrap();
+    let from_bgra = load("bgra", &bgra).unwrap();
+    assert_eq!(from_bgr.as_slice(), from_bgra.as_slice());
+    assert_eq!(*from_bgr.get(1, 0), [0, 255, 0, 255]);
+    assert_eq!(*from_bgr.get(2, 1), [70, 80, 90, 255]);
+
+    // Bit 5 of the descriptor puts the first row at the top, which flips the loaded array
+    bgr[17] |= 1 << 5;
+    bgra[17] |= 1 << 5;
+    let flipped_bgr = load("bgr_top", &bgr).unwrap();
+    let flipped_bgra = load("bgra_top", &bgra).unwrap();
+    assert_eq!(flipped_bgr.as_slice(), flipped_bgra.as_slice());
+    assert_eq!(flipped_bgr.row(0), from_bgr.row(1));
+    assert_eq!(flipped_bgr.row(1), from_bgr.row(0));
+}
+
+#[test]
+fn compressed_and_odd_formats_are_rejected() {
+    // Run length encoded color, and grayscale with 24 bits per pixel
+    assert!(load("rle", &header(10, 1, 1, 24, 0)).is_err());
+    assert!(load("gray24", &header(3, 1, 1, 24, 0)).is_err());
+    // Fewer pixels than the header says
+    let mut bytes = header(3, 2, 2, 8, 0);
+    bytes.extend([1, 2, 3]);
+    assert!(load("truncated", &bytes).is_err());
+}
+
+#[test]
+fn saved_header_has_little_endian_fields_and_loads_back() {
+    let mut image = raytracing2::image::Array2d::new(258, 3);
+    *image.get_mut(257, 2) = [1, 2, 3, 4];
+    let path = std::env::temp_dir().join(format!("raytracing2_saved_{}.tga", std::process::id()));
+    let path = path.to_str().unwrap();
+    tga::save(&image, path).unwrap();
+    let bytes = std::fs::read(path).unwrap();
+    let loaded = tga::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    // Uncompressed 32 bits color, 258 = 0x0102 pixels wide and 3 high, with the low byte first
+    assert_eq!(bytes[..18], [0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, 0x01, 3, 0, 32, 0]);
+    assert_eq!(bytes.len(), 18 + 258 * 3 * 4);
+    assert_eq!((loaded.width(), loaded.height()), (258, 3));
+    assert_eq!(loaded.as_slice(), image.as_slice());
+}
@@ -0,0 +1,55 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::light::LightList;
+use raytracing2::material::*;
+use raytracing2::randomness::*;
+use raytracing2::render::*;
+use raytracing2::utility::*;
+
+mod common;
+use common::ray;
+
+// The sky is infinitely bright: any ray that escapes after a black surface turns the color into NaN,
+// because 0 * inf is not a number. The color stays finite only if no ray is cast past the black surface.
+
+fn infinite_sky() -> Emit {
+    Emit::Color(rgb(INFINITY, INFINITY, INFINITY))
+}
+
+fn trace(root: &Hittable, scene_data: &SceneData, ray: &Ray) -> Color {
+    let mut rng = Randomizer::seed_from_u64(1);
+    let lights = LightList::new(root, scene_data);
+    trace_path(root, ray, 8, scene_data, &mut rng, &infinite_sky(), &lights, None, 0.0).final_color
+}
+
+/// A ground so big that it looks flat, and the sky above it
+fn ground(material: MaterialId) -> Hittable {
+    Hittable::Sphere {center: vector![0.0, -1e4, 0.0], radius: 1e4, material}
+}
+
+#[test]
+fn black_surface_casts_no_further_rays() {
+    let data = SceneData::new(vec![
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::BlackBody, Emit::None),
+        Material::new(Scatter::Lambert {two_sided: false}, Absorb::BlackBody, Emit::Color(rgb(0.5, 0.25, 0.125))),
+    ]);
+    let view = ray(vector![0.0, 1.0, 0.0], vector![0.3, -1.0, 0.2]);
+    assert_eq!(trace(&ground(MaterialId(0)), &data, &view), rgb(0.0, 0.0, 0.0));
+    // The emission of the black surface itself is still seen
+    assert_eq!(trace(&ground(MaterialId(1)), &data, &view), rgb(0.5, 0.25, 0.125));
+}
+
+#[test]
+fn path_stops_when_the_absorptions_add_up_to_black() {
+    // A red mirror on the ground, and a green mirror sphere in the way of the reflection.
+    // Each of them lets some light through, but nothing is left after both.
+    let data = SceneData::new(vec![
+        Material::new(Scatter::Metal {fuzziness: 0.0}, Absorb::Albedo(rgb(1.0, 0.0, 0.0)), Emit::None),
+        Material::new(Scatter::Metal {fuzziness: 0.0}, Absorb::Albedo(rgb(0.0, 1.0, 0.0)), Emit::None),
+    ]);
+    let root = Hittable::List(vec![
+        ground(MaterialId(0)),
+        Hittable::Sphere {center: vector![0.0, 1.0, 2.0], radius: 0.5, material: MaterialId(1)},
+    ]);
+    let view = ray(vector![0.0, 1.0, -2.0], vector![0.0, -1.0, 2.0]);
+    assert_eq!(trace(&root, &data, &view), rgb(0.0, 0.0, 0.0));
+}
@@ -0,0 +1,45 @@
+use raytracing2::example_scenes;
+use raytracing2::render::*;
+
+fn num_tiles(width: u32, height: u32, tile_size: u32) -> u32 {
+    width.div_ceil(tile_size) * height.div_ceil(tile_size)
+}
+
+#[test]
+fn there_are_several_tiles_for_each_thread() {
+    let tile_size = auto_tile_size(1920, 1080, 8);
+    assert!(num_tiles(1920, 1080, tile_size) >= 4 * 8);
+    // The largest tiles are enough there, only a small image with many threads needs smaller ones
+    assert_eq!(tile_size, 64);
+    let tile_size = auto_tile_size(320, 240, 16);
+    assert_eq!(tile_size, 32);
+    assert!(num_tiles(320, 240, tile_size) >= 4 * 16);
+    // But they are not made tiny to feed too many threads
+    assert_eq!(auto_tile_size(64, 64, 1000), 8);
+    assert!(available_threads() >= 1);
+}
+
+#[test]
+fn image_does_not_depend_on_the_tiles_nor_the_threads() {
+    let render = |tile_size, num_threads| {
+        let mut scene = example_scenes::three_balls();
+        scene.camera.aspect_ratio = 1.5;
+        let params = RenderParams {
+            width: 24,
+            height: 16,
+            num_samples: 4,
+            filter: Filter::Tent {radius: 1.0},
+            max_bounce: 4,
+            tile_size,
+            num_threads,
+            seed: 7,
+            ..RenderParams::default()
+        };
+        render_image(&scene, &params).unwrap().color.into_raw()
+    };
+    let reference = render(8, 1);
+    assert_eq!(render(16, 3), reference);
+    assert_eq!(render(5, 2), reference);
+    // No thread at all still gets one worker, rather than a black image
+    assert_eq!(render(8, 0), reference);
+}
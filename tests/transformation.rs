@@ -0,0 +1,150 @@
+use raytracing2::utility::*;
+use raytracing2::randomness::*;
+
+const TOLERANCE: Real = 1e-12;
+
+fn assert_close(a: &Rvec3, b: &Rvec3) {
+    assert!((a - b).norm() < TOLERANCE, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn axis_angle_and_euler_agree() {
+    let point = vector![1.0, 2.0, 3.0];
+    let axis_angle = Rotation::AxisAngle {axis: vector![0.0, 0.0, 2.0], angle: FRAC_PI_2};
+    let euler = Rotation::Euler {x: 0.0, y: 0.0, z: FRAC_PI_2};
+    assert_close(&(axis_angle.matrix() * point), &vector![-2.0, 1.0, 3.0]);
+    assert_close(&(euler.matrix() * point), &vector![-2.0, 1.0, 3.0]);
+}
+
+#[test]
+fn euler_angles_apply_x_then_y_then_z() {
+    let euler = Rotation::Euler {x: 0.3, y: -1.1, z: 2.0};
+    let x = Rotation::AxisAngle {axis: vector![1.0, 0.0, 0.0], angle: 0.3}.matrix();
+    let y = Rotation::AxisAngle {axis: vector![0.0, 1.0, 0.0], angle: -1.1}.matrix();
+    let z = Rotation::AxisAngle {axis: vector![0.0, 0.0, 1.0], angle: 2.0}.matrix();
+    assert!((euler.matrix() - z * y * x).norm() < TOLERANCE);
+}
+
+#[test]
+fn rotated_and_scaled_point_and_normal() {
+    // Scale by 2 along X, turn a quarter around Z, then move up
+    let transformation = Transformation::from_trs(
+        &vector![0.0, 0.0, 5.0],
+        &Rotation::AxisAngle {axis: vector![0.0, 0.0, 1.0], angle: FRAC_PI_2},
+        &vector![2.0, 1.0, 1.0]
+    );
+    assert_close(&transformation.transform_point(&vector![1.0, 1.0, 0.0]), &vector![-1.0, 2.0, 5.0]);
+
+    // The plane x + y = 1 becomes the plane through (0, 2) and (-1, 0), whose normal is along (-2, 1)
+    let normal = transformation.transform_normal(&vector![1.0, 1.0, 0.0].normalize());
+    assert_close(&normal, &vector![-2.0, 1.0, 0.0].normalize());
+    let along_plane = transformation.transform_vector(&vector![1.0, -1.0, 0.0]);
+    assert!(normal.dot(&along_plane).abs() < TOLERANCE);
+}
+
+#[test]
+fn inverse_undoes_a_scaled_transformation() {
+    let transformation = Transformation::from_trs(
+        &vector![1.0, -2.0, 3.0],
+        &Rotation::Euler {x: 0.4, y: 0.5, z: 0.6},
+        &vector![0.5, 2.0, 3.0]
+    );
+    let point = vector![0.7, 0.8, -0.9];
+    let round_trip = transformation.inverse().transform_point(&transformation.transform_point(&point));
+    assert_close(&round_trip, &point);
+}
+
+#[test]
+fn normals_stay_perpendicular_under_anisotropic_scale() {
+    let mut rng = Randomizer::seed_from_u64(5);
+    for _ in 0..100 {
+        let transformation = Transformation::from_trs(
+            &rng.sample(UnitBall),
+            &Rotation::AxisAngle {axis: rng.sample(UnitSphere), angle: TAU * rng.gen::<Real>()},
+            &vector![0.1 + 10.0 * rng.gen::<Real>(), 0.1 + rng.gen::<Real>(), 0.1 + 0.1 * rng.gen::<Real>()]
+        );
+        // A triangle and its normal, then the same after the transformation
+        let [a, b, c]: [Rvec3; 3] = [rng.sample(UnitBall), rng.sample(UnitBall), rng.sample(UnitBall)];
+        let normal = (b - a).cross(&(c - a)).normalize();
+        let [ta, tb, tc] = [a, b, c].map(|p| transformation.transform_point(&p));
+        let transformed_normal = transformation.transform_normal(&normal);
+
+        assert!((transformed_normal.norm() - 1.0).abs() < TOLERANCE);
+        assert!(transformed_normal.dot(&(tb - ta).normalize()).abs() < 1e-9);
+        assert!(transformed_normal.dot(&(tc - ta).normalize()).abs() < 1e-9);
+        // Still on the same side of the surface, for a transformation that does not mirror
+        assert!(transformed_normal.dot(&(tb - ta).cross(&(tc - ta))) > 0.0);
+    }
+}
+
+#[test]
+fn inverse_transforms_normals_back() {
+    let transformation = Transformation::from_trs(
+        &vector![1.0, 2.0, 3.0],
+        &Rotation::Euler {x: 1.0, y: 0.0, z: -0.5},
+        &vector![3.0, 0.2, 1.0]
+    );
+    let normal = vector![0.0, 0.6, 0.8];
+    let round_trip = transformation.inverse().transform_normal(&transformation.transform_normal(&normal));
+    assert_close(&round_trip, &normal);
+}
+
+#[test]
+fn ray_is_moved_and_turned_into_another_frame() {
+    let ray = Ray {
+        origin: vector![1.0, 0.0, 0.0],
+        direction: vector![1.0, 0.0, 0.0],
+        t_min: 0.5,
+        t_max: 7.0,
+        wavelength: Some(Wavelength::Green),
+        differentials: None,
+    };
+    // A quarter turn around Z, then a move up
+    let transformation = Transformation::from_trs(
+        &vector![0.0, 0.0, 3.0],
+        &Rotation::AxisAngle {axis: vector![0.0, 0.0, 1.0], angle: FRAC_PI_2},
+        &vector![1.0, 1.0, 1.0]
+    );
+    let moved = ray.transform(&transformation);
+    assert_close(&moved.origin, &vector![0.0, 1.0, 3.0]);
+    assert_close(&moved.direction, &vector![0.0, 1.0, 0.0]);
+    assert_eq!((moved.t_min, moved.t_max, moved.wavelength), (0.5, 7.0, Some(Wavelength::Green)));
+    assert_close(&moved.at(2.0), &transformation.transform_point(&ray.at(2.0)));
+
+    // With a scale, the direction is stretched so that the points along the ray keep their t
+    let scaled = ray.transform(&Transformation::from_trs(&Rvec3::zeros(), &Rotation::Euler {x: 0.0, y: 0.0, z: 0.0},
+        &vector![2.0, 2.0, 2.0]));
+    assert_close(&scaled.direction, &vector![2.0, 0.0, 0.0]);
+    assert_close(&scaled.at(2.0), &vector![6.0, 0.0, 0.0]);
+
+    // And the inverse brings it back
+    let back = moved.transform(&transformation.inverse());
+    assert_close(&back.origin, &ray.origin);
+    assert_close(&back.direction, &ray.direction);
+}
+
+#[test]
+fn composition_applies_the_right_transformation_first() {
+    let parent = Transformation::from_trs(
+        &vector![1.0, -2.0, 3.0],
+        &Rotation::Euler {x: 0.4, y: 0.5, z: 0.6},
+        &vector![0.5, 2.0, 3.0]
+    );
+    let child = Transformation::from_trs(
+        &vector![-0.3, 0.2, 0.7],
+        &Rotation::AxisAngle {axis: vector![1.0, 1.0, 0.0], angle: 1.2},
+        &vector![1.5, 0.25, 1.0]
+    );
+    let composed = parent.compose(&child);
+    let point = vector![0.7, 0.8, -0.9];
+    assert_close(&composed.transform_point(&point), &parent.transform_point(&child.transform_point(&point)));
+    let normal = vector![0.0, 0.6, 0.8];
+    assert_close(&composed.transform_normal(&normal), &parent.transform_normal(&child.transform_normal(&normal)));
+
+    // Composing with the inverse gives back the identity, on both sides
+    for identity in [parent.compose(&parent.inverse()), parent.inverse().compose(&parent)].iter() {
+        assert!((identity.linear() - Rmat3::identity()).norm() < TOLERANCE);
+        assert_close(identity.position(), &Rvec3::zeros());
+        assert_close(&identity.transform_normal(&normal), &normal);
+    }
+}
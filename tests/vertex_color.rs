@@ -0,0 +1,71 @@
+use raytracing2::hittable::Hittable;
+use raytracing2::material::*;
+use raytracing2::mesh::*;
+use raytracing2::randomness::*;
+use raytracing2::render::SceneData;
+use raytracing2::utility::*;
+
+#[test]
+fn vertex_colors_are_interpolated_at_the_hit() {
+    let normal = vector![0.0, 0.0, 1.0];
+    let uv = Rvec2::zeros();
+    let vertices = vec![
+        Vertex {position: vector![0.0, 0.0, 0.0], normal, uv, color: rgb(1.0, 0.0, 0.0)},
+        Vertex {position: vector![3.0, 0.0, 0.0], normal, uv, color: rgb(0.0, 1.0, 0.0)},
+        Vertex {position: vector![0.0, 3.0, 0.0], normal, uv, color: rgb(0.0, 0.0, 1.0)},
+    ];
+    let scene_data = SceneData {
+        material_table: vec![Material::new(Scatter::Lambert {two_sided: false}, Absorb::VertexColor, Emit::None)],
+        mesh_table: vec![Mesh::new(vertices, vec![0, 1, 2], MaterialId(0))],
+        ..SceneData::empty()
+    };
+    let root = Hittable::Triangle {triangle: TriangleId(0), mesh: MeshId(0)};
+
+    // Straight down on the centroid, which takes a third of each color
+    let ray = Ray {
+        origin: vector![1.0, 1.0, 1.0],
+        direction: vector![0.0, 0.0, -1.0],
+        t_min: RAY_EPSILON,
+        t_max: Real::INFINITY,
+        wavelength: None,
+        differentials: None,
+    };
+    let (hit, material) = root.hit(&ray, &scene_data).unwrap();
+    assert!((hit.color - rgb(1.0, 1.0, 1.0) / 3.0).norm() < 1e-12);
+
+    let mut rng = Randomizer::seed_from_u64(1);
+    let output = scene_data.material(material).evaluate(&ray, &hit, &scene_data, &mut rng, 0.0);
+    assert_eq!(output.absorb, hit.color);
+
+    // The other surfaces are white
+    let sphere = Hittable::Sphere {center: Rvec3::zeros(), radius: 0.5, material: MaterialId(0)};
+    let (hit, _) = sphere.hit(&Ray {origin: vector![0.0, 0.0, 1.0], ..ray}, &scene_data).unwrap();
+    assert_eq!(hit.color, WHITE);
+}
+
+#[test]
+fn loaders_read_the_vertex_colors() {
+    let path = |extension: &str| {
+        std::env::temp_dir().join(format!("raytracing2_vertex_color_{}.{}", std::process::id(), extension))
+    };
+
+    // The colors after the positions are a common extension of the obj format, the vertices without stay white
+    let obj_path = path("obj");
+    std::fs::write(&obj_path, "v 0 0 0 1 0 0\nv 1 0 0 0 0.5 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+    let obj_mesh = obj::load(obj_path.to_str().unwrap());
+    std::fs::remove_file(&obj_path).unwrap();
+    let colors: Vec<Color> = obj_mesh.unwrap().vertices().iter().map(|v| v.color).collect();
+    assert_eq!(colors, vec![rgb(1.0, 0.0, 0.0), rgb(0.0, 0.5, 0.0), WHITE]);
+
+    // The integer channels of a ply go up to their largest value
+    let ply_path = path("ply");
+    let ply = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+        property uchar red\nproperty uchar green\nproperty uchar blue\nelement face 1\n\
+        property list uchar int vertex_indices\nend_header\n\
+        0 0 0 255 0 0\n1 0 0 0 255 0\n0 1 0 0 0 51\n3 0 1 2\n";
+    std::fs::write(&ply_path, ply).unwrap();
+    let ply_mesh = ply::load(ply_path.to_str().unwrap());
+    std::fs::remove_file(&ply_path).unwrap();
+    let colors: Vec<Color> = ply_mesh.unwrap().vertices().iter().map(|v| v.color).collect();
+    assert_eq!(colors, vec![rgb(1.0, 0.0, 0.0), rgb(0.0, 1.0, 0.0), rgb(0.0, 0.0, 0.2)]);
+}